@@ -12,6 +12,7 @@ pub struct Model {
     pub mc_board_name: String,
     pub menu_id:       i32,
     pub category_id:   i32,
+    pub archived:      bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -9,6 +9,7 @@ pub struct Model {
     pub id:        i32,
     pub save_path: String,
     pub url:       String,
+    pub size:      i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -2,10 +2,18 @@
 
 pub mod prelude;
 
+pub mod account;
 pub mod board;
+pub mod board_account;
 pub mod board_bookmark;
+pub mod board_identity;
 pub mod category;
+pub mod draft;
+pub mod identity;
 pub mod image;
 pub mod menu;
+pub mod my_post;
+pub mod post_history;
+pub mod post_queue;
 pub mod thread;
 pub mod thread_post;
@@ -1,11 +1,19 @@
 //! SeaORM Entity. Generated by sea-orm-codegen 0.9.1
 
 pub use super::{
+    account::Entity as Account,
     board::Entity as Board,
+    board_account::Entity as BoardAccount,
     board_bookmark::Entity as BoardBookmark,
+    board_identity::Entity as BoardIdentity,
     category::Entity as Category,
+    draft::Entity as Draft,
+    identity::Entity as Identity,
     image::Entity as Image,
     menu::Entity as Menu,
+    my_post::Entity as MyPost,
+    post_history::Entity as PostHistory,
+    post_queue::Entity as PostQueue,
     thread::Entity as Thread,
     thread_post::Entity as ThreadPost,
 };
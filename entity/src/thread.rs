@@ -6,17 +6,23 @@ use sea_orm::entity::prelude::*;
 #[sea_orm(table_name = "thread")]
 pub struct Model {
     #[sea_orm(primary_key)]
-    pub id:           i32,
-    pub index:        i32,
-    pub name:         String,
-    pub url:          String,
-    pub count:        i32,
-    pub ikioi:        Option<f64>,
-    pub stopdone:     bool,
-    pub is_read:      bool,
-    pub before_read:  i32,
-    pub created_time: Option<i64>,
-    pub board_id:     i32,
+    pub id:              i32,
+    pub index:           i32,
+    pub name:            String,
+    pub url:             String,
+    pub count:           i32,
+    pub ikioi:           Option<f64>,
+    pub stopdone:        bool,
+    pub is_read:         bool,
+    pub before_read:     i32,
+    pub created_time:    Option<i64>,
+    pub board_id:        i32,
+    pub last_read_index: i32,
+    pub scroll_offset:   i32,
+    pub archived:        bool,
+    pub archived_at:     i64,
+    pub fetched_at:      i64,
+    pub is_new:          bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -29,6 +35,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Board,
+    #[sea_orm(has_many = "super::my_post::Entity")]
+    MyPost,
     #[sea_orm(has_many = "super::thread_post::Entity")]
     ThreadPost,
 }
@@ -39,6 +47,12 @@ impl Related<super::board::Entity> for Entity {
     }
 }
 
+impl Related<super::my_post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MyPost.def()
+    }
+}
+
 impl Related<super::thread_post::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ThreadPost.def()
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::{Board, Thread};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLiteはALTER TABLEで複数のカラムを一度に追加できないので分ける。
+        add_column(
+            manager,
+            Board::Table,
+            ColumnDef::new(Board::Archived)
+                .boolean()
+                .not_null()
+                .default(false),
+        )
+        .await?;
+        add_column(
+            manager,
+            Thread::Table,
+            ColumnDef::new(Thread::Archived)
+                .boolean()
+                .not_null()
+                .default(false),
+        )
+        .await?;
+        add_column(
+            manager,
+            Thread::Table,
+            ColumnDef::new(Thread::ArchivedAt)
+                .big_integer()
+                .not_null()
+                .default(0),
+        )
+        .await?;
+        add_column(
+            manager,
+            Thread::Table,
+            ColumnDef::new(Thread::FetchedAt)
+                .big_integer()
+                .not_null()
+                .default(0),
+        )
+        .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_column(manager, Thread::Table, Thread::FetchedAt).await?;
+        drop_column(manager, Thread::Table, Thread::ArchivedAt).await?;
+        drop_column(manager, Thread::Table, Thread::Archived).await?;
+        drop_column(manager, Board::Table, Board::Archived).await
+    }
+}
+
+async fn add_column(
+    manager: &SchemaManager<'_>,
+    table: impl Iden + 'static,
+    column: &mut ColumnDef,
+) -> Result<(), DbErr> {
+    manager
+        .alter_table(Table::alter().table(table).add_column(column).to_owned())
+        .await
+}
+
+async fn drop_column(
+    manager: &SchemaManager<'_>,
+    table: impl Iden + 'static,
+    column: impl Iden + 'static,
+) -> Result<(), DbErr> {
+    manager
+        .alter_table(Table::alter().table(table).drop_column(column).to_owned())
+        .await
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::Thread;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .add_column(
+                        ColumnDef::new(Thread::IsNew)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .drop_column(Thread::IsNew)
+                    .to_owned(),
+            )
+            .await
+    }
+}
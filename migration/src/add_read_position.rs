@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::Thread;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLiteはALTER TABLEで複数のカラムを一度に追加できないので分ける。
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .add_column(
+                        ColumnDef::new(Thread::LastReadIndex)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .add_column(
+                        ColumnDef::new(Thread::ScrollOffset)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .drop_column(Thread::ScrollOffset)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Thread::Table)
+                    .drop_column(Thread::LastReadIndex)
+                    .to_owned(),
+            )
+            .await
+    }
+}
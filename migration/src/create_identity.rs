@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::{BoardIdentity, Identity};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Identity::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Identity::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Identity::Label)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Identity::Name).string().not_null())
+                    .col(ColumnDef::new(Identity::Email).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(BoardIdentity::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(BoardIdentity::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BoardIdentity::BoardUrl)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(BoardIdentity::IdentityId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_board_identity_identity_id")
+                            .from(BoardIdentity::Table, BoardIdentity::IdentityId)
+                            .to(Identity::Table, Identity::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BoardIdentity::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Identity::Table).to_owned())
+            .await
+    }
+}
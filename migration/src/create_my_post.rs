@@ -0,0 +1,54 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::{MyPost, Thread};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MyPost::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MyPost::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MyPost::BoardUrl).string().not_null())
+                    .col(ColumnDef::new(MyPost::ThreadUrl).string())
+                    .col(ColumnDef::new(MyPost::ThreadId).integer())
+                    .col(ColumnDef::new(MyPost::Subject).string())
+                    .col(ColumnDef::new(MyPost::PostIndex).integer())
+                    .col(ColumnDef::new(MyPost::Name).string().not_null())
+                    .col(ColumnDef::new(MyPost::Email).string().not_null())
+                    .col(ColumnDef::new(MyPost::Message).string().not_null())
+                    .col(ColumnDef::new(MyPost::PostedAt).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(MyPost::AfterCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk_my_post_thread_id")
+                            .from(MyPost::Table, MyPost::ThreadId)
+                            .to(Thread::Table, Thread::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MyPost::Table).to_owned())
+            .await
+    }
+}
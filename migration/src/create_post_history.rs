@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::{Draft, PostHistory};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Draft::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Draft::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Draft::Url).string().not_null().unique_key())
+                    .col(ColumnDef::new(Draft::Name).string().not_null())
+                    .col(ColumnDef::new(Draft::Email).string().not_null())
+                    .col(ColumnDef::new(Draft::Subject).string())
+                    .col(ColumnDef::new(Draft::Message).string().not_null())
+                    .col(ColumnDef::new(Draft::UpdatedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostHistory::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PostHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostHistory::Url).string().not_null())
+                    .col(ColumnDef::new(PostHistory::IsThread).boolean().not_null())
+                    .col(ColumnDef::new(PostHistory::Name).string().not_null())
+                    .col(ColumnDef::new(PostHistory::Email).string().not_null())
+                    .col(ColumnDef::new(PostHistory::Subject).string())
+                    .col(ColumnDef::new(PostHistory::Message).string().not_null())
+                    .col(ColumnDef::new(PostHistory::Success).boolean().not_null())
+                    .col(ColumnDef::new(PostHistory::Reason).string().not_null())
+                    .col(
+                        ColumnDef::new(PostHistory::PostedAt)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostHistory::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Draft::Table).to_owned())
+            .await
+    }
+}
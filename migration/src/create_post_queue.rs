@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+use crate::table::PostQueue;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PostQueue::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PostQueue::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PostQueue::Url).string().not_null())
+                    .col(ColumnDef::new(PostQueue::IsThread).boolean().not_null())
+                    .col(ColumnDef::new(PostQueue::Name).string().not_null())
+                    .col(ColumnDef::new(PostQueue::Email).string().not_null())
+                    .col(ColumnDef::new(PostQueue::Subject).string())
+                    .col(ColumnDef::new(PostQueue::Message).string().not_null())
+                    .col(ColumnDef::new(PostQueue::QueuedAt).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PostQueue::Table).to_owned())
+            .await
+    }
+}
@@ -1,4 +1,12 @@
 pub use sea_orm_migration::prelude::*;
+mod add_archive;
+mod add_is_new;
+mod add_read_position;
+mod create_account;
+mod create_identity;
+mod create_my_post;
+mod create_post_history;
+mod create_post_queue;
 mod create_table;
 mod table;
 
@@ -7,6 +15,16 @@ pub struct Migrator;
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(create_table::Migration)]
+        vec![
+            Box::new(create_table::Migration),
+            Box::new(add_read_position::Migration),
+            Box::new(create_my_post::Migration),
+            Box::new(create_post_history::Migration),
+            Box::new(create_identity::Migration),
+            Box::new(create_account::Migration),
+            Box::new(create_post_queue::Migration),
+            Box::new(add_archive::Migration),
+            Box::new(add_is_new::Migration),
+        ]
     }
 }
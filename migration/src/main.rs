@@ -1,19 +1,30 @@
+use std::path::PathBuf;
+
+use directories::ProjectDirs;
 use migration::Migrator;
 use sea_orm::Database;
 use sea_orm_migration::prelude::*;
 
-#[cfg(target_os = "linux")]
-const DATABASE_URL: &str = "sqlite:///var/tmp/termchan.db?mode=rwc";
-
-#[cfg(target_os = "macos")]
-const DATABASE_URL: &str = "sqlite:///var/tmp/termchan.db?mode=rwc";
-
-#[cfg(target_os = "windows")]
-const DATABASE_URL: &str = "sqlite:///C:\\Windows\\Temp\\termchan.db?mode=rwc";
+/// termchan本体と同じく、環境変数が無ければデータディレクトリ直下のDBを使う。
+fn database_url() -> String {
+    let path = match std::env::var_os("TERMCHAN_DB").filter(|p| !p.is_empty()) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            ProjectDirs::from("com", "haryoiro", "termchan")
+                .expect("Failed to get the data directory")
+                .data_dir()
+                .join("termchan.db")
+        }
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create the data directory");
+    }
+    format!("sqlite://{}?mode=rwc", path.display())
+}
 
 #[tokio::main]
 async fn main() {
-    let db = Database::connect(DATABASE_URL)
+    let db = Database::connect(database_url())
         .await
         .expect("Failed to setup the database");
 
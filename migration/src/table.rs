@@ -26,6 +26,8 @@ pub enum Board {
     MCBoardName,
     MenuId,
     CategoryId,
+    /// 板のスレを全てアーカイブする
+    Archived,
 }
 
 #[derive(Iden)]
@@ -53,6 +55,18 @@ pub enum Thread {
     /// ここまで読んだ
     BeforeRead,
     BoardId,
+    /// 最後に選択していたレスの位置
+    LastReadIndex,
+    /// 最後に表示していたスクロール位置
+    ScrollOffset,
+    /// 全てのレスと画像を保存し、整理で消さない
+    Archived,
+    /// 最後にレスと画像を全て保存した時刻
+    ArchivedAt,
+    /// 最後に取得した時刻 整理するときの基準
+    FetchedAt,
+    /// 前回スレ一覧を取得したときに無かった新しいスレ
+    IsNew,
 }
 
 #[derive(Iden)]
@@ -77,6 +91,135 @@ pub enum ThreadPost {
     /// スレッドID データベースのThreadテーブルに対応
     ThreadId,
 }
+/// 自分の書き込み
+#[derive(Iden)]
+pub enum MyPost {
+    Table,
+    Id,
+    /// 書き込んだ板のURL
+    BoardUrl,
+    /// 書き込んだスレッドのURL
+    /// スレ立ての場合は、スレッドが見つかるまでNULL
+    ThreadUrl,
+    /// スレッドID データベースのThreadテーブルに対応
+    ThreadId,
+    /// スレ立てしたときのタイトル
+    Subject,
+    /// 投稿番号 レスと照合できるまでNULL
+    PostIndex,
+    /// ユーザネーム トリップキーは含めない
+    Name,
+    /// sageとかageとか
+    Email,
+    /// 書き込んだ本文
+    Message,
+    /// 書き込んだ時刻 unix time
+    PostedAt,
+    /// 書き込んだ時点で取得済みだったレス数
+    /// 照合はこれより後のレスから行う
+    AfterCount,
+}
+
+/// 書きかけのフォーム
+#[derive(Iden)]
+pub enum Draft {
+    Table,
+    Id,
+    /// 書き込み先のスレッドか板のURL
+    Url,
+    Name,
+    Email,
+    /// スレ立てのときのタイトル
+    Subject,
+    Message,
+    /// unix time
+    UpdatedAt,
+}
+
+/// 書き込みの履歴 失敗したものも残す
+#[derive(Iden)]
+pub enum PostHistory {
+    Table,
+    Id,
+    /// 書き込み先のスレッドか板のURL
+    Url,
+    /// スレ立てかどうか
+    IsThread,
+    Name,
+    Email,
+    /// スレ立てのときのタイトル
+    Subject,
+    Message,
+    /// 書き込めたかどうか
+    Success,
+    /// 失敗したときのサーバーからの理由
+    Reason,
+    /// unix time
+    PostedAt,
+}
+
+/// オフラインのあいだに書いた、送信を待つ書き込み
+#[derive(Iden)]
+pub enum PostQueue {
+    Table,
+    Id,
+    /// 書き込み先のスレッドか板のURL
+    Url,
+    /// スレ立てかどうか
+    IsThread,
+    Name,
+    Email,
+    /// スレ立てのときのタイトル
+    Subject,
+    Message,
+    /// unix time
+    QueuedAt,
+}
+
+/// 書き込みに使う名前とメール欄の組
+/// トリップキーはキーリングに保存する
+#[derive(Iden)]
+pub enum Identity {
+    Table,
+    Id,
+    /// 一覧に表示する名前
+    Label,
+    /// ユーザネーム トリップキーは含めない
+    Name,
+    /// sageとかageとか
+    Email,
+}
+
+/// 板ごとに使う名前
+#[derive(Iden)]
+pub enum BoardIdentity {
+    Table,
+    Id,
+    BoardUrl,
+    IdentityId,
+}
+
+/// 5chのアカウント
+/// メールアドレスとパスワード、セッションのCookieはキーリングに保存する
+#[derive(Iden)]
+pub enum Account {
+    Table,
+    Id,
+    /// 一覧に表示する名前
+    Label,
+    /// 板ごとに選んでいないときに使うか
+    IsDefault,
+}
+
+/// 板ごとに使うアカウント
+#[derive(Iden)]
+pub enum BoardAccount {
+    Table,
+    Id,
+    BoardUrl,
+    AccountId,
+}
+
 #[derive(Iden)]
 pub enum Image {
     Table,
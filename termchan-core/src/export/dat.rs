@@ -0,0 +1,91 @@
+use super::{escape_html, format_date, ExportThread};
+use crate::{
+    get::message::{Message, Text},
+    util::encoding::{to_ncr, utf8_to_sjis_byte},
+};
+
+/// 一行に一レスの`名前<>メール<>日付 ID<>本文<>スレタイ`にする。
+/// スレタイは一行目にだけ入れる。Shift_JISで表せない文字は数値文字参照にする。
+pub fn to_dat(thread: &ExportThread) -> Vec<u8> {
+    let mut dat = String::new();
+    for (number, post) in thread.numbered_posts() {
+        let title = if number == 1 {
+            thread.title.as_str()
+        } else {
+            ""
+        };
+        dat.push_str(&format!(
+            "{}<>{}<>{} {}<>{}<>{}\n",
+            field(&post.name),
+            field(&post.email),
+            format_date(post.date),
+            field(&post.post_id),
+            message_to_dat(&post.message),
+            field(title)
+        ));
+    }
+    utf8_to_sjis_byte(&to_ncr(&dat))
+}
+
+/// 区切りの`<>`と改行が入らないようにする。
+fn field(s: &str) -> String {
+    s.replace("<>", "&lt;&gt;").replace('\n', " ")
+}
+
+/// 本文を`parse_dat`で読める元のHTMLに戻す。
+pub fn message_to_dat(message: &Message) -> String {
+    message.text.iter().map(text_to_dat).collect()
+}
+
+fn text_to_dat(text: &Text) -> String {
+    match text {
+        Text::Plain(text) => escape_html(text),
+        Text::Link(url) | Text::Image(url) => url.clone(),
+        Text::Anchors(anchors) => {
+            let anchors = anchors
+                .iter()
+                .map(|anchor| text_to_dat(anchor))
+                .collect::<Vec<_>>();
+            format!("&gt;&gt;{}", anchors.join(","))
+        }
+        Text::AnchorRange(start, end) => format!("{}-{}", start, end),
+        Text::Anchor(number) => number.to_string(),
+        Text::NewLine => "<br>".to_string(),
+        Text::Space => " ".to_string(),
+        Text::End => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get::thread::parse_dat, util::encoding::sjis_to_utf8};
+
+    #[test]
+    fn test_to_dat() {
+        let url = "https://egg.example.net/news4vip/dat/1234567890.dat";
+        let source = concat!(
+            "名無し<>sage<>2022/07/26(火) 14:18:49 ID:AbCd<>テスト &amp; &lt;b&gt; &#128512;<br>",
+            "https://example.com/a.png<>スレタイ\n",
+            "<b>名無し</b><><>2022/07/26(火) 14:19:00 ID:EfGh<>&gt;&gt;1 &gt;&gt;1-2,3 &gt;test<>\n",
+        );
+        let before = parse_dat(source, url).unwrap();
+        let thread = ExportThread {
+            title: before.detail.title.clone(),
+            posts: before.posts.clone(),
+            ..Default::default()
+        };
+        let after = parse_dat(&sjis_to_utf8(&to_dat(&thread)), url).unwrap();
+
+        assert_eq!(after.detail.title, "スレタイ");
+        assert_eq!(after.posts.len(), 2);
+        for (a, b) in before.posts.iter().zip(after.posts.iter()) {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.email, b.email);
+            assert_eq!(a.date, b.date);
+            assert_eq!(a.post_id, b.post_id);
+            assert_eq!(a.message.text, b.message.text);
+        }
+        assert!(after.posts[0].message.to_string().contains("😀"));
+    }
+}
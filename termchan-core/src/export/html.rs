@@ -0,0 +1,98 @@
+use super::{escape_html, format_date, ExportThread};
+use crate::get::message::{Message, Text};
+
+const STYLE: &str = "body { max-width: 50em; margin: 0 auto; padding: 1em; line-height: 1.6; }
+dt { margin-top: 1em; color: #555; }
+dt .name { color: #228b22; font-weight: bold; }
+dd { margin-left: 1em; }
+img { max-width: 100%; max-height: 20em; }";
+
+/// 一つのファイルで読めるHTMLにする。アンカーはそのレスへのリンクになる。
+pub fn to_html(thread: &ExportThread) -> String {
+    let title = escape_html(&thread.title);
+    let url = escape_html(&thread.url);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p><a href=\"{url}\">{url}</a></p>\n<dl>\n"
+    );
+    for (number, post) in thread.numbered_posts() {
+        html.push_str(&format!(
+            "<dt id=\"r{}\">{} <span class=\"name\">{}</span> [{}] {} {}</dt>\n<dd>{}</dd>\n",
+            number,
+            number,
+            escape_html(&post.name),
+            escape_html(&post.email),
+            format_date(post.date),
+            escape_html(&post.post_id),
+            message_to_html(&post.message, thread)
+        ));
+    }
+    html.push_str("</dl>\n</body>\n</html>\n");
+    html
+}
+
+fn message_to_html(message: &Message, thread: &ExportThread) -> String {
+    message
+        .text
+        .iter()
+        .map(|text| text_to_html(text, thread))
+        .collect()
+}
+
+fn text_to_html(text: &Text, thread: &ExportThread) -> String {
+    match text {
+        Text::Plain(text) => escape_html(text),
+        Text::Link(url) => {
+            let url = escape_html(url);
+            format!("<a href=\"{}\">{}</a>", url, url)
+        }
+        Text::Image(url) => {
+            let src = thread.images.get(url).unwrap_or(url);
+            format!(
+                "<a href=\"{}\"><img src=\"{}\" alt=\"\"></a>",
+                escape_html(url),
+                escape_html(src)
+            )
+        }
+        Text::Anchors(anchors) => {
+            let anchors = anchors
+                .iter()
+                .map(|anchor| text_to_html(anchor, thread))
+                .collect::<Vec<_>>();
+            format!("&gt;&gt;{}", anchors.join(","))
+        }
+        Text::AnchorRange(start, end) => format!("<a href=\"#r{}\">{}-{}</a>", start, start, end),
+        Text::Anchor(number) => format!("<a href=\"#r{}\">{}</a>", number, number),
+        Text::NewLine => "<br>\n".to_string(),
+        Text::Space => " ".to_string(),
+        Text::End => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get::thread::ThreadPost;
+
+    #[test]
+    fn test_to_html() {
+        let mut thread = ExportThread {
+            title: "<スレ>".to_string(),
+            posts: vec![ThreadPost {
+                name: "名無し".to_string(),
+                message: Message::new("&gt;&gt;1<br>https://example.com/a.png"),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        thread
+            .images
+            .insert("https://example.com/a.png".to_string(), "a.png".to_string());
+        let html = to_html(&thread);
+        assert!(html.contains("<title>&lt;スレ&gt;</title>"));
+        assert!(html.contains("<dt id=\"r1\">1 "));
+        assert!(html.contains("&gt;&gt;<a href=\"#r1\">1</a>"));
+        assert!(html.contains("<img src=\"a.png\""));
+    }
+}
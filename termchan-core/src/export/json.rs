@@ -0,0 +1,29 @@
+use serde_json::json;
+
+use super::{format_date, plain_message, ExportThread};
+
+/// レスごとに本文とアンカー先、画像のURLを持つJSONにする。
+pub fn to_json(thread: &ExportThread) -> String {
+    let posts = thread
+        .numbered_posts()
+        .map(|(number, post)| {
+            json!({
+                "number": number,
+                "name": post.name,
+                "mail": post.email,
+                "date": format_date(post.date),
+                "timestamp": post.date,
+                "id": post.post_id,
+                "message": plain_message(&post.message),
+                "anchors": post.message.anchor_indexes(),
+                "images": post.message.images,
+            })
+        })
+        .collect::<Vec<_>>();
+    let thread = json!({
+        "title": thread.title,
+        "url": thread.url,
+        "posts": posts,
+    });
+    serde_json::to_string_pretty(&thread).unwrap_or_default() + "\n"
+}
@@ -0,0 +1,64 @@
+use super::{format_date, ExportThread};
+use crate::get::message::{Message, Text};
+
+/// 見出しとレスの並びにする。アンカーはそのレスへのリンクになる。
+pub fn to_markdown(thread: &ExportThread) -> String {
+    let mut markdown = format!("# {}\n\n<{}>\n", escape(&thread.title), thread.url);
+    for (number, post) in thread.numbered_posts() {
+        markdown.push_str(&format!(
+            "\n---\n\n<a id=\"r{}\"></a>**{}** {} [{}] {} {}\n\n{}\n",
+            number,
+            number,
+            escape(&post.name),
+            escape(&post.email),
+            format_date(post.date),
+            escape(&post.post_id),
+            message_to_markdown(&post.message, thread).trim_end()
+        ));
+    }
+    markdown
+}
+
+/// Markdownの記号として読まれないようにする。
+fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn message_to_markdown(message: &Message, thread: &ExportThread) -> String {
+    message
+        .text
+        .iter()
+        .map(|text| text_to_markdown(text, thread))
+        .collect()
+}
+
+fn text_to_markdown(text: &Text, thread: &ExportThread) -> String {
+    match text {
+        Text::Plain(text) => escape(text),
+        Text::Link(url) => format!("<{}>", url),
+        Text::Image(url) => format!("![]({})", thread.images.get(url).unwrap_or(url)),
+        Text::Anchors(anchors) => {
+            let anchors = anchors
+                .iter()
+                .map(|anchor| text_to_markdown(anchor, thread))
+                .collect::<Vec<_>>();
+            format!("\\>\\>{}", anchors.join(","))
+        }
+        Text::AnchorRange(start, end) => format!("[{}-{}](#r{})", start, end, start),
+        Text::Anchor(number) => format!("[{}](#r{})", number, number),
+        // 行末に空白を二つ置いて、段落を分けずに改行する。
+        Text::NewLine => "  \n".to_string(),
+        Text::Space => " ".to_string(),
+        Text::End => String::new(),
+    }
+}
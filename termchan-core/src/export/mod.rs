@@ -0,0 +1,151 @@
+pub mod dat;
+pub mod html;
+pub mod json;
+pub mod markdown;
+pub mod text;
+
+use std::{collections::HashMap, fmt::Display};
+
+use chrono::{DateTime, Datelike, Utc};
+
+use crate::get::{
+    message::{Message, Text},
+    thread::ThreadPost,
+};
+
+/// 書き出す形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dat,
+    Html,
+    Markdown,
+    Text,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 5] = [
+        ExportFormat::Dat,
+        ExportFormat::Html,
+        ExportFormat::Markdown,
+        ExportFormat::Text,
+        ExportFormat::Json,
+    ];
+
+    pub fn parse(s: &str) -> Option<ExportFormat> {
+        match s {
+            "dat" => Some(ExportFormat::Dat),
+            "html" => Some(ExportFormat::Html),
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "txt" | "text" => Some(ExportFormat::Text),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// 書き出すファイルの拡張子
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Dat => "dat",
+            ExportFormat::Html => "html",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Text => "txt",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// 書き出すスレ
+#[derive(Debug, Clone, Default)]
+pub struct ExportThread {
+    pub title:  String,
+    pub url:    String,
+    /// レス番号の順に並べる
+    pub posts:  Vec<ThreadPost>,
+    /// 画像のURLと、保存したファイルのパス HTMLではこちらを表示する
+    pub images: HashMap<String, String>,
+}
+
+impl ExportThread {
+    /// レス番号と一緒に返す。
+    /// datとjsonでindexの数え方が違うので、並び順から1始まりで振り直す。
+    pub fn numbered_posts(&self) -> impl Iterator<Item = (usize, &ThreadPost)> {
+        self.posts.iter().enumerate().map(|(i, post)| (i + 1, post))
+    }
+}
+
+/// 形式に合わせて書き出す。DATだけはShift_JISになる。
+pub fn export(thread: &ExportThread, format: ExportFormat) -> Vec<u8> {
+    match format {
+        ExportFormat::Dat => dat::to_dat(thread),
+        ExportFormat::Html => html::to_html(thread).into_bytes(),
+        ExportFormat::Markdown => markdown::to_markdown(thread).into_bytes(),
+        ExportFormat::Text => text::to_text(thread).into_bytes(),
+        ExportFormat::Json => json::to_json(thread).into_bytes(),
+    }
+}
+
+/// HTMLとして書き出すときに、本文や名前の特殊な文字を実体参照にする。
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 本文を装飾の無いテキストにする。`Display`と違い、アンカーに`>>`を付ける。
+pub fn plain_message(message: &Message) -> String {
+    message
+        .text
+        .iter()
+        .map(|text| {
+            match text {
+                Text::Anchors(..) => format!(">>{}", text),
+                text => text.to_string(),
+            }
+        })
+        .collect()
+}
+
+const WEEKDAYS: [&str; 7] = ["月", "火", "水", "木", "金", "土", "日"];
+
+/// 掲示板と同じ`2022/01/01(土) 00:00:00`の形にする。
+/// 取得した日時は日本時間のままUTCとして保存しているので、そのまま書式にする。
+/// 日時が分からないレスは空にする。
+pub fn format_date(date: i64) -> String {
+    match DateTime::<Utc>::from_timestamp(date, 0) {
+        Some(d) if date != 0 => {
+            format!(
+                "{}({}) {}",
+                d.format("%Y/%m/%d"),
+                WEEKDAYS[d.weekday().num_days_from_monday() as usize],
+                d.format("%H:%M:%S")
+            )
+        }
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::time::decode_japan_date;
+
+    #[test]
+    fn test_format_date() {
+        let date = decode_japan_date("2022/07/26(火) 14:18:49").unwrap();
+        assert_eq!(format_date(date), "2022/07/26(火) 14:18:49");
+        assert_eq!(format_date(0), "");
+        assert_eq!(ExportFormat::parse("md"), Some(ExportFormat::Markdown));
+        assert_eq!(
+            plain_message(&Message::new("&gt;&gt;1,2-3 a<br>b")),
+            ">>1,2-3 a\nb"
+        );
+    }
+}
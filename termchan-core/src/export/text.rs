@@ -0,0 +1,18 @@
+use super::{format_date, plain_message, ExportThread};
+
+/// 装飾の無いテキストにする。
+pub fn to_text(thread: &ExportThread) -> String {
+    let mut text = format!("{}\n{}\n", thread.title, thread.url);
+    for (number, post) in thread.numbered_posts() {
+        text.push_str(&format!(
+            "\n{} {} [{}] {} {}\n{}\n",
+            number,
+            post.name,
+            post.email,
+            format_date(post.date),
+            post.post_id,
+            plain_message(&post.message).trim_end()
+        ));
+    }
+    text
+}
@@ -39,6 +39,35 @@ impl Message {
     pub fn json_string(&self) -> String {
         serde_json::to_string(self).unwrap()
     }
+
+    /// アンカーで参照しているレス番号を全て返す。
+    /// `>>1-3`のような範囲は展開する。
+    pub fn anchor_indexes(&self) -> Vec<i32> {
+        let mut indexes = vec![];
+        for anchor in &self.anchors {
+            collect_anchor_indexes(anchor, &mut indexes);
+        }
+        indexes
+    }
+}
+
+/// 範囲アンカーを展開するときの上限
+const MAX_ANCHOR_RANGE: i32 = 1000;
+
+fn collect_anchor_indexes(text: &Text, indexes: &mut Vec<i32>) {
+    match text {
+        Text::Anchor(n) => indexes.push(*n),
+        Text::AnchorRange(start, end) => {
+            let end = (*end).min(start.saturating_add(MAX_ANCHOR_RANGE));
+            indexes.extend(*start..=end);
+        }
+        Text::Anchors(texts) => {
+            for text in texts {
+                collect_anchor_indexes(text, indexes);
+            }
+        }
+        _ => {}
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -169,28 +198,25 @@ where
                     self.next();
                     let mut anchors_tmp = vec![];
                     loop {
-                        if matches!(self.token, Some(Number(_))) {
-                            let mut n = 0;
-                            if let Some(Number(num)) = self.token {
-                                n = *num
-                            }
+                        if let Some(Number(n)) = self.token {
+                            let n = *n as i32;
                             self.next();
 
                             if let Some(Hyphen) = self.token {
                                 self.next();
 
                                 if let Some(Number(m)) = self.token {
-                                    anchors_tmp
-                                        .push(Box::new(Text::AnchorRange(n as i32, *m as i32)));
+                                    anchors_tmp.push(Box::new(Text::AnchorRange(n, *m as i32)));
                                     self.next();
                                 } else {
-                                    anchors_tmp.push(Box::new(Text::Anchor(n as i32)));
+                                    anchors_tmp.push(Box::new(Text::Anchor(n)));
                                     break;
                                 }
+                            } else {
+                                anchors_tmp.push(Box::new(Text::Anchor(n)));
                             }
 
                             if let Some(Comma) = self.token {
-                                anchors_tmp.push(Box::new(Text::Anchor(n as i32)));
                                 self.next();
                                 continue;
                             }
@@ -409,24 +435,37 @@ where
 
     /// エスケープされている特定の文字列を取得する。
     /// &から始まり、;で終わる。
+    /// 対応する;が無いときは&をそのまま返し、後続の文字は読み進めない。
     fn read_escaped_char(&mut self) -> Token {
-        let mut text = String::new();
-        text.push(self.ch);
-
-        self.read_char();
-        if !(self.ch.is_alphanumeric() && self.ch == '#') {
-            return Token::Char(self.ch);
+        let mut text = String::from("&");
+        let mut iter = self.input.clone();
+        let mut count = 0;
+        loop {
+            match iter.next().and_then(|s| s.chars().next()) {
+                Some(';') => {
+                    text.push(';');
+                    count += 1;
+                    break;
+                }
+                Some(ch) if (ch.is_ascii_alphanumeric() || ch == '#') && count < 10 => {
+                    text.push(ch);
+                    count += 1;
+                }
+                _ => return Token::Char('&'),
+            }
         }
-        text.push(self.ch);
 
-        while self.ch != ';' {
+        while count > 0 {
             self.read_char();
-            text.push(self.ch);
+            count -= 1;
         }
 
-        if text.starts_with("&#") {
-            let num = text[2..text.len() - 1].to_string().parse::<u32>().unwrap();
-            let c = char::from_u32(num);
+        if let Some(num) = text.strip_prefix("&#").and_then(|t| t.strip_suffix(';')) {
+            let c = match num.strip_prefix('x').or_else(|| num.strip_prefix('X')) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => num.parse::<u32>().ok(),
+            }
+            .and_then(char::from_u32);
             if let Some(c) = c {
                 return Token::Char(c);
             }
@@ -567,6 +606,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_anchors() {
+        let tests = vec![
+            ("&gt;&gt;1", vec![1]),
+            ("&gt;&gt;1,3-5", vec![1, 3, 4, 5]),
+            ("&gt;&gt;3-5,7 text", vec![3, 4, 5, 7]),
+            ("&gt;quote", vec![]),
+        ];
+        for (input, output) in tests {
+            assert_eq!(parse_msg(input).anchor_indexes(), output);
+        }
+    }
+
     #[test]
     fn test_parse_string() {
         let tests = vec![
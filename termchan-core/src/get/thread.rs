@@ -150,6 +150,9 @@ pub fn parse_dat<'a>(dat: &'a str, url: &'a str) -> Result<ThreadResponse> {
 
     for (i, line) in dat.lines().enumerate() {
         let line_split = line.split("<>").collect::<Vec<_>>();
+        if line_split.len() < 4 || (i == 0 && line_split.len() < 5) {
+            Err(eyre!("malformed dat line {}: {}", i + 1, line))?;
+        }
         let name = line_split[0];
         let email = line_split[1];
         let date_id = line_split[2];
@@ -187,10 +190,14 @@ pub fn parse_dat<'a>(dat: &'a str, url: &'a str) -> Result<ThreadResponse> {
         post.post_count_all = counter.get(&&post.post_id);
     }
 
-    let url = Url::from_str(url).unwrap();
-    let host = url.host_str().unwrap();
+    let url = Url::from_str(url).wrap_err_with(|| format!("invalid dat url {}", url))?;
+    let host = url.host_str().ok_or_else(|| eyre!("no host {}", url))?;
     let sub = host.split(".").collect::<Vec<_>>()[0].to_string();
-    let thread_id = url.path_segments().unwrap().last().unwrap().to_string();
+    let thread_id = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .ok_or_else(|| eyre!("no thread key {}", url))?
+        .to_string();
     let thread_id = thread_id.split('.').collect::<Vec<_>>()[0]
         .parse::<i64>()
         .unwrap_or(0);
@@ -359,6 +366,19 @@ struct ThreadJson {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_dat_malformed() {
+        let url = "https://mi.5ch.net/test/read.cgi/news4vip/1234567890";
+        let dat = "名無し<>sage<>22/01/01(土) 00:00:00 ID:AAAA<> 本文 <>スレタイ\n名無し<>sage";
+        assert!(parse_dat(dat, url).is_err());
+        // 一行目だけはスレタイが必要
+        assert!(parse_dat("名無し<>sage<>22/01/01(土) 00:00:00 ID:AAAA<> 本文 ", url).is_err());
+
+        let res = parse_dat(dat.lines().next().unwrap(), url).unwrap();
+        assert_eq!(res.detail.title, "スレタイ");
+        assert_eq!(res.posts.len(), 1);
+    }
+
     #[tokio::test]
     async fn get_json() {
         let url = "https://itest.\x35\x63\x68.net/public/newapi/client.php?subdomain=kizuna&board=pasta&dat=1569615752&rand=";
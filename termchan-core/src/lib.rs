@@ -1,3 +1,4 @@
+pub mod export;
 pub mod get;
 pub mod header;
 pub mod post;
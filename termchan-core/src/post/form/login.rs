@@ -1,6 +1,4 @@
-use std::collections::HashMap;
-
-use crate::util::encoding::utf8_to_sjis_string;
+use super::build_form;
 
 pub struct LoginFormData {
     pub pw:    String,
@@ -18,16 +16,17 @@ impl LoginFormData {
     }
 
     pub fn build(&self) -> String {
-        let mut form = HashMap::new();
-        form.insert("pw", &self.pw);
-        form.insert("em", &self.em);
-        form.insert("login", &self.login);
-        form.iter_mut()
-            .map(|(k, v)| (*k, utf8_to_sjis_string(v)))
-            .collect::<HashMap<_, _>>()
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<_>>()
-            .join("&")
+        build_form(&[("pw", &self.pw), ("em", &self.em), ("login", &self.login)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let form = LoginFormData::new("p&ss", "user@example.com").build();
+        assert_eq!(form, "pw=p%26ss&em=user%40example.com&login=");
     }
 }
@@ -1,3 +1,13 @@
 pub mod login;
 pub mod reply;
 pub mod thread;
+
+use crate::util::encoding::sjis_percent_encode;
+
+/// フォームの値をShift_JISでパーセントエンコードして、リクエストボディを組み立てる。
+fn build_form(form: &[(&str, &str)]) -> String {
+    form.iter()
+        .map(|(k, v)| format!("{}={}", k, sjis_percent_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -1,9 +1,5 @@
-use std::collections::HashMap;
-
-use crate::{
-    url::reply::ThreadParams,
-    util::{encoding::utf8_to_sjis_string, time::unix_now_time},
-};
+use super::build_form;
+use crate::{url::reply::ThreadParams, util::time::unix_now_time};
 
 pub struct ReplyFormData {
     name:    String,
@@ -39,22 +35,15 @@ impl ReplyFormData {
     }
 
     pub fn build(&self) -> String {
-        let mut form = HashMap::new();
-        form.insert("FORM", &self.name);
-        form.insert("mail", &self.mail);
-        form.insert("MESSAGE", &self.message);
-        form.insert("bbs", &self.bbs);
-        form.insert("key", &self.key);
-        form.insert("time", &self.time);
-        form.insert("submit", &self.submit);
-        form.insert("sid", &self.sid);
-
-        form.iter_mut()
-            .map(|(k, v)| (*k, utf8_to_sjis_string(v)))
-            .collect::<HashMap<_, _>>()
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<_>>()
-            .join("&")
+        build_form(&[
+            ("FROM", &self.name),
+            ("mail", &self.mail),
+            ("MESSAGE", &self.message),
+            ("bbs", &self.bbs),
+            ("key", &self.key),
+            ("time", &self.time),
+            ("submit", &self.submit),
+            ("sid", &self.sid),
+        ])
     }
 }
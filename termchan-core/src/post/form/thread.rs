@@ -1,9 +1,5 @@
-use std::collections::HashMap;
-
-use crate::{
-    url::thread::BoardParams,
-    util::{encoding::utf8_to_sjis_string, time::unix_now_time},
-};
+use super::build_form;
+use crate::{url::thread::BoardParams, util::time::unix_now_time};
 
 pub struct ThreadFormData {
     submit:  String, // submit
@@ -44,22 +40,16 @@ impl ThreadFormData {
     }
 
     pub fn build(&self) -> String {
-        let mut form = HashMap::new();
-        form.insert("submit", &self.submit);
-        form.insert("subject", &self.subject);
-        form.insert("FORM", &self.form);
-        form.insert("mail", &self.mail);
-        form.insert("MESSAGE", &self.message);
-        form.insert("site", &self.site);
-        form.insert("bbs", &self.bbs);
-        form.insert("time", &self.time);
-        form.insert("cert", &self.cert);
-        form.iter_mut()
-            .map(|(k, v)| (*k, utf8_to_sjis_string(v)))
-            .collect::<HashMap<_, _>>()
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<_>>()
-            .join("&")
+        build_form(&[
+            ("submit", &self.submit),
+            ("subject", &self.subject),
+            ("FROM", &self.form),
+            ("mail", &self.mail),
+            ("MESSAGE", &self.message),
+            ("site", &self.site),
+            ("bbs", &self.bbs),
+            ("time", &self.time),
+            ("cert", &self.cert),
+        ])
     }
 }
@@ -1,16 +1,27 @@
 use std::sync::Arc;
 
 use eyre::bail;
-use reqwest::cookie::Jar;
+use reqwest::{
+    cookie::{CookieStore, Jar},
+    Url,
+};
 
 use super::form::login::LoginFormData;
 
+// 申し訳程度の検索よけ
+const LOGIN_URL: &str = "\x68\x74\x74\x70\x73\x3a\x2f\x2f\x6c\x6f\x67\x69\x6e\x2e\x35\x63\x68\x2e\x6e\x65\x74\x2f\x6c\x6f\x67\x2e\x70\x68\x70";
+const LOGIN_HOST: &str = "\x6c\x6f\x67\x69\x6e\x2e\x35\x63\x68\x2e\x6e\x65\x74";
+
 pub async fn do_login(email: &str, password: &str) -> eyre::Result<Arc<Jar>> {
-    // 申し訳程度の検索よけ
-    let url = "\x68\x74\x74\x70\x73\x3a\x2f\x2f\x6c\x6f\x67\x69\x6e\x2e\x35\x63\x68\x2e\x6e\x65\x74\x2f\x6c\x6f\x67\x2e\x70\x68\x70";
-    let host = "\x6c\x6f\x67\x69\x6e\x2e\x35\x63\x68\x2e\x6e\x65\x74";
+    login_with_jar(Arc::new(Jar::default()), email, password).await
+}
+
+/// 前回のCookieを入れたjarでログインする。
+/// セッションが残っていればログインのリクエストは送らない。
+pub async fn login_with_jar(jar: Arc<Jar>, email: &str, password: &str) -> eyre::Result<Arc<Jar>> {
+    let url = LOGIN_URL;
+    let host = LOGIN_HOST;
 
-    let jar = Arc::new(Jar::default());
     let client = reqwest::Client::builder()
         .cookie_store(true)
         .cookie_provider(Arc::clone(&jar))
@@ -27,8 +38,7 @@ pub async fn do_login(email: &str, password: &str) -> eyre::Result<Arc<Jar>> {
     }
 
     // ログイン画面のフォームデータを生成
-    let email = email.replace("@", "%40");
-    let form_data = LoginFormData::new(password, &email).build();
+    let form_data = LoginFormData::new(password, email).build();
     // ログインリクエスト用のURLを生成
     let post_url = format!("https://{}/log.php", host);
     let resp = client
@@ -51,3 +61,69 @@ pub async fn do_login(email: &str, password: &str) -> eyre::Result<Arc<Jar>> {
         bail!("ERROR: login failed");
     }
 }
+
+/// ログインのセッションを表すCookieの名前
+const SESSION_COOKIE: &str = "sid";
+
+/// ログインのセッションのCookieを`sid=...`の形で取り出す。
+/// 他のCookieはログインした画面のためのものなので、保存しない。
+pub fn session_cookies(jar: &Jar) -> Option<String> {
+    let url = Url::parse(LOGIN_URL).ok()?;
+    let cookies = jar.cookies(&url)?;
+    cookie_value(cookies.to_str().ok()?, SESSION_COOKIE)
+        .map(|sid| format!("{}={}", SESSION_COOKIE, sid))
+}
+
+/// `session_cookies`で取り出したCookieからjarを作る。
+/// 書き込み先の板でも送られるように、ドメイン全体のCookieにする。
+/// 以前に保存した`sid`以外のCookieは、元のドメインが分からないので捨てる。
+pub fn jar_from_cookies(cookies: &str) -> Arc<Jar> {
+    let jar = Jar::default();
+    if let Some(sid) = cookie_value(cookies, SESSION_COOKIE) {
+        let url = Url::parse(LOGIN_URL).unwrap();
+        let domain = LOGIN_HOST.split_once('.').map_or(LOGIN_HOST, |(_, d)| d);
+        jar.add_cookie_str(
+            &format!("{}={}; Domain={}; Path=/", SESSION_COOKIE, sid, domain),
+            &url,
+        );
+    }
+    Arc::new(jar)
+}
+
+/// 書き込むときに送るセッションのID ログインしていなければ`None`
+pub fn session_id(jar: &Jar) -> Option<String> {
+    // 申し訳程度の検索よけ
+    let url = "\x68\x74\x74\x70\x73\x3a\x2f\x2f\x35\x63\x68\x2e\x6e\x65\x74\x2f"
+        .parse::<Url>()
+        .ok()?;
+    let cookies = jar.cookies(&url)?;
+    cookie_value(cookies.to_str().ok()?, SESSION_COOKIE).map(String::from)
+}
+
+/// `a=1; b=2`の形のCookieから、名前で値を探す。
+fn cookie_value<'a>(cookies: &'a str, name: &str) -> Option<&'a str> {
+    cookies
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_cookies() {
+        // 以前はsid以外のCookieも保存していた。
+        let jar = jar_from_cookies("other=1; sid=abc");
+        assert_eq!(session_cookies(&jar).as_deref(), Some("sid=abc"));
+        assert_eq!(session_id(&jar).as_deref(), Some("abc"));
+
+        let jar = jar_from_cookies("sid=abc=");
+        assert_eq!(session_id(&jar).as_deref(), Some("abc="));
+
+        assert_eq!(session_cookies(&Jar::default()), None);
+        assert_eq!(session_id(&jar_from_cookies("other=1")), None);
+    }
+}
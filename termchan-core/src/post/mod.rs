@@ -1,4 +1,7 @@
 pub mod form;
 pub mod login;
+pub mod preview;
+pub mod quote;
 pub mod reply;
 pub mod thread;
+pub mod validate;
@@ -0,0 +1,43 @@
+use crate::get::message::{parse_msg, Message};
+
+/// 本文をサーバーがdatに書き出すのと同じ形にエスケープする。
+/// HTMLの特殊文字を実体参照にし、改行を`<br>`にする。
+pub fn escape_message(message: &str) -> String {
+    let mut escaped = String::with_capacity(message.len());
+    for c in message.trim_end_matches('\n').chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str(" <br> "),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 書き込む前の本文を、取得したレスと同じように解析する。
+pub fn preview_message(message: &str) -> Message {
+    parse_msg(&escape_message(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get::message::Text;
+
+    #[test]
+    fn test_preview_message() {
+        assert_eq!(
+            escape_message("a<b>&\"\nc\n"),
+            "a&lt;b&gt;&amp;&quot; <br> c"
+        );
+
+        let message = preview_message(">>1\n>>2-3 <test>");
+        assert_eq!(message.anchor_indexes(), vec![1, 2, 3]);
+        assert!(message.text.contains(&Text::NewLine));
+        assert!(message.to_string().contains("<test>"));
+    }
+}
@@ -0,0 +1,66 @@
+/// レス番号の一覧から`>>1-3,5`の形のアンカーを作る。
+/// 番号は並べ替え、連続する番号は範囲にまとめる。
+pub fn format_anchors(indexes: &[i32]) -> String {
+    let mut indexes = indexes.to_vec();
+    indexes.sort_unstable();
+    indexes.dedup();
+
+    let mut ranges: Vec<(i32, i32)> = vec![];
+    for index in indexes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+    if ranges.is_empty() {
+        return String::new();
+    }
+
+    let anchors = ranges
+        .iter()
+        .map(|&(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>();
+    format!(">>{}", anchors.join(","))
+}
+
+/// 本文の各行の先頭に`>`を付けて引用にする。
+/// 前後の空行と行末の空白は除く。AAや字下げが崩れないよう、行頭の空白は残す。
+pub fn quote_message(message: &str) -> String {
+    message
+        .trim_matches('\n')
+        .lines()
+        .map(|line| format!("> {}", line.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::post::preview::preview_message;
+
+    #[test]
+    fn test_format_anchors() {
+        assert_eq!(format_anchors(&[]), "");
+        assert_eq!(format_anchors(&[3]), ">>3");
+        assert_eq!(format_anchors(&[8, 1, 2, 3, 5, 7, 2]), ">>1-3,5,7-8");
+
+        let message = preview_message(&format_anchors(&[1, 2, 3, 5]));
+        assert_eq!(message.anchor_indexes(), vec![1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_quote_message() {
+        assert_eq!(quote_message("\n一行目 \n 二行目\n"), "> 一行目\n>  二行目");
+        assert_eq!(
+            quote_message("　 ∧＿∧\n　（　´∀｀）"),
+            "> 　 ∧＿∧\n> 　（　´∀｀）"
+        );
+    }
+}
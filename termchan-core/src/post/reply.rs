@@ -1,17 +1,13 @@
 use std::{str::FromStr, sync::Arc};
 
-use anyhow;
-use reqwest::{
-    self,
-    cookie::{CookieStore, Jar},
-    header::HeaderValue,
-    Url,
-};
+use anyhow::{self, anyhow as any};
+use reqwest::{self, cookie::Jar, Url};
 
 use crate::{
     header::{build::post_header, cookie::Cookies},
-    post::form::reply::ReplyFormData,
+    post::{form::reply::ReplyFormData, login::session_id},
     url::{reply::ThreadParams, url::URL},
+    util::error::get_error,
 };
 
 ///
@@ -32,22 +28,7 @@ pub async fn post_reply(
     header_str: String,
     jar: Option<Arc<Jar>>,
 ) -> anyhow::Result<String> {
-    let mut login = false;
-    let jar = jar.unwrap_or(Arc::new(Jar::default()));
-    // 申し訳程度の検索よけ
-    let login_str = &"\x68\x74\x74\x70\x73\x3a\x2f\x2f\x35\x63\x68\x2e\x6e\x65\x74\x2f"
-        .parse::<Url>()
-        .unwrap();
-
-    let sid = Arc::clone(&jar)
-        .cookies(&login_str)
-        .unwrap_or(HeaderValue::from_str("").unwrap());
-    let sid = if sid.is_empty() {
-        ""
-    } else {
-        login = true;
-        sid.to_str().unwrap().split("=").nth(1).unwrap()
-    };
+    let sid = jar.as_deref().and_then(session_id);
 
     let client = reqwest::Client::builder().cookie_store(true).build()?;
     let thread_params = ThreadParams::new(url);
@@ -56,7 +37,7 @@ pub async fn post_reply(
     let mut cookies = Cookies::new();
     cookies.add("yuki", "akari");
     cookies.add("READJS", "\"off\"");
-    if login {
+    if let Some(sid) = &sid {
         cookies.add("sid", sid);
     }
 
@@ -82,11 +63,14 @@ pub async fn post_reply(
             .await;
         let body = &res?.text().await?;
 
-        return Ok(body.to_string());
-    }
-    if body.contains("書き込みが完了しました") {
-        return Ok(body.to_string());
+        return match get_error(body) {
+            Err(err) => Err(any!(err)),
+            _ => Ok(body.to_string()),
+        };
     }
 
-    Ok(body.to_string())
+    match get_error(body) {
+        Err(err) => Err(any!(err)),
+        _ => Ok(body.to_string()),
+    }
 }
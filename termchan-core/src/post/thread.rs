@@ -1,16 +1,17 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
 use anyhow::{self, anyhow as any, Ok};
-use reqwest::{self, Url};
+use reqwest::{self, cookie::Jar, Url};
 
 use crate::{
     get::board_cert::board_cert,
     header::{build::post_header, cookie::Cookies},
-    post::form::thread::ThreadFormData,
+    post::{form::thread::ThreadFormData, login::session_id},
     url::{thread::BoardParams, url::URL},
     util::error::get_error,
 };
 
+/// `jar`にログインのセッションがあれば、アカウントで書き込む。
 pub async fn create_thread(
     url: &str,
     subject: &str,
@@ -18,7 +19,9 @@ pub async fn create_thread(
     name: Option<&str>,
     mail: Option<&str>,
     header_str: &str,
+    jar: Option<Arc<Jar>>,
 ) -> anyhow::Result<String> {
+    let sid = jar.as_deref().and_then(session_id);
     let client = reqwest::Client::new();
     let board_params = BoardParams::new(url);
     let cert = board_cert(board_params.build_board_url()).await?;
@@ -27,6 +30,9 @@ pub async fn create_thread(
     let mut cookies = Cookies::new();
     cookies.add("yuki", "akari");
     cookies.add("READJS", "\"off\"");
+    if let Some(sid) = &sid {
+        cookies.add("sid", sid);
+    }
 
     let header = post_header(Url::from_str(url).unwrap(), cookies, header_str.to_string());
 
@@ -0,0 +1,236 @@
+use std::fmt::Display;
+
+use crate::util::encoding::{sjis_to_utf8, sjis_unencodable_chars, utf8_to_sjis_byte};
+
+/// 板ごとの書き込みの上限と名無しの名前
+/// 板の`SETTING.TXT`から取得し、無い項目は5chの一般的な値を使う。
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostLimits {
+    /// 本文の行数
+    pub max_lines:         usize,
+    /// 本文のバイト数(Shift_JIS)
+    pub max_message_bytes: usize,
+    /// スレタイのバイト数(Shift_JIS)
+    pub max_subject_bytes: usize,
+    /// 名前のバイト数(Shift_JIS)
+    pub max_name_bytes:    usize,
+    /// メール欄のバイト数(Shift_JIS)
+    pub max_mail_bytes:    usize,
+    /// 名前が空のときに表示される名前
+    pub noname_name:       String,
+}
+
+impl Default for PostLimits {
+    fn default() -> Self {
+        PostLimits {
+            max_lines:         32,
+            max_message_bytes: 2048,
+            max_subject_bytes: 96,
+            max_name_bytes:    64,
+            max_mail_bytes:    64,
+            noname_name:       "名無しさん".to_string(),
+        }
+    }
+}
+
+impl PostLimits {
+    /// `SETTING.TXT`の`KEY=VALUE`から上限を読み取る。
+    pub fn parse(setting: &str) -> Self {
+        let mut limits = PostLimits::default();
+        for line in setting.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => continue,
+            };
+            if key == "BBS_NONAME_NAME" {
+                if !value.is_empty() {
+                    limits.noname_name = value.to_string();
+                }
+                continue;
+            }
+            let value = match value.parse::<usize>() {
+                Ok(value) if value > 0 => value,
+                _ => continue,
+            };
+            match key {
+                // 実際の上限は設定値の2倍になる。
+                "BBS_LINE_NUMBER" => limits.max_lines = value * 2,
+                "BBS_MESSAGE_COUNT" => limits.max_message_bytes = value,
+                "BBS_SUBJECT_COUNT" => limits.max_subject_bytes = value,
+                "BBS_NAME_COUNT" => limits.max_name_bytes = value,
+                "BBS_MAIL_COUNT" => limits.max_mail_bytes = value,
+                _ => {}
+            }
+        }
+        limits
+    }
+
+    /// 板の`SETTING.TXT`を取得する。
+    /// board_urlは`https://host/board/`の形式
+    pub async fn fetch(board_url: &str) -> anyhow::Result<Self> {
+        let url = format!("{}/SETTING.TXT", board_url.trim_end_matches('/'));
+        let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+        Ok(PostLimits::parse(&sjis_to_utf8(&bytes)))
+    }
+}
+
+/// フォームの項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostField {
+    Name,
+    Mail,
+    Subject,
+    Message,
+}
+
+impl Display for PostField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostField::Name => write!(f, "名前"),
+            PostField::Mail => write!(f, "メール"),
+            PostField::Subject => write!(f, "タイトル"),
+            PostField::Message => write!(f, "本文"),
+        }
+    }
+}
+
+/// 送信前に見つかった問題
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostProblem {
+    /// Shift_JISで表現できない文字
+    /// 数値文字参照に置き換えれば送信できる。
+    Unencodable(PostField, Vec<char>),
+    /// 行数が上限を超えている
+    TooManyLines { lines: usize, max: usize },
+    /// バイト数が上限を超えている
+    TooManyBytes {
+        field: PostField,
+        bytes: usize,
+        max:   usize,
+    },
+    /// 空にできない項目が空
+    Empty(PostField),
+}
+
+impl PostProblem {
+    pub fn field(&self) -> PostField {
+        match self {
+            PostProblem::Unencodable(field, _) => *field,
+            PostProblem::TooManyLines { .. } => PostField::Message,
+            PostProblem::TooManyBytes { field, .. } => *field,
+            PostProblem::Empty(field) => *field,
+        }
+    }
+
+    /// 数値文字参照への置き換えで解決できるか
+    pub fn is_substitutable(&self) -> bool {
+        matches!(self, PostProblem::Unencodable(..))
+    }
+}
+
+impl Display for PostProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PostProblem::Unencodable(field, chars) => {
+                write!(
+                    f,
+                    "{}: Shift_JISで表現できない文字があります: {}",
+                    field,
+                    chars.iter().collect::<String>()
+                )
+            }
+            PostProblem::TooManyLines { lines, max } => {
+                write!(f, "本文: 行数が多すぎます ({}/{})", lines, max)
+            }
+            PostProblem::TooManyBytes { field, bytes, max } => {
+                write!(f, "{}: 長すぎます ({}/{}バイト)", field, bytes, max)
+            }
+            PostProblem::Empty(field) => write!(f, "{}: 入力してください", field),
+        }
+    }
+}
+
+/// 書き込みの内容を検査する。
+/// スレ立てのときはsubjectを渡す。
+/// バイト数は、表現できない文字を数値文字参照に置き換えた後のShift_JISで数える。
+pub fn validate_post(
+    name: &str,
+    mail: &str,
+    subject: Option<&str>,
+    message: &str,
+    limits: &PostLimits,
+) -> Vec<PostProblem> {
+    let mut problems = vec![];
+
+    if message.trim().is_empty() {
+        problems.push(PostProblem::Empty(PostField::Message));
+    }
+    if let Some(subject) = subject {
+        if subject.trim().is_empty() {
+            problems.push(PostProblem::Empty(PostField::Subject));
+        }
+    }
+
+    let mut fields = vec![
+        (PostField::Name, name, limits.max_name_bytes),
+        (PostField::Mail, mail, limits.max_mail_bytes),
+    ];
+    if let Some(subject) = subject {
+        fields.push((PostField::Subject, subject, limits.max_subject_bytes));
+    }
+    fields.push((PostField::Message, message, limits.max_message_bytes));
+
+    for (field, value, max) in fields {
+        let chars = sjis_unencodable_chars(value);
+        if !chars.is_empty() {
+            problems.push(PostProblem::Unencodable(field, chars));
+        }
+        let bytes = utf8_to_sjis_byte(value).len();
+        if bytes > max {
+            problems.push(PostProblem::TooManyBytes { field, bytes, max });
+        }
+    }
+
+    let lines = message.lines().count();
+    if lines > limits.max_lines {
+        problems.push(PostProblem::TooManyLines {
+            lines,
+            max: limits.max_lines,
+        });
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_setting() {
+        let setting =
+            "BBS_TITLE=ニュース速報\nBBS_LINE_NUMBER=16\nBBS_MESSAGE_COUNT=4096\nBBS_NAME_COUNT=\nBBS_NONAME_NAME=風吹けば名無し\n";
+        let limits = PostLimits::parse(setting);
+        assert_eq!(limits.max_lines, 32);
+        assert_eq!(limits.max_message_bytes, 4096);
+        assert_eq!(limits.max_name_bytes, PostLimits::default().max_name_bytes);
+        assert_eq!(limits.noname_name, "風吹けば名無し");
+    }
+
+    #[test]
+    fn test_validate_post() {
+        let limits = PostLimits {
+            max_lines: 2,
+            ..Default::default()
+        };
+        assert!(validate_post("", "sage", None, "テスト", &limits).is_empty());
+        assert_eq!(
+            validate_post("", "", Some(" "), "a\nb\nc😀", &limits),
+            vec![
+                PostProblem::Empty(PostField::Subject),
+                PostProblem::Unencodable(PostField::Message, vec!['😀']),
+                PostProblem::TooManyLines { lines: 3, max: 2 },
+            ]
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod reply;
+pub mod resolve;
 pub mod thread;
 pub mod url;
@@ -62,4 +62,11 @@ impl ThreadParams {
     pub fn build_board(&self) -> String {
         format!("{}://{}/{}/", self.scheme, self.host, self.board_key)
     }
+    /// スレ一覧から保存したものと同じ形のURL 末尾の`/`が無い
+    pub fn build_thread_url(&self) -> String {
+        format!(
+            "{}://{}/test/read.cgi/{}/{}",
+            self.scheme, self.host, self.board_key, self.thread_id
+        )
+    }
 }
@@ -0,0 +1,108 @@
+use super::{reply::ThreadParams, thread::BoardParams};
+
+/// 携帯向けのホスト 最初のパスにサーバー名が入る
+const MOBILE_HOSTS: &[&str] = &["itest", "sp"];
+
+/// ブラウザで開くURLを、板とスレのURLに直す。
+/// `https://egg.5ch.net/test/read.cgi/news4vip/1234567890/l50`
+/// `https://itest.5ch.net/egg/test/read.cgi/news4vip/1234567890`
+/// `https://egg.5ch.net/news4vip/`
+/// のどれも受け付ける。サーバー名の無い`itest`の形式では、ホストは`5ch.net`になる。
+pub fn resolve(url: &str) -> Option<(BoardParams, Option<ThreadParams>)> {
+    let url = url.trim();
+    let (scheme, rest) = url.split_once("://")?;
+    if scheme != "http" && scheme != "https" {
+        return None;
+    }
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let mut segments = path
+        .split(['/', '?', '#'])
+        .take_while(|s| !s.contains('='))
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut host = host.to_string();
+    if let Some((sub, domain)) = host.clone().split_once('.') {
+        if MOBILE_HOSTS.contains(&sub) {
+            host = match segments.first() {
+                Some(&server) if server != "test" && server != "subback" && segments.len() > 1 => {
+                    segments.remove(0);
+                    format!("{}.{}", server, domain)
+                }
+                _ => domain.to_string(),
+            };
+            // 携帯向けの板のURL
+            if segments.first() == Some(&"subback") {
+                segments.remove(0);
+            }
+        }
+    }
+
+    match segments.as_slice() {
+        ["test", "read.cgi", board, key, ..] if key.chars().all(|c| c.is_ascii_digit()) => {
+            let thread = ThreadParams::from(
+                format!("{}://{}/test/read.cgi/{}/{}", scheme, host, board, key).as_str(),
+            );
+            let board = BoardParams::from(format!("{}://{}/{}/", scheme, host, board).as_str());
+            Some((board, Some(thread)))
+        }
+        [board] | [board, "index.html"] => {
+            Some((
+                BoardParams::from(format!("{}://{}/{}/", scheme, host, board).as_str()),
+                None,
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(url: &str) -> Option<(String, Option<String>)> {
+        resolve(url).map(|(board, thread)| {
+            (
+                board.build_board_url(),
+                thread.map(|t| t.build_thread_url()),
+            )
+        })
+    }
+
+    #[test]
+    fn test_resolve() {
+        let thread = Some((
+            "https://egg.5ch.net/news4vip/".to_string(),
+            Some("https://egg.5ch.net/test/read.cgi/news4vip/1234567890".to_string()),
+        ));
+        assert_eq!(
+            urls("https://egg.5ch.net/test/read.cgi/news4vip/1234567890/l50"),
+            thread
+        );
+        assert_eq!(
+            urls("https://egg.5ch.net/test/read.cgi/news4vip/1234567890/"),
+            thread
+        );
+        assert_eq!(
+            urls("https://itest.5ch.net/egg/test/read.cgi/news4vip/1234567890"),
+            thread
+        );
+        assert_eq!(
+            urls("https://itest.5ch.net/test/read.cgi/news4vip/1234567890"),
+            Some((
+                "https://5ch.net/news4vip/".to_string(),
+                Some("https://5ch.net/test/read.cgi/news4vip/1234567890".to_string())
+            ))
+        );
+        assert_eq!(
+            urls("https://egg.5ch.net/news4vip"),
+            Some(("https://egg.5ch.net/news4vip/".to_string(), None))
+        );
+        assert_eq!(
+            urls("https://itest.5ch.net/subback/news4vip"),
+            Some(("https://5ch.net/news4vip/".to_string(), None))
+        );
+        assert_eq!(urls("egg.5ch.net/news4vip/"), None);
+        assert_eq!(urls("https://egg.5ch.net/"), None);
+    }
+}
@@ -1,3 +1,5 @@
+use percent_encoding::{percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
 pub fn sjis_to_utf8(before: &[u8]) -> String {
     let (message, ..) = encoding_rs::SHIFT_JIS.decode(before);
     message.to_string()
@@ -7,10 +9,69 @@ pub fn utf8_to_sjis_byte(before: &str) -> Vec<u8> {
     message.to_vec()
 }
 
-pub fn utf8_to_sjis_string(s: &str) -> String {
-    let (message, ..) = encoding_rs::SHIFT_JIS.encode(s);
-    let message = message.to_vec();
-    let message = unsafe { &*std::str::from_utf8_unchecked(&message) };
+/// Shift_JISで表現できない文字を、重複を除いて出現順に返す。
+/// これらの文字は書き込み時に数値文字参照として送られる。
+pub fn sjis_unencodable_chars(s: &str) -> Vec<char> {
+    let mut chars = vec![];
+    let mut buf = [0u8; 4];
+    for c in s.chars() {
+        let (_, _, had_errors) = encoding_rs::SHIFT_JIS.encode(c.encode_utf8(&mut buf));
+        if had_errors && !chars.contains(&c) {
+            chars.push(c);
+        }
+    }
+    chars
+}
 
-    message.to_string()
+/// Shift_JISで表現できない文字を数値文字参照に置き換える。
+pub fn to_ncr(s: &str) -> String {
+    let unencodable = sjis_unencodable_chars(s);
+    s.chars()
+        .map(|c| {
+            if unencodable.contains(&c) {
+                format!("&#{};", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// フォームの値として送れないバイト
+/// application/x-www-form-urlencodedと同じく英数字と`*-._`以外をエスケープする。
+const FORM: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'*')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_');
+
+/// Shift_JISに変換してからパーセントエンコードする。
+/// Shift_JISで表現できない文字は数値文字参照として送られる。
+pub fn sjis_percent_encode(s: &str) -> String {
+    percent_encode(&utf8_to_sjis_byte(s), FORM).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sjis_unencodable_chars() {
+        assert!(sjis_unencodable_chars("こんにちは、ｱｲｳ ABC ①").is_empty());
+        assert_eq!(sjis_unencodable_chars("😀テスト😀한"), vec!['😀', '한']);
+    }
+
+    #[test]
+    fn test_sjis_percent_encode() {
+        let tests = vec![
+            ("abc", "abc"),
+            ("a b&c=d", "a%20b%26c%3Dd"),
+            ("あ", "%82%A0"),
+            ("😀", "%26%23128512%3B"),
+        ];
+        for (input, output) in tests {
+            assert_eq!(sjis_percent_encode(input), output);
+        }
+        assert_eq!(to_ncr("a😀b"), "a&#128512;b");
+    }
 }
@@ -0,0 +1,152 @@
+use encoding_rs::SHIFT_JIS;
+use sha1::{Digest, Sha1};
+
+/// トリップの前に付く記号
+pub const TRIP_MARK: char = '◆';
+
+/// 計算できないキーに対してサーバーが返すトリップ
+const INVALID_TRIP: &str = "???";
+
+/// 名前欄を名前とトリップキーに分ける。
+/// 全角の`＃`もキーの区切りとして扱われる。
+pub fn split_name(name: &str) -> (&str, Option<&str>) {
+    match name.find(['#', '＃']) {
+        Some(i) => {
+            let sep = name[i..].chars().next().unwrap();
+            (&name[..i], Some(&name[i + sep.len_utf8()..]))
+        }
+        None => (name, None),
+    }
+}
+
+/// `#`を除いたトリップキーからトリップを計算する。
+/// キーはShift_JISのバイト列として扱い、12バイト以上ならSHA-1、それ未満ならDESで計算する。
+/// `#`から始まる12バイト以上のキーは生キーとして扱う。
+pub fn tripcode(key: &str) -> String {
+    let (key, ..) = SHIFT_JIS.encode(key);
+    let key = key.as_ref();
+
+    if key.len() >= 12 {
+        match key[0] {
+            b'#' => return raw_tripcode(&key[1..]),
+            b'$' => return INVALID_TRIP.to_string(),
+            _ => {}
+        }
+        let hash = Sha1::digest(key);
+        base64::encode(hash)[..12].replace('+', ".")
+    } else {
+        des_tripcode(key)
+    }
+}
+
+/// 生キー(`##`に続く16桁の16進数と、任意の2文字までのsalt)からトリップを計算する。
+/// 16進数をそのままDESの鍵にする。
+fn raw_tripcode(key: &[u8]) -> String {
+    if !(16..=18).contains(&key.len()) {
+        return INVALID_TRIP.to_string();
+    }
+    let (hex, salt) = key.split_at(16);
+    let bytes = hex
+        .chunks(2)
+        .map(|c| {
+            std::str::from_utf8(c)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        })
+        .collect::<Option<Vec<_>>>();
+    let mut bytes = match bytes {
+        Some(bytes) => bytes,
+        None => return INVALID_TRIP.to_string(),
+    };
+    // crypt(3)と同じく、鍵はNULで終わる。
+    if let Some(end) = bytes.iter().position(|&b| b == 0) {
+        bytes.truncate(end);
+    }
+
+    let salt = salt
+        .iter()
+        .chain(b"..")
+        .take(2)
+        .copied()
+        .collect::<Vec<_>>();
+    crypt_tripcode(&bytes, &salt_of(&salt))
+}
+
+/// saltに使えない文字を置き換える。
+fn salt_of(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            match b {
+                b':'..=b'@' => (b - b':' + b'A') as char,
+                b'['..=b'`' => (b - b'[' + b'a') as char,
+                b'.'..=b'z' => b as char,
+                _ => '.',
+            }
+        })
+        .collect()
+}
+
+/// 8文字までのキーから、crypt(3)の結果の末尾10文字をトリップにする。
+fn des_tripcode(key: &[u8]) -> String {
+    let salt = key
+        .iter()
+        .chain(b"H.")
+        .skip(1)
+        .take(2)
+        .copied()
+        .collect::<Vec<_>>();
+    crypt_tripcode(key, &salt_of(&salt))
+}
+
+fn crypt_tripcode(key: &[u8], salt: &str) -> String {
+    // トリップの計算にしか使わないので、古いアルゴリズムでも構わない。
+    #[allow(deprecated)]
+    match pwhash::unix_crypt::hash_with(salt, key) {
+        Ok(hash) => hash[hash.len() - 10..].to_string(),
+        Err(_) => INVALID_TRIP.to_string(),
+    }
+}
+
+/// 名前欄をサーバーが表示するのと同じ形にする。
+/// `名前#キー`は`名前 ◆トリップ`になり、名前に含まれる`◆`と`★`は`◇`と`☆`に置き換えられる。
+/// 名前が空のときは`default_name`を使う。
+pub fn format_name(name: &str, default_name: &str) -> String {
+    let (name, key) = split_name(name);
+    let name = name.replace('◆', "◇").replace('★', "☆");
+    let name = if name.is_empty() && key.is_none() {
+        default_name.to_string()
+    } else {
+        name
+    };
+
+    match key {
+        Some(key) => format!("{} {}{}", name, TRIP_MARK, tripcode(key)),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tripcode() {
+        assert_eq!(tripcode("test"), ".CzKQna1OU");
+        assert_eq!(tripcode("123456789012"), "jZk8zfYo4m4X");
+        assert_eq!(tripcode("テスト用の長いキー"), "Yw1c98MPMw//");
+        assert_eq!(tripcode("$12345678901"), "???");
+        // "test"を16進数にした生キー
+        assert_eq!(tripcode("#7465737400000000es"), ".CzKQna1OU");
+        assert_eq!(tripcode("#74657374zz000000"), "???");
+    }
+
+    #[test]
+    fn test_format_name() {
+        assert_eq!(format_name("", "名無しさん"), "名無しさん");
+        assert_eq!(format_name("名前#test", "名無しさん"), "名前 ◆.CzKQna1OU");
+        assert_eq!(format_name("名前＃test", "名無しさん"), "名前 ◆.CzKQna1OU");
+        assert_eq!(format_name("#test", "名無しさん"), " ◆.CzKQna1OU");
+        assert_eq!(format_name("◆偽物★", "名無しさん"), "◇偽物☆");
+    }
+}
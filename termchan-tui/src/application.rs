@@ -1,24 +1,68 @@
+use std::{
+    collections::{hash_map::Entry, BTreeSet, HashMap},
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
 use eyre::{bail, Result};
 use rayon::slice::ParallelSliceMut;
+use reqwest::cookie::Jar;
+use sea_orm::DbConn;
 use serde::{Deserialize, Serialize};
-use termchan_core::post::reply::post_reply;
-use tui_textarea::TextArea;
+use termchan_core::{
+    post::{
+        preview::preview_message,
+        quote::{format_anchors, quote_message},
+        reply::post_reply,
+        thread::create_thread,
+        validate::{validate_post, PostLimits, PostProblem},
+    },
+    url::{reply::ThreadParams, resolve::resolve, thread::BoardParams, url::URL},
+    util::{encoding::to_ncr, tripcode::format_name},
+};
+use tokio::sync::mpsc::Sender;
+use tui_textarea::{CursorMove, Input, Key, TextArea};
 
 use crate::{
-    config::{config::Config, credentials::Account, theme::Theme},
-    event::{Event, Order, Sort},
+    archive::{self, SnapshotProgress},
+    command::{self, CommandLine},
+    config::{
+        config::Config,
+        credentials,
+        keymap::{Action, Keymap, Mode},
+        ng::NgKind,
+        theme::Theme,
+    },
+    database::maintenance,
+    editor,
+    event::{Command, Event, Order, Sort},
+    export,
     state::{
+        account::AccountStateItem,
+        archive::ArchiveStateItem,
         bbsmenu::BbsMenuStateItem,
         board::BoardStateItem,
         bookmark::BookmarkStateItem,
         categories::CategoriesStateItem,
+        draft::DraftStateItem,
+        history::PostHistoryStateItem,
         home::{HomeItem, HomeStateItem},
+        identity::IdentityStateItem,
         layout::{LayoutState, Pane},
+        my_post::MyPostStateItem,
+        notification::NotificationStateItem,
+        offline::{is_disconnected, OfflineState},
         post::ThreadPostStateItem,
+        post_queue::QueuedPostStateItem,
+        prompt::{Prompt, PromptKind},
+        setting::SettingStateItem,
         tab::{LeftTabItem, RightTabItem, TabsState},
-        thread::ThreadStateItem,
+        thread::{BoardSync, ThreadStateItem},
     },
-    ui::stateful_list::StatefulList,
+    ui::stateful_list::{Motion, StatefulList},
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -27,15 +71,42 @@ pub struct App<'a> {
     pub right_tabs: TabsState<RightTabItem>,
     pub left_tabs:  TabsState<LeftTabItem>,
 
-    pub theme:      Theme,
-    pub layout:     LayoutState,
-    pub home:       StatefulList<HomeStateItem>,
-    pub bookmark:   StatefulList<BookmarkStateItem>,
-    pub bbsmenu:    StatefulList<BbsMenuStateItem>,
-    pub categories: StatefulList<CategoriesStateItem>,
-    pub category:   StatefulList<BoardStateItem>,
-    pub board:      StatefulList<ThreadStateItem>,
-    pub thread:     StatefulList<ThreadPostStateItem>,
+    pub theme:         Theme,
+    pub layout:        LayoutState,
+    pub home:          StatefulList<HomeStateItem>,
+    pub bookmark:      StatefulList<BookmarkStateItem>,
+    pub bbsmenu:       StatefulList<BbsMenuStateItem>,
+    pub categories:    StatefulList<CategoriesStateItem>,
+    pub category:      StatefulList<BoardStateItem>,
+    pub board:         StatefulList<ThreadStateItem>,
+    pub thread:        StatefulList<ThreadPostStateItem>,
+    #[serde(default)]
+    pub notifications: StatefulList<NotificationStateItem>,
+    #[serde(default)]
+    pub archives:      StatefulList<ArchiveStateItem>,
+    #[serde(default)]
+    pub histories:     StatefulList<PostHistoryStateItem>,
+    #[serde(default)]
+    pub identities:    StatefulList<IdentityStateItem>,
+    /// 設定タブの項目 アカウントもここに並べる
+    #[serde(default)]
+    pub settings:      StatefulList<SettingStateItem>,
+
+    /// 現在開いている板
+    #[serde(default)]
+    pub current_board:  Option<BoardStateItem>,
+    /// 現在開いているスレッド
+    #[serde(default)]
+    pub current_thread: Option<ThreadStateItem>,
+    /// 新着レスの先頭の位置
+    #[serde(default)]
+    pub first_unread:   Option<usize>,
+    /// 印を付けたレスの番号
+    #[serde(skip)]
+    pub marked_posts:   BTreeSet<i32>,
+    /// 範囲選択を始めた位置
+    #[serde(skip)]
+    pub visual_start:   Option<usize>,
 
     pub sort: StatefulList<Sort>,
 
@@ -47,16 +118,73 @@ pub struct App<'a> {
     #[serde(skip_deserializing)]
     pub board_textareas:        Vec<TextArea<'a>>,
     pub board_textareas_which:  usize,
+    /// 板のURLごとの書き込みの上限
+    #[serde(skip)]
+    pub post_limits:            HashMap<String, PostLimits>,
+    /// フォームの内容の問題
+    #[serde(skip)]
+    pub post_problems:          Vec<PostProblem>,
+    /// 数値文字参照に置き換えて送信することを確認済みか
+    #[serde(skip)]
+    pub confirm_ncr:            bool,
+    /// 書き込みフォームで使っている名前
+    #[serde(skip)]
+    pub post_identity:          Option<IdentityStateItem>,
+    /// 編集中の名前 フォームは`board_textareas`を使う
+    #[serde(skip)]
+    pub editing_identity:       Option<IdentityStateItem>,
+    /// 書き込みフォームで使っているアカウント
+    #[serde(skip)]
+    pub post_account:           Option<AccountStateItem>,
+    /// 編集中のアカウント フォームは`board_textareas`を使う
+    #[serde(skip)]
+    pub editing_account:        Option<AccountStateItem>,
+    /// 編集中の設定 フォームは`board_textareas`を使う
+    #[serde(skip)]
+    pub editing_setting:        Option<SettingStateItem>,
+    /// アカウントのIDごとのログイン済みのCookie
+    #[serde(skip)]
+    pub sessions:               HashMap<i32, Arc<Jar>>,
 
-    pub config: Config,
+    pub config:           Config,
+    /// 設定から作ったキーの割り当て
+    #[serde(skip)]
+    pub keymap:           Keymap,
+    /// キーの一覧を表示しているか
+    #[serde(skip)]
+    pub show_help:        bool,
+    /// ステータスラインで入力している`:`や`/`
+    #[serde(skip)]
+    pub prompt:           Option<Prompt>,
+    /// 最後に探した文字列 `n`と`N`で次と前を探す
+    #[serde(skip)]
+    pub search:           Option<String>,
+    /// `:`で実行したコマンド 新しいものが先頭
+    #[serde(default)]
+    pub command_history:  Vec<String>,
+    /// `:quit`で終了する
+    #[serde(skip)]
+    pub should_quit:      bool,
+    /// オフラインで読んでいるか
+    #[serde(skip)]
+    pub offline:          OfflineState,
+    /// 最後にアーカイブを保存した時刻
+    #[serde(skip)]
+    pub last_snapshot:    Option<Instant>,
+    /// 裏でアーカイブを保存している
+    #[serde(skip)]
+    pub snapshot_running: bool,
 
     pub input_mode:     bool,
     pub request_header: String,
+
+    /// 起動時に確立したコネクションプール
+    #[serde(skip)]
+    pub db: DbConn,
 }
 
 impl App<'_> {
-    pub fn new() -> Self {
-        let config = Config::load_config().unwrap();
+    pub fn new(db: DbConn, config: Config) -> Self {
         let theme = config.theme.clone();
 
         let left_tabs = TabsState::new(vec![LeftTabItem::Home]);
@@ -66,20 +194,18 @@ impl App<'_> {
 
         // BBS Menuを DBに登録。
         let bbsmenu_url_list = config.bbsmenu_url_list.clone();
-        futures::executor::block_on(BbsMenuStateItem::init(bbsmenu_url_list));
+        futures::executor::block_on(BbsMenuStateItem::init(&db, bbsmenu_url_list));
 
         //  DB中のMenuを取得。
-        let init_bbsmenu = futures::executor::block_on(BbsMenuStateItem::get()).unwrap();
+        let init_bbsmenu =
+            futures::executor::block_on(BbsMenuStateItem::get(&db, &config.bbsmenu_url_list))
+                .unwrap();
         let bbsmenu = StatefulList::with_items(init_bbsmenu);
         let categories = StatefulList::with_items(vec![CategoriesStateItem::default()]);
         let category = StatefulList::with_items(vec![BoardStateItem::default()]);
         let board = StatefulList::with_items(vec![ThreadStateItem::default()]);
         let thread = StatefulList::with_items(vec![]);
-        let home = StatefulList::with_items(vec![
-            HomeStateItem::new(HomeItem::Bookmark),
-            HomeStateItem::new(HomeItem::AllChannels),
-            HomeStateItem::new(HomeItem::Settings),
-        ]);
+        let home = StatefulList::with_items(HomeStateItem::all());
         let bookmark = StatefulList::with_items(vec![BookmarkStateItem::default()]);
 
         let sort = StatefulList::with_items(vec![
@@ -99,6 +225,8 @@ impl App<'_> {
         let board_textareas = vec![TextArea::default(); 4];
 
         let request_header = config.request_header.clone();
+        // 設定ファイルを読んだときに検査済み
+        let keymap = Keymap::new(&config.keymap).unwrap_or_default();
 
         App {
             left_tabs,
@@ -113,18 +241,53 @@ impl App<'_> {
             category,
             board,
             thread,
+            notifications: StatefulList::with_items(vec![]),
+            archives: StatefulList::with_items(vec![]),
+            histories: StatefulList::with_items(vec![]),
+            identities: StatefulList::with_items(vec![]),
+            settings: StatefulList::with_items(vec![]),
+            current_board: None,
+            current_thread: None,
+            first_unread: None,
+            marked_posts: BTreeSet::new(),
+            visual_start: None,
             sort,
             thread_textareas,
             thread_textareas_which: 0,
             board_textareas,
             board_textareas_which: 0,
+            post_limits: HashMap::new(),
+            post_problems: vec![],
+            confirm_ncr: false,
+            post_identity: None,
+            editing_identity: None,
+            post_account: None,
+            editing_account: None,
+            editing_setting: None,
+            sessions: HashMap::new(),
             config,
+            keymap,
+            show_help: false,
+            prompt: None,
+            search: None,
+            command_history: vec![],
+            should_quit: false,
+            offline: OfflineState::default(),
+            last_snapshot: None,
+            snapshot_running: false,
             input_mode: false,
             request_header,
+            db,
         }
     }
 }
 
+/// 保存するコマンドの履歴の数
+const COMMAND_HISTORY_LEN: usize = 100;
+/// アーカイブを保存する間隔と、一度に保存するスレの数
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+const SNAPSHOT_BATCH: usize = 3;
+
 // GET
 #[allow(dead_code)]
 impl App<'_> {
@@ -160,6 +323,332 @@ impl App<'_> {
     }
 }
 
+// 移動
+impl App<'_> {
+    /// フォーカスしている一覧の選択を動かす。
+    pub fn move_focused(&mut self, motion: Motion) {
+        match self.layout.focus_pane {
+            Pane::Side => {
+                match self.left_tabs.get() {
+                    LeftTabItem::Home => self.home.motion(motion),
+                    LeftTabItem::Bookmarks => self.bookmark.motion(motion),
+                    LeftTabItem::Bbsmenu => self.bbsmenu.motion(motion),
+                    LeftTabItem::Categories => self.categories.motion(motion),
+                    LeftTabItem::Category(..) => self.category.motion(motion),
+                    LeftTabItem::Board(..) => self.board.motion(motion),
+                    LeftTabItem::Notifications => self.notifications.motion(motion),
+                    LeftTabItem::Archive => self.archives.motion(motion),
+                    LeftTabItem::History => self.histories.motion(motion),
+                    LeftTabItem::Identities => self.identities.motion(motion),
+                    LeftTabItem::Settings => self.settings.motion(motion),
+                }
+            }
+            Pane::Main => {
+                match self.right_tabs.get() {
+                    RightTabItem::Thread(..) => self.thread.motion(motion),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// スレではレス番号、それ以外の一覧では1から数えた位置へ移動する。
+    pub fn jump(&mut self, number: usize) {
+        if self.layout.focus_pane == Pane::Main {
+            match self
+                .thread
+                .items
+                .iter()
+                .position(|post| post.index as usize == number)
+            {
+                Some(i) => self.thread.select_index(i),
+                None => self.update_message(format!("{}番のレスはありません。", number)),
+            }
+        } else {
+            self.move_focused(Motion::Index(number.saturating_sub(1)));
+        }
+    }
+
+    /// ステータスラインの入力にキーを渡す。Enterで実行し、Escか空でBackspaceなら閉じる。
+    /// コマンドではTabで補完し、上下で履歴を呼び出す。
+    pub async fn input_prompt(&mut self, input: Input) -> Result<()> {
+        let prompt = match self.prompt.as_mut() {
+            Some(prompt) => prompt,
+            None => return Ok(()),
+        };
+        let is_command = prompt.kind == PromptKind::Command;
+        match input.key {
+            Key::Esc => self.prompt = None,
+            Key::Backspace if prompt.input.is_empty() => self.prompt = None,
+            Key::Backspace => {
+                prompt.input.pop();
+            }
+            Key::Enter | Key::Char('\n') => {
+                let Prompt { kind, input, .. } = prompt.clone();
+                self.prompt = None;
+                match kind {
+                    PromptKind::Command => {
+                        let input = input.trim().to_string();
+                        if input.is_empty() {
+                            return Ok(());
+                        }
+                        self.command_history.retain(|line| *line != input);
+                        self.command_history.insert(0, input.clone());
+                        self.command_history.truncate(COMMAND_HISTORY_LEN);
+                        if let Err(e) = self.run_command(&input).await {
+                            self.update_message(e.to_string());
+                        }
+                    }
+                    PromptKind::Search => self.update(Event::Search(input)).await?,
+                }
+            }
+            Key::Tab | Key::Char('\t') if is_command => {
+                let candidates = command::complete(&prompt.input, &Config::keys());
+                match candidates.len() {
+                    0 => {}
+                    1 => prompt.input = candidates[0].clone(),
+                    _ => {
+                        prompt.input = command::common_prefix(&candidates);
+                        self.update_message(candidates.join("  "));
+                    }
+                }
+            }
+            Key::Up if is_command && !self.command_history.is_empty() => {
+                let i = prompt
+                    .history
+                    .map_or(0, |i| (i + 1).min(self.command_history.len() - 1));
+                prompt.history = Some(i);
+                prompt.input = self.command_history[i].clone();
+            }
+            Key::Down if is_command => {
+                match prompt.history {
+                    Some(0) | None => {
+                        prompt.history = None;
+                        prompt.input.clear();
+                    }
+                    Some(i) => {
+                        prompt.history = Some(i - 1);
+                        prompt.input = self.command_history[i - 1].clone();
+                    }
+                }
+            }
+            Key::Char(c) if !input.ctrl && !input.alt => prompt.input.push(c),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `:`で入力したコマンドを実行する。
+    pub async fn run_command(&mut self, line: &str) -> Result<()> {
+        match command::parse(line)? {
+            CommandLine::Jump(number) => self.update(Event::Jump(number)).await?,
+            CommandLine::Open(url) => self.open_url(&url).await?,
+            CommandLine::Bookmark(add) => self.bookmark_current_board(add).await?,
+            CommandLine::Ng(kind, value) => {
+                let value = match value {
+                    Some(value) => value,
+                    None => self.selected_post_ng_value(kind)?,
+                };
+                if !self.config.ng.add(kind, &value) {
+                    bail!("既に追加されています。: {}", value);
+                }
+                self.apply_config().await?;
+                self.update_message(format!("あぼーんに追加しました。: {} {}", kind, value));
+            }
+            CommandLine::Unng(kind, value) => {
+                if !self.config.ng.remove(kind, &value) {
+                    bail!("追加されていません。: {} {}", kind, value);
+                }
+                self.apply_config().await?;
+                self.update_message(format!("あぼーんから削除しました。: {} {}", kind, value));
+            }
+            CommandLine::Set(key, value) => {
+                self.config = self.config.with_value(&key, &value)?;
+                self.apply_config().await?;
+                self.update_message(format!("設定しました。: {} = {}", key, value));
+            }
+            CommandLine::Export(format, path) => {
+                let thread = match &self.current_thread {
+                    Some(thread) => thread.clone(),
+                    None => bail!("スレを開いていません。"),
+                };
+                let path = match path {
+                    Some(path) => PathBuf::from(path),
+                    None => export::default_path(&thread, format)?,
+                };
+                export::write_thread(&self.db, &thread, format, &path).await?;
+                self.update_message(format!("書き出しました。: {}", path.display()));
+            }
+            CommandLine::Offline(enabled) => {
+                if enabled.unwrap_or(!self.offline.enabled) {
+                    self.set_offline().await?;
+                    self.update_message(
+                        "オフラインにしました。保存済みのデータを表示します。".to_string(),
+                    );
+                } else {
+                    self.update_message("オンラインにしました。".to_string());
+                    self.set_online().await?;
+                }
+            }
+            CommandLine::Archive { board, archived } => {
+                self.archive_current(board, archived).await?
+            }
+            CommandLine::Prune(days) => {
+                match days.unwrap_or(self.config.prune_days) {
+                    0 => bail!("日数を指定するか、設定の prune_days を1以上にしてください。"),
+                    days => {
+                        if self.prune(days).await? == 0 {
+                            self.update_message("消すスレはありませんでした。".to_string());
+                        }
+                    }
+                }
+            }
+            CommandLine::Reload => self.update(Event::Get).await?,
+            CommandLine::Help => self.show_help = true,
+            CommandLine::Quit => self.should_quit = true,
+        }
+        Ok(())
+    }
+
+    /// 選択しているレスのIDか名前 本文の単語は選べない
+    fn selected_post_ng_value(&self, kind: NgKind) -> Result<String> {
+        let post = match self.thread.items.get(self.thread.selected()) {
+            Some(post) if self.layout.focus_pane == Pane::Main => post,
+            _ => bail!("スレでレスを選択するか、値を入力してください。"),
+        };
+        match kind {
+            NgKind::Id if !post.post_id.is_empty() => Ok(post.post_id.clone()),
+            NgKind::Name => Ok(post.name.clone()),
+            NgKind::Id => bail!("このレスにはIDがありません。"),
+            NgKind::Word => bail!("単語を入力してください。"),
+        }
+    }
+
+    /// 開いている板をブックマークに追加する。falseなら削除する。
+    async fn bookmark_current_board(&mut self, add: bool) -> Result<()> {
+        let board = match self.current_board.clone() {
+            Some(board) => board,
+            None => bail!("板を開いてください。"),
+        };
+        if add {
+            BookmarkStateItem::add(&self.db, board.url).await?;
+            self.update_message(format!("ブックマークに追加しました。: {}", board.name));
+        } else {
+            let bookmarks = BookmarkStateItem::get_all(&self.db)
+                .await
+                .unwrap_or_default();
+            match bookmarks.into_iter().find(|b| b.url == board.url) {
+                Some(bookmark) => BookmarkStateItem::delete(&self.db, bookmark.id).await?,
+                None => bail!("ブックマークにありません。: {}", board.name),
+            }
+            self.update_message(format!("ブックマークから削除しました。: {}", board.name));
+        }
+        Ok(())
+    }
+
+    /// 板かスレのURLを開く。知らない板なら登録してから開く。
+    pub async fn open_url(&mut self, url: &str) -> Result<()> {
+        let (board, thread) = match resolve(url) {
+            Some(resolved) => resolved,
+            None => bail!("板かスレのURLではありません。: {}", url),
+        };
+        let board = BoardStateItem::find_or_register(&self.db, &board).await?;
+        let thread_url = thread.map(|thread| {
+            // サーバー名が分からなかったときは、見つけた板のホストに合わせる。
+            match BoardParams::from(board.url.as_str()) {
+                b if b.host != thread.host => {
+                    format!(
+                        "{}/test/read.cgi/{}/{}",
+                        b.origin(),
+                        thread.board_key,
+                        thread.thread_id
+                    )
+                }
+                _ => thread.build_thread_url(),
+            }
+        });
+        let db = self.db.clone();
+        self.fetch_board(board.fetch(&db)).await?;
+        MyPostStateItem::match_threads(&self.db, board.id, &board.url).await?;
+        self.board
+            .set_items(ThreadStateItem::get_by_board_id(&self.db, board.id).await?);
+        self.sort_board().await?;
+        self.current_board = Some(board.clone());
+        self.left_tabs
+            .history_add(LeftTabItem::Board(board.name.clone()));
+        self.left_tabs.next();
+        self.layout.focus_pane = Pane::Side;
+
+        if let Some(thread_url) = thread_url {
+            // DAT落ちしてスレ一覧に無くても開けるようにする。
+            let thread = ThreadStateItem::register(&self.db, board.id, &thread_url).await?;
+            if let Some(i) = self.board.items.iter().position(|t| t.id == thread.id) {
+                self.board.select_index(i);
+            }
+            self.open_thread(thread.clone()).await?;
+            let name = self
+                .current_thread
+                .as_ref()
+                .map_or(thread.name, |t| t.name.clone());
+            self.right_tabs
+                .history_add(RightTabItem::Thread(name, thread.url));
+            self.layout.focus_pane = Pane::Main;
+        }
+        Ok(())
+    }
+
+    /// 最後に探した文字列を、フォーカスしている一覧の次(`forward`がfalseなら前)から探す。
+    pub fn next_hit(&mut self, forward: bool) {
+        let query = match &self.search {
+            Some(query) => query.to_lowercase(),
+            None => return,
+        };
+        let hit = |text: &str| text.to_lowercase().contains(&query);
+        let found = match self.layout.focus_pane {
+            Pane::Side => {
+                match self.left_tabs.get() {
+                    LeftTabItem::Home => self.home.find(forward, |h| hit(&h.item.to_string())),
+                    LeftTabItem::Bookmarks => self.bookmark.find(forward, |b| hit(&b.name)),
+                    LeftTabItem::Bbsmenu => self.bbsmenu.find(forward, |b| hit(&b.url)),
+                    LeftTabItem::Categories => self.categories.find(forward, |c| hit(&c.name)),
+                    LeftTabItem::Category(..) => self.category.find(forward, |b| hit(&b.name)),
+                    LeftTabItem::Board(..) => self.board.find(forward, |t| hit(&t.name)),
+                    LeftTabItem::Archive => {
+                        self.archives
+                            .find(forward, |a| hit(&a.thread.name) || hit(&a.board_name))
+                    }
+                    LeftTabItem::Notifications => {
+                        self.notifications
+                            .find(forward, |n| hit(&n.thread_name) || hit(&n.name))
+                    }
+                    LeftTabItem::History => {
+                        self.histories
+                            .find(forward, |h| hit(&h.message) || hit(&h.name))
+                    }
+                    LeftTabItem::Identities => {
+                        self.identities
+                            .find(forward, |i| hit(&i.label) || hit(&i.name))
+                    }
+                    LeftTabItem::Settings => self.settings.find(forward, |s| hit(&s.label())),
+                }
+            }
+            Pane::Main => {
+                match self.right_tabs.get() {
+                    RightTabItem::Thread(..) => {
+                        self.thread.find(forward, |post| {
+                            hit(&post.message.to_string()) || hit(&post.name) || hit(&post.post_id)
+                        })
+                    }
+                }
+            }
+            _ => false,
+        };
+        if !found {
+            self.update_message(format!("見つかりません。: {}", query));
+        }
+    }
+}
+
 impl App<'_> {
     pub async fn update(&mut self, event: Event) -> Result<()> {
         match event {
@@ -182,12 +671,16 @@ impl App<'_> {
                                     self.update_board().await?
                                 }
                             }
-                            LeftTabItem::Settings => (),
+                            LeftTabItem::Notifications => self.update_notifications().await?,
+                            LeftTabItem::Archive => self.update_archives().await?,
+                            LeftTabItem::History => self.update_histories().await?,
+                            LeftTabItem::Identities => self.update_identities().await?,
+                            LeftTabItem::Settings => self.update_settings().await?,
                         }
                     }
                     Pane::Main => {
                         match self.right_tabs.get() {
-                            RightTabItem::Thread(..) => self.update_thread().await?,
+                            RightTabItem::Thread(..) => self.reload_thread().await?,
                         }
                     }
                     _ => (),
@@ -204,7 +697,11 @@ impl App<'_> {
                             LeftTabItem::Categories => self.categories.next(),
                             LeftTabItem::Category(..) => self.category.next(),
                             LeftTabItem::Board(..) => self.board.next(),
-                            _ => {}
+                            LeftTabItem::Notifications => self.notifications.next(),
+                            LeftTabItem::Archive => self.archives.next(),
+                            LeftTabItem::History => self.histories.next(),
+                            LeftTabItem::Identities => self.identities.next(),
+                            LeftTabItem::Settings => self.settings.next(),
                         }
                     }
                     Pane::Main => {
@@ -226,7 +723,11 @@ impl App<'_> {
                             LeftTabItem::Categories => self.categories.prev(),
                             LeftTabItem::Category(..) => self.category.prev(),
                             LeftTabItem::Board(..) => self.board.prev(),
-                            _ => {}
+                            LeftTabItem::Notifications => self.notifications.prev(),
+                            LeftTabItem::Archive => self.archives.prev(),
+                            LeftTabItem::History => self.histories.prev(),
+                            LeftTabItem::Identities => self.identities.prev(),
+                            LeftTabItem::Settings => self.settings.prev(),
                         }
                     }
                     Pane::Main => {
@@ -239,70 +740,28 @@ impl App<'_> {
                 Ok(())
             }
             Event::ScrollToTop => {
-                match self.layout.focus_pane {
-                    Pane::Side => {
-                        match self.left_tabs.get() {
-                            LeftTabItem::Home => self.home.state.select(Some(0)),
-                            LeftTabItem::Bookmarks => self.bookmark.state.select(Some(0)),
-                            LeftTabItem::Bbsmenu => self.bbsmenu.state.select(Some(0)),
-                            LeftTabItem::Categories => self.categories.state.select(Some(0)),
-                            LeftTabItem::Category(..) => self.category.state.select(Some(0)),
-                            LeftTabItem::Board(..) => self.board.state.select(Some(0)),
-                            _ => {}
-                        }
-                    }
-                    Pane::Main => {
-                        match self.right_tabs.get() {
-                            RightTabItem::Thread(..) => self.thread.state.select(Some(0)),
-                        }
-                    }
-                    _ => {}
-                }
+                self.move_focused(Motion::Top);
                 Ok(())
             }
             Event::ScrollToBottom => {
-                match self.layout.focus_pane {
-                    Pane::Side => {
-                        match self.left_tabs.get() {
-                            LeftTabItem::Home => {
-                                self.home.state.select(Some(self.home.items.len() - 1))
-                            }
-                            LeftTabItem::Bookmarks => {
-                                self.bookmark
-                                    .state
-                                    .select(Some(self.bookmark.items.len() - 1))
-                            }
-                            LeftTabItem::Bbsmenu => {
-                                self.bbsmenu
-                                    .state
-                                    .select(Some(self.bbsmenu.items.len() - 1));
-                            }
-                            LeftTabItem::Categories => {
-                                self.categories
-                                    .state
-                                    .select(Some(self.categories.items.len() - 1));
-                            }
-
-                            LeftTabItem::Category(..) => {
-                                self.category
-                                    .state
-                                    .select(Some(self.category.items.len() - 1));
-                            }
-                            LeftTabItem::Board(..) => {
-                                self.board.state.select(Some(self.board.items.len() - 1));
-                            }
-                            _ => {}
-                        }
-                    }
-                    Pane::Main => {
-                        match self.right_tabs.get() {
-                            RightTabItem::Thread(..) => {
-                                self.thread.state.select(Some(self.thread.items.len() - 1));
-                            }
-                        }
-                    }
-                    _ => {}
-                }
+                self.move_focused(Motion::Bottom);
+                Ok(())
+            }
+            Event::Move(motion) => {
+                self.move_focused(motion);
+                Ok(())
+            }
+            Event::Jump(number) => {
+                self.jump(number);
+                Ok(())
+            }
+            Event::Search(query) => {
+                self.search = Some(query).filter(|q| !q.is_empty());
+                self.next_hit(true);
+                Ok(())
+            }
+            Event::NextHit(forward) => {
+                self.next_hit(forward);
                 Ok(())
             }
             Event::RemoveHistory => {
@@ -334,7 +793,7 @@ impl App<'_> {
             }
             Event::Left => {
                 if self.layout.focus_pane == Pane::Main {
-                    self.update_thread().await?;
+                    self.reload_thread().await?;
                     self.right_tabs.previous();
                 }
                 Ok(())
@@ -391,10 +850,45 @@ impl App<'_> {
                                             self.left_tabs.next();
                                         }
                                     }
+                                    HomeItem::Notifications => {
+                                        let res = self.update_notifications().await;
+                                        if res.is_ok() {
+                                            self.layout.focus_pane = Pane::Side;
+                                            self.left_tabs.history_add(LeftTabItem::Notifications);
+                                            self.left_tabs.next();
+                                        }
+                                    }
+                                    HomeItem::Archive => {
+                                        let res = self.update_archives().await;
+                                        if res.is_ok() {
+                                            self.layout.focus_pane = Pane::Side;
+                                            self.left_tabs.history_add(LeftTabItem::Archive);
+                                            self.left_tabs.next();
+                                        }
+                                    }
+                                    HomeItem::History => {
+                                        let res = self.update_histories().await;
+                                        if res.is_ok() {
+                                            self.layout.focus_pane = Pane::Side;
+                                            self.left_tabs.history_add(LeftTabItem::History);
+                                            self.left_tabs.next();
+                                        }
+                                    }
+                                    HomeItem::Identities => {
+                                        let res = self.update_identities().await;
+                                        if res.is_ok() {
+                                            self.layout.focus_pane = Pane::Side;
+                                            self.left_tabs.history_add(LeftTabItem::Identities);
+                                            self.left_tabs.next();
+                                        }
+                                    }
                                     HomeItem::Settings => {
-                                        self.layout.focus_pane = Pane::Side;
-                                        self.left_tabs.history_add(LeftTabItem::Settings);
-                                        self.left_tabs.next();
+                                        let res = self.update_settings().await;
+                                        if res.is_ok() {
+                                            self.layout.focus_pane = Pane::Side;
+                                            self.left_tabs.history_add(LeftTabItem::Settings);
+                                            self.left_tabs.next();
+                                        }
                                     }
                                     HomeItem::AllChannels => {
                                         self.layout.focus_pane = Pane::Side;
@@ -447,21 +941,76 @@ impl App<'_> {
 
                                 self.layout.focus_pane = Pane::Main;
                             }
-                            LeftTabItem::Settings => {
+                            LeftTabItem::Notifications => {
+                                if self.notifications.items.is_empty() {
+                                    return Ok(());
+                                }
+                                let notification =
+                                    self.notifications.items[self.notifications.selected()].clone();
+                                let thread =
+                                    ThreadStateItem::get_by_id(&self.db, notification.thread_id)
+                                        .await?;
+                                self.open_thread(thread).await?;
+
+                                // 返信のレスを選択する。
+                                if let Some(i) = self
+                                    .thread
+                                    .items
+                                    .iter()
+                                    .position(|p| p.index == notification.index)
+                                {
+                                    self.thread.state.select(Some(i));
+                                    self.thread.state.offset = i;
+                                }
+                                self.right_tabs.history_add(RightTabItem::Thread(
+                                    notification.thread_name,
+                                    notification.thread_url,
+                                ));
                                 self.layout.focus_pane = Pane::Main;
+                            }
+                            LeftTabItem::Archive => {
+                                let archive =
+                                    match self.archives.items.get(self.archives.selected()) {
+                                        Some(archive) => archive.clone(),
+                                        None => return Ok(()),
+                                    };
+                                self.open_thread(archive.thread.clone()).await?;
                                 self.right_tabs.history_add(RightTabItem::Thread(
-                                    "".to_string(),
-                                    "".to_string(),
+                                    archive.thread.name,
+                                    archive.thread.url,
+                                ));
+                                self.layout.focus_pane = Pane::Main;
+                            }
+                            LeftTabItem::History => {
+                                if self.histories.items.is_empty() {
+                                    return Ok(());
+                                }
+                                // 履歴の内容を下書きに戻して、フォームから編集できるようにする。
+                                let history =
+                                    self.histories.items[self.histories.selected()].clone();
+                                DraftStateItem::from(&history).save(&self.db).await?;
+                                self.update_message(format!(
+                                    "下書きに戻しました。: {}",
+                                    history.subject.unwrap_or(history.url)
                                 ));
                             }
+                            LeftTabItem::Identities => {
+                                if let Some(identity) = self
+                                    .identities
+                                    .items
+                                    .get(self.identities.selected())
+                                    .cloned()
+                                {
+                                    self.open_identity_form(identity);
+                                }
+                            }
+                            LeftTabItem::Settings => self.enter_setting().await?,
                         }
                         Ok(())
                     }
                     Pane::Main => {
                         match self.right_tabs.get() {
-                            RightTabItem::Thread(..) => {
-                                self.layout.visible_popup = true;
-                            }
+                            RightTabItem::Thread(..) => self.open_post_form().await?,
                         }
 
                         Ok(())
@@ -478,7 +1027,7 @@ impl App<'_> {
                 match self.left_tabs.get() {
                     LeftTabItem::Category(..) => {
                         let board = self.category.items[self.category.selected()].clone();
-                        let res = BookmarkStateItem::add(board.url).await;
+                        let res = BookmarkStateItem::add(&self.db, board.url).await;
                         match res {
                             Ok(()) => {
                                 self.update_message(format!(
@@ -496,7 +1045,7 @@ impl App<'_> {
                     }
                     LeftTabItem::Bookmarks => {
                         let bookmark = self.bookmark.items[self.bookmark.selected()].clone();
-                        let res = BookmarkStateItem::delete(bookmark.id).await;
+                        let res = BookmarkStateItem::delete(&self.db, bookmark.id).await;
                         match res {
                             Ok(()) => {
                                 self.update_message(format!(
@@ -522,7 +1071,16 @@ impl App<'_> {
                 Ok(())
             }
             Event::ClosePopup => {
+                if self.layout.visible_popup {
+                    self.save_draft().await?;
+                }
+                if !self.layout.visible_popup {
+                    self.clear_post_selection();
+                }
                 self.layout.visible_popup = false;
+                self.editing_identity = None;
+                self.editing_account = None;
+                self.editing_setting = None;
                 Ok(())
             }
             Event::DisableInputMode => {
@@ -540,7 +1098,7 @@ impl App<'_> {
                             (self.thread_textareas_which + 1) % self.thread_textareas.len();
                     } else if self.layout.focus_pane == Pane::Side {
                         self.board_textareas_which =
-                            (self.board_textareas_which + 1) % self.board_textareas.len() - 1;
+                            (self.board_textareas_which + 1) % self.board_textareas.len();
                     }
                 }
                 Ok(())
@@ -556,6 +1114,8 @@ impl App<'_> {
                         }
                         _ => {}
                     }
+                    self.confirm_ncr = false;
+                    self.check_form();
                 }
 
                 Ok(())
@@ -563,51 +1123,93 @@ impl App<'_> {
             Event::Post => {
                 if self.layout.visible_popup {
                     match self.layout.focus_pane {
-                        Pane::Main => {
-                            let url = self.board.items[self.board.selected()].url.clone();
-                            let name = if !self.thread_textareas[0].is_empty() {
-                                let name = self.thread_textareas[0].lines().join("\n");
-                                Some(name)
-                            } else {
-                                None
-                            };
-                            let mail = if !self.thread_textareas[1].is_empty() {
-                                let mail = self.thread_textareas[1].lines().clone().join("\n");
-                                Some(mail)
-                            } else {
-                                None
-                            };
-
-                            let comment = self.thread_textareas[2].lines().join("\n");
-                            let jar = Account::new().get_jar().await?;
-                            let res = post_reply(
-                                &url,
-                                &comment,
-                                name,
-                                mail,
-                                self.config.request_header.clone(),
-                                Some(jar),
-                            )
-                            .await;
-                            match res {
-                                Ok(message) => {
-                                    warn!("{}", message);
-                                    self.update_message(format!(
-                                        "投稿に成功しました。: {}",
-                                        message
-                                    ));
-                                }
-                                Err(_) => {
-                                    self.update_message(format!("投稿に失敗しました。"));
-                                }
-                            }
+                        Pane::Main => self.post_reply().await?,
+                        Pane::Side if self.editing_identity.is_some() => {
+                            self.save_identity().await?
                         }
-                        Pane::Side => {}
+                        Pane::Side if self.editing_account.is_some() => self.save_account().await?,
+                        Pane::Side if self.editing_setting.is_some() => self.save_setting().await?,
+                        Pane::Side => self.post_thread().await?,
                         _ => {}
                     }
                 }
                 Ok(())
             }
+            Event::OpenPostForm => {
+                if self.layout.focus_pane == Pane::Side
+                    && self.left_tabs.get() == LeftTabItem::Identities
+                {
+                    self.open_identity_form(IdentityStateItem::default());
+                    return Ok(());
+                }
+                if self.layout.focus_pane == Pane::Side
+                    && self.left_tabs.get() == LeftTabItem::Settings
+                {
+                    self.open_account_form(AccountStateItem::default());
+                    return Ok(());
+                }
+                self.open_post_form().await
+            }
+            Event::RetryPost => self.retry_post().await,
+            Event::Delete => {
+                if self.layout.focus_pane == Pane::Side
+                    && self.left_tabs.get() == LeftTabItem::Identities
+                {
+                    self.delete_identity().await?;
+                }
+                if self.layout.focus_pane == Pane::Side
+                    && self.left_tabs.get() == LeftTabItem::Settings
+                {
+                    self.delete_setting().await?;
+                }
+                Ok(())
+            }
+            Event::ToggleMark => {
+                // 設定ではオンとオフや既定のアカウントを切り替える。
+                if self.layout.focus_pane == Pane::Side
+                    && self.left_tabs.get() == LeftTabItem::Settings
+                {
+                    self.toggle_setting().await?;
+                }
+                if self.layout.focus_pane == Pane::Main {
+                    if let Some(post) = self.thread.items.get(self.thread.selected()) {
+                        if !self.marked_posts.remove(&post.index) {
+                            self.marked_posts.insert(post.index);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Event::ToggleVisual => {
+                if self.layout.focus_pane == Pane::Main {
+                    match self.visual_start.take() {
+                        // 範囲を印に変えて、続けて別の範囲を選べるようにする。
+                        Some(_) => {
+                            self.marked_posts = self.selected_post_indexes().into_iter().collect()
+                        }
+                        None => self.visual_start = Some(self.thread.selected()),
+                    }
+                }
+                Ok(())
+            }
+            Event::QuoteReply(with_text) => {
+                if self.layout.focus_pane == Pane::Main {
+                    self.quote_reply(with_text).await?;
+                }
+                Ok(())
+            }
+            Event::NextAccount => {
+                if self.layout.visible_popup && self.editing_account.is_none() {
+                    self.next_account().await?;
+                }
+                Ok(())
+            }
+            Event::NextIdentity => {
+                if self.layout.visible_popup && self.editing_identity.is_none() {
+                    self.next_identity().await?;
+                }
+                Ok(())
+            }
             Event::Exit => todo!(),
             Event::ToggleSidepane => todo!(),
             Event::ToggleFocusPane => todo!(),
@@ -620,8 +1222,9 @@ impl App<'_> {
 
     pub async fn sort_board(&mut self) -> Result<()> {
         let sort_type = self.get_sort_order();
+        // dat落ちしたスレは、どの並び順でも後ろにまとめる。
         self.board.items.par_sort_by(|a, b| {
-            match sort_type.clone() {
+            let order = match sort_type.clone() {
                 Sort::None(order) => {
                     match order {
                         Order::Asc => a.index.cmp(&b.index),
@@ -646,18 +1249,35 @@ impl App<'_> {
                         Order::Desc => b.is_read.partial_cmp(&a.is_read).unwrap(),
                     }
                 }
-            }
+            };
+            a.stopdone.cmp(&b.stopdone).then(order)
         });
 
         Ok(())
     }
 
+    /// 描画したときに表示できたリストの項目の数を戻す。
+    pub fn keep_list_heights(&mut self, drawn: &App) {
+        self.home.state.height = drawn.home.state.height;
+        self.bookmark.state.height = drawn.bookmark.state.height;
+        self.bbsmenu.state.height = drawn.bbsmenu.state.height;
+        self.categories.state.height = drawn.categories.state.height;
+        self.category.state.height = drawn.category.state.height;
+        self.board.state.height = drawn.board.state.height;
+        self.thread.state.height = drawn.thread.state.height;
+        self.notifications.state.height = drawn.notifications.state.height;
+        self.archives.state.height = drawn.archives.state.height;
+        self.histories.state.height = drawn.histories.state.height;
+        self.identities.state.height = drawn.identities.state.height;
+        self.settings.state.height = drawn.settings.state.height;
+    }
+
     pub fn update_message(&mut self, message: String) {
         self.message = message;
     }
 
     pub async fn update_bookmark(&mut self) -> Result<()> {
-        let bookmarks = BookmarkStateItem::get_all().await;
+        let bookmarks = BookmarkStateItem::get_all(&self.db).await;
         match bookmarks {
             Ok(bookmarks) => {
                 self.bookmark.set_items(bookmarks);
@@ -672,15 +1292,18 @@ impl App<'_> {
     }
 
     pub async fn update_bbsmenu(&mut self) -> Result<()> {
-        self.bbsmenu.set_items(BbsMenuStateItem::get().await?);
+        self.bbsmenu
+            .set_items(BbsMenuStateItem::get(&self.db, &self.config.bbsmenu_url_list).await?);
         Ok(())
     }
 
     pub async fn update_categories(&mut self) -> Result<()> {
-        self.bbsmenu.items[self.bbsmenu.selected()].update().await?;
+        let db = self.db.clone();
+        let menu = self.bbsmenu.items[self.bbsmenu.selected()].clone();
+        self.fetch_online(menu.update(&db)).await?;
 
         let menu_id = self.get_menu_id();
-        let categories = CategoriesStateItem::get_by_menu_id(menu_id).await?;
+        let categories = CategoriesStateItem::get_by_menu_id(&self.db, menu_id).await?;
         self.categories.set_items(categories);
         Ok(())
     }
@@ -689,48 +1312,1288 @@ impl App<'_> {
         //現在洗濯中のカテゴりID
         let category_id = self.get_category_id();
         // カテゴリ内の板一覧
-        let category = BoardStateItem::get_by_category_id(category_id).await?;
+        let category = BoardStateItem::get_by_category_id(&self.db, category_id).await?;
         self.category.set_items(category);
         Ok(())
     }
 
     pub async fn update_board_from_bookmark(&mut self) -> Result<()> {
-        self.bookmark.items[self.bookmark.selected()]
-            .clone()
-            .fetch()
-            .await?;
+        let bookmark = self.bookmark.items[self.bookmark.selected()].clone();
+        let db = self.db.clone();
+        self.fetch_board(bookmark.fetch(&db)).await?;
         let board_id = self.get_board_id_by_bookmark();
-        let items = ThreadStateItem::get_by_board_id(board_id).await?;
+        MyPostStateItem::match_threads(&self.db, board_id, &bookmark.url).await?;
+        let items = ThreadStateItem::get_by_board_id(&self.db, board_id).await?;
 
         self.board.set_items(items);
         self.sort_board().await?;
+        self.current_board = Some(BoardStateItem {
+            id:   board_id,
+            url:  bookmark.url,
+            name: bookmark.name,
+        });
         Ok(())
     }
 
     pub async fn update_board(&mut self) -> Result<()> {
-        self.category.items[self.category.selected()]
-            .clone()
-            .fetch()
-            .await?;
+        let board = self.category.items[self.category.selected()].clone();
+        let db = self.db.clone();
+        self.fetch_board(board.fetch(&db)).await?;
         let board_id = self.get_board_id();
-        let items = ThreadStateItem::get_by_board_id(board_id).await?;
+        MyPostStateItem::match_threads(&self.db, board_id, &board.url).await?;
+        let items = ThreadStateItem::get_by_board_id(&self.db, board_id).await?;
 
         self.board.set_items(items);
         self.sort_board().await?;
+        self.current_board = Some(board);
         Ok(())
     }
 
+    /// スレッド一覧で選択しているスレッドを開く。
     pub async fn update_thread(&mut self) -> Result<()> {
-        self.board.items[self.board.selected()]
-            .clone()
-            .fetch()
-            .await?;
+        let thread = self.board.items[self.board.selected()].clone();
+        self.open_thread(thread).await
+    }
+
+    /// 開いているスレッドを再取得する。
+    pub async fn reload_thread(&mut self) -> Result<()> {
+        match self.current_thread.clone() {
+            Some(thread) => self.open_thread(thread).await,
+            None => Ok(()),
+        }
+    }
 
-        let thread_id = self.get_thread_id();
-        let res = ThreadStateItem::update_is_read(thread_id).await?;
-        warn!("{:?}", res);
-        let threads = ThreadPostStateItem::get_by_thread_id(thread_id).await?;
+    /// スレッドを取得して開く。
+    /// 既読なら新着レスの先頭か、前回の位置から表示する。
+    pub async fn open_thread(&mut self, thread: ThreadStateItem) -> Result<()> {
+        // 開いていたスレッドの表示位置を残しておく。
+        self.save_read_position().await?;
+
+        let db = self.db.clone();
+        self.offline.stale_thread = self.fetch_online(thread.fetch(&db)).await?.is_none();
+        self.clear_post_selection();
+
+        let thread_id = thread.id;
+        MyPostStateItem::match_posts(&self.db, thread_id).await?;
+        let read = ThreadStateItem::get_by_id(&self.db, thread_id).await?;
+        let mut threads = ThreadPostStateItem::get_by_thread_id(&self.db, thread_id).await?;
+        let my_indexes = MyPostStateItem::get_indexes_by_thread_id(&self.db, thread_id).await?;
+        ThreadPostStateItem::mark_my_posts(&mut threads, &my_indexes);
+        ThreadPostStateItem::mark_ng(&mut threads, &self.config.ng);
+        let len = threads.len();
         self.thread.set_items(threads);
+
+        let before_read = read.before_read.max(0) as usize;
+        if read.is_read && before_read < len {
+            // 新着レスの先頭から開く。
+            self.first_unread = Some(before_read);
+            self.thread.state.select(Some(before_read));
+            self.thread.state.offset = before_read;
+        } else if read.is_read && len > 0 {
+            // 新着が無ければ前回の位置に戻る。
+            let index = (read.last_read_index.max(0) as usize).min(len - 1);
+            self.first_unread = None;
+            self.thread.state.select(Some(index));
+            self.thread.state.offset = (read.scroll_offset.max(0) as usize).min(index);
+        } else {
+            self.first_unread = None;
+            self.thread.state.select(Some(0));
+        }
+
+        ThreadStateItem::update_is_read(&self.db, thread_id, len as i32).await?;
+        if let Some(item) = self.board.items.iter_mut().find(|t| t.id == thread_id) {
+            item.is_read = true;
+            item.before_read = len as i32;
+        }
+        self.current_thread = Some(ThreadStateItem {
+            is_read: true,
+            before_read: len as i32,
+            ..read
+        });
+        Ok(())
+    }
+
+    /// 印を付けたレスと範囲選択しているレスの番号
+    /// どちらも無ければ選択しているレスの番号
+    pub fn selected_post_indexes(&self) -> Vec<i32> {
+        let mut indexes = self.marked_posts.clone();
+        if let Some(start) = self.visual_start {
+            let end = self.thread.selected();
+            let range = start.min(end)..=start.max(end);
+            indexes.extend(self.thread.items[range].iter().map(|p| p.index));
+        }
+        if indexes.is_empty() {
+            if let Some(post) = self.thread.items.get(self.thread.selected()) {
+                indexes.insert(post.index);
+            }
+        }
+        indexes.into_iter().collect()
+    }
+
+    /// 一覧のi番目のレスが、印か範囲選択で選ばれているか
+    pub fn is_post_selected(&self, i: usize) -> bool {
+        let in_range = self.visual_start.is_some_and(|start| {
+            let end = self.thread.selected();
+            (start.min(end)..=start.max(end)).contains(&i)
+        });
+        in_range
+            || self
+                .thread
+                .items
+                .get(i)
+                .is_some_and(|p| self.marked_posts.contains(&p.index))
+    }
+
+    fn clear_post_selection(&mut self) {
+        self.marked_posts.clear();
+        self.visual_start = None;
+    }
+
+    /// 選択しているレスへのアンカーを本文に足して書き込みフォームを開く。
+    /// with_textなら、レスごとにアンカーと引用した本文を並べる。
+    async fn quote_reply(&mut self, with_text: bool) -> Result<()> {
+        let indexes = self.selected_post_indexes();
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let quote = if with_text {
+            self.thread
+                .items
+                .iter()
+                .filter(|p| indexes.contains(&p.index))
+                .map(|p| {
+                    format!(
+                        "{}\n{}",
+                        format_anchors(&[p.index]),
+                        quote_message(&p.message.to_string())
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            format_anchors(&indexes)
+        };
+
+        self.open_post_form().await?;
+        let mut draft = match self.get_form() {
+            Some(draft) if self.layout.visible_popup => draft,
+            _ => return Ok(()),
+        };
+        if !draft.message.is_empty() {
+            draft.message.push('\n');
+        }
+        draft.message.push_str(&quote);
+        draft.message.push('\n');
+        self.set_form(&draft);
+        // 続けて本文を書けるようにする。
+        self.thread_textareas_which = 2;
+        self.thread_textareas[2].move_cursor(CursorMove::Bottom);
+        self.thread_textareas[2].move_cursor(CursorMove::End);
+        self.clear_post_selection();
+        self.check_form();
+        Ok(())
+    }
+
+    pub async fn update_archives(&mut self) -> Result<()> {
+        let archives = ArchiveStateItem::get_all(&self.db).await?;
+        self.archives.set_items(archives);
+        self.archives.state.select(Some(0));
         Ok(())
     }
+
+    /// 開いているスレか板のアーカイブを切り替える。スレならすぐに保存する。
+    async fn archive_current(&mut self, board: bool, archived: bool) -> Result<()> {
+        if board {
+            let board = match self.current_board.clone() {
+                Some(board) => board,
+                None => bail!("板を開いていません。"),
+            };
+            BoardStateItem::set_archived(&self.db, board.id, archived).await?;
+            self.update_message(if archived {
+                format!("板のスレを全てアーカイブします。: {}", board.name)
+            } else {
+                format!("板のアーカイブをやめました。: {}", board.name)
+            });
+        } else {
+            let thread = match self.current_thread.clone() {
+                Some(thread) => thread,
+                None => bail!("スレを開いていません。"),
+            };
+            ThreadStateItem::set_archived(&self.db, thread.id, archived).await?;
+            if !archived {
+                self.update_message(format!("アーカイブをやめました。: {}", thread.name));
+            } else if self.offline.enabled {
+                self.update_message(format!(
+                    "アーカイブしました。接続が戻ってから保存します。: {}",
+                    thread.name
+                ));
+            } else {
+                let snapshot = archive::snapshot(&self.db, &thread).await?;
+                let failed = if snapshot.failed > 0 {
+                    format!(" 画像{}件は後で取得し直します。", snapshot.failed)
+                } else {
+                    String::new()
+                };
+                self.update_message(format!(
+                    "アーカイブしました。{}レス 画像{}件を保存しました。{}: {}",
+                    snapshot.posts, snapshot.images, failed, thread.name
+                ));
+            }
+        }
+        if self.left_tabs.get() == LeftTabItem::Archive {
+            self.update_archives().await?;
+        }
+        Ok(())
+    }
+
+    /// まだ保存していないアーカイブを、少しずつ取得して保存する。
+    /// 一度に多くのスレを取得しないよう、間隔を空けて数件ずつ進める。
+    /// 取得は別のタスクで行い、経過は`tx`に送る。保存の途中なら何もしない。
+    pub fn snapshot_archives(&mut self, tx: &Sender<Command>) {
+        if self.offline.enabled
+            || self.snapshot_running
+            || self
+                .last_snapshot
+                .is_some_and(|t| t.elapsed() < SNAPSHOT_INTERVAL)
+        {
+            return;
+        }
+        self.last_snapshot = Some(Instant::now());
+        self.snapshot_running = true;
+        tokio::spawn(archive::snapshot_pending(
+            self.db.clone(),
+            SNAPSHOT_BATCH,
+            tx.clone(),
+        ));
+    }
+
+    /// 裏で保存したアーカイブの経過を反映する。
+    pub async fn on_snapshot(&mut self, progress: SnapshotProgress) -> Result<()> {
+        match progress {
+            SnapshotProgress::Saved(name) => {
+                self.update_message(format!("アーカイブを保存しました。: {}", name));
+            }
+            SnapshotProgress::Disconnected => {
+                self.offline.disconnect();
+            }
+            SnapshotProgress::Failed(e) => {
+                self.snapshot_running = false;
+                self.update_message(e);
+            }
+            SnapshotProgress::Done(saved) => {
+                self.snapshot_running = false;
+                if saved == 0 {
+                    return Ok(());
+                }
+                if self.left_tabs.get() == LeftTabItem::Archive {
+                    self.update_archives().await?;
+                }
+                self.update_message(format!("アーカイブを{}件保存しました。", saved));
+            }
+        }
+        Ok(())
+    }
+
+    /// 古いスレを消す。`vacuum`なら空いた領域も詰める。
+    pub async fn clean_database(&mut self, vacuum: bool) -> Result<()> {
+        let cleanup = maintenance::clean(&self.db, self.config.retention_days, vacuum).await?;
+        if vacuum || cleanup.threads > 0 {
+            self.update_message(format!(
+                "スレ{}件 {}レスを消し、{}小さくなりました。",
+                cleanup.threads,
+                cleanup.posts,
+                maintenance::format_size(cleanup.freed.max(0))
+            ));
+        }
+        Ok(())
+    }
+
+    /// アーカイブしていないスレのうち、`days`日より前に取得したスレのレスを消す。
+    /// レスを消したスレの数を返す。
+    pub async fn prune(&mut self, days: u32) -> Result<usize> {
+        let before = Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+        let (threads, posts) = ThreadStateItem::prune(&self.db, before).await?;
+        if threads > 0 {
+            self.update_message(format!(
+                "{}日より前のスレ{}件から{}レスを消しました。",
+                days, threads, posts
+            ));
+        }
+        Ok(threads)
+    }
+
+    pub async fn update_notifications(&mut self) -> Result<()> {
+        let notifications = NotificationStateItem::get_all(&self.db).await;
+        match notifications {
+            Ok(notifications) => {
+                self.notifications.set_items(notifications);
+                self.notifications.state.select(Some(0));
+            }
+            Err(e) => {
+                error!("{}", e);
+                self.update_message("通知の取得に失敗しました。".to_string());
+                bail!(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn update_histories(&mut self) -> Result<()> {
+        let histories = PostHistoryStateItem::get_all(&self.db).await;
+        match histories {
+            Ok(histories) => {
+                self.histories.set_items(histories);
+                self.histories.state.select(Some(0));
+            }
+            Err(e) => {
+                error!("{}", e);
+                self.update_message("書き込み履歴の取得に失敗しました。".to_string());
+                bail!(e);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn update_identities(&mut self) -> Result<()> {
+        let identities = IdentityStateItem::get_all(&self.db).await;
+        match identities {
+            Ok(identities) => {
+                self.identities.set_items(identities);
+                self.identities.state.select(Some(0));
+            }
+            Err(e) => {
+                error!("{}", e);
+                self.update_message("名前の取得に失敗しました。".to_string());
+                bail!(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 名前の編集フォームを開く。idが0なら新しく追加する。
+    fn open_identity_form(&mut self, identity: IdentityStateItem) {
+        let textarea = |text: &str| {
+            if text.is_empty() {
+                TextArea::default()
+            } else {
+                TextArea::new(vec![text.to_string()])
+            }
+        };
+        self.board_textareas = vec![
+            textarea(&identity.label),
+            textarea(&identity.name),
+            textarea(identity.trip_key.as_deref().unwrap_or_default()),
+            textarea(&identity.email),
+        ];
+        self.board_textareas_which = 0;
+        self.post_problems = vec![];
+        self.editing_identity = Some(identity);
+        self.layout.visible_popup = true;
+    }
+
+    /// 編集フォームの内容で名前を保存する。
+    async fn save_identity(&mut self) -> Result<()> {
+        let id = match &self.editing_identity {
+            Some(identity) => identity.id,
+            None => return Ok(()),
+        };
+        let text = |t: &TextArea| t.lines().join("").trim().to_string();
+        let identity = IdentityStateItem {
+            id,
+            label: text(&self.board_textareas[0]),
+            name: text(&self.board_textareas[1]),
+            trip_key: Some(text(&self.board_textareas[2])).filter(|k| !k.is_empty()),
+            email: text(&self.board_textareas[3]),
+        };
+        if identity.label.is_empty() {
+            self.update_message("ラベルを入力してください。".to_string());
+            return Ok(());
+        }
+        if let Err(e) = identity.save(&self.db).await {
+            self.update_message(format!("名前の保存に失敗しました。: {}", e));
+            return Ok(());
+        }
+
+        self.editing_identity = None;
+        self.layout.visible_popup = false;
+        self.input_mode = false;
+        self.update_identities().await?;
+        self.update_message(format!("名前を保存しました。: {}", identity.label));
+        Ok(())
+    }
+
+    /// 選択している名前を削除する。
+    async fn delete_identity(&mut self) -> Result<()> {
+        let identity = match self.identities.items.get(self.identities.selected()) {
+            Some(identity) => identity.clone(),
+            None => return Ok(()),
+        };
+        match IdentityStateItem::delete(&self.db, identity.id).await {
+            Ok(()) => {
+                self.update_message(format!("名前を削除しました。: {}", identity.label));
+            }
+            Err(e) => {
+                self.update_message(format!("名前の削除に失敗しました。: {}", e));
+            }
+        }
+        self.update_identities().await
+    }
+
+    /// 設定とアカウントの一覧を読み込み直す。選択している位置はそのままにする。
+    pub async fn update_settings(&mut self) -> Result<()> {
+        let accounts = AccountStateItem::get_all(&self.db).await;
+        match accounts {
+            Ok(accounts) => {
+                let selected = self.settings.selected();
+                let items = SettingStateItem::all(
+                    &self.config,
+                    accounts,
+                    maintenance::database_size(&self.db).await?,
+                    maintenance::board_usage(&self.db).await?,
+                );
+                self.settings
+                    .state
+                    .select(Some(selected.min(items.len().saturating_sub(1))));
+                self.settings.items = items;
+            }
+            Err(e) => {
+                error!("{}", e);
+                self.update_message("アカウントの取得に失敗しました。".to_string());
+                bail!(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn selected_setting(&self) -> Option<SettingStateItem> {
+        self.settings.items.get(self.settings.selected()).cloned()
+    }
+
+    /// 切り替える項目は切り替え、それ以外は編集フォームを開く。
+    async fn enter_setting(&mut self) -> Result<()> {
+        let setting = match self.selected_setting() {
+            Some(setting) => setting,
+            None => return Ok(()),
+        };
+        match setting {
+            SettingStateItem::Account(account) => self.open_account_form(account),
+            SettingStateItem::AddAccount => self.open_account_form(AccountStateItem::default()),
+            SettingStateItem::Database(..) => {
+                self.clean_database(true).await?;
+                self.update_settings().await?;
+            }
+            SettingStateItem::BoardUsage(..) => {}
+            setting if setting.toggle(&mut self.config) => self.apply_config().await?,
+            setting => {
+                let value = setting.value(&self.config);
+                self.board_textareas = vec![if value.is_empty() {
+                    TextArea::default()
+                } else {
+                    TextArea::new(value.lines().map(String::from).collect())
+                }];
+                self.board_textareas_which = 0;
+                self.post_problems = vec![];
+                self.editing_setting = Some(setting);
+                self.layout.visible_popup = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// 編集フォームの内容を検査して設定を保存する。
+    async fn save_setting(&mut self) -> Result<()> {
+        let setting = match &self.editing_setting {
+            Some(setting) => setting.clone(),
+            None => return Ok(()),
+        };
+        let value = self.board_textareas[0].lines().join("\n");
+        let old = setting.value(&self.config);
+        if let Err(e) = setting.apply(&mut self.config, &value) {
+            self.update_message(e.to_string());
+            return Ok(());
+        }
+        // URLを書き換えたbbsmenuは、削除したときと同じく古いメニューを消す。
+        if matches!(setting, SettingStateItem::BbsmenuUrl(..)) && old != setting.value(&self.config)
+        {
+            if let Err(e) = BbsMenuStateItem::delete(&self.db, &old).await {
+                warn!("{}", e);
+            }
+        }
+
+        self.editing_setting = None;
+        self.layout.visible_popup = false;
+        self.input_mode = false;
+        self.board_textareas = vec![TextArea::default(); 4];
+        self.apply_config().await?;
+        self.update_message(format!("設定を保存しました。: {}", setting.label()));
+        Ok(())
+    }
+
+    /// Spaceではオンとオフなどの切り替えと、既定のアカウントの切り替えをする。
+    async fn toggle_setting(&mut self) -> Result<()> {
+        match self.selected_setting() {
+            Some(SettingStateItem::Account(account)) => self.toggle_default_account(account).await,
+            Some(setting) if setting.toggle(&mut self.config) => self.apply_config().await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn delete_setting(&mut self) -> Result<()> {
+        match self.selected_setting() {
+            Some(SettingStateItem::Account(account)) => self.delete_account(account).await,
+            Some(setting) => {
+                let url = setting.value(&self.config);
+                if setting.remove(&mut self.config) {
+                    // 取得済みの板が残っているメニューは消せないので、設定からだけ外す。
+                    if let Err(e) = BbsMenuStateItem::delete(&self.db, &url).await {
+                        warn!("{}", e);
+                    }
+                    self.apply_config().await?;
+                    self.update_message(format!("bbsmenuを削除しました。: {}", url));
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// 設定をファイルに書き出し、再起動せずに反映する。
+    async fn apply_config(&mut self) -> Result<()> {
+        if let Err(e) = self.config.write() {
+            self.update_message(format!("設定の保存に失敗しました。: {}", e));
+        }
+        self.reload_config(self.config.clone()).await
+    }
+
+    /// 読み込んだ設定を反映する。キャッシュから復元したときと、設定ファイルが更新されたときに使う。
+    pub async fn reload_config(&mut self, config: Config) -> Result<()> {
+        self.config = config;
+        self.theme = self.config.theme.clone();
+        self.request_header = self.config.request_header.clone();
+        self.keymap = Keymap::new(&self.config.keymap)?;
+        BbsMenuStateItem::init(&self.db, self.config.bbsmenu_url_list.clone()).await;
+        self.update_bbsmenu().await?;
+        if !self.settings.items.is_empty() {
+            self.update_settings().await?;
+        }
+        ThreadPostStateItem::mark_ng(&mut self.thread.items, &self.config.ng);
+        Ok(())
+    }
+
+    /// アカウントの編集フォームを開く。idが0なら新しく追加する。
+    /// パスワードは表示せず、空のままなら変更しない。
+    fn open_account_form(&mut self, account: AccountStateItem) {
+        let textarea = |text: &str| {
+            if text.is_empty() {
+                TextArea::default()
+            } else {
+                TextArea::new(vec![text.to_string()])
+            }
+        };
+        // パスワードは画面に出さない。
+        let mut password = TextArea::default();
+        password.set_mask_char('●');
+        self.board_textareas = vec![
+            textarea(&account.label),
+            textarea(account.email.as_deref().unwrap_or_default()),
+            password,
+        ];
+        self.board_textareas_which = 0;
+        self.post_problems = vec![];
+        self.editing_account = Some(account);
+        self.layout.visible_popup = true;
+    }
+
+    /// 編集フォームの内容でアカウントを保存する。
+    async fn save_account(&mut self) -> Result<()> {
+        let account = match &self.editing_account {
+            Some(account) => account.clone(),
+            None => return Ok(()),
+        };
+        let text = |t: &TextArea| t.lines().join("").trim().to_string();
+        let label = text(&self.board_textareas[0]);
+        let email = text(&self.board_textareas[1]);
+        let password = Some(text(&self.board_textareas[2])).filter(|p| !p.is_empty());
+        if label.is_empty() || email.is_empty() {
+            self.update_message("ラベルとメールアドレスを入力してください。".to_string());
+            return Ok(());
+        }
+        if account.id == 0 && password.is_none() {
+            self.update_message("パスワードを入力してください。".to_string());
+            return Ok(());
+        }
+        let first = account.id == 0 && AccountStateItem::get_all(&self.db).await?.is_empty();
+
+        let account = AccountStateItem { label, ..account };
+        if let Err(e) = account.save(&self.db, &email, password.as_deref()).await {
+            self.update_message(format!("アカウントの保存に失敗しました。: {}", e));
+            return Ok(());
+        }
+        // ログインし直すように、覚えているセッションを捨てる。
+        self.sessions.remove(&account.id);
+
+        self.editing_account = None;
+        self.layout.visible_popup = false;
+        self.input_mode = false;
+        // 残ったパスワードを消しておく。
+        self.board_textareas = vec![TextArea::default(); 4];
+        // 初めてアカウントを追加したときは、ログインして書き込むようにする。
+        if first && !self.config.login {
+            self.config.login = true;
+            self.apply_config().await?;
+        }
+        self.update_settings().await?;
+        self.update_message(format!("アカウントを保存しました。: {}", account.label));
+        Ok(())
+    }
+
+    async fn delete_account(&mut self, account: AccountStateItem) -> Result<()> {
+        self.sessions.remove(&account.id);
+        match AccountStateItem::delete(&self.db, account.id).await {
+            Ok(()) => {
+                self.update_message(format!("アカウントを削除しました。: {}", account.label));
+            }
+            Err(e) => {
+                self.update_message(format!("アカウントの削除に失敗しました。: {}", e));
+            }
+        }
+        self.update_settings().await
+    }
+
+    /// アカウントを既定のアカウントにする。既に既定なら外す。
+    async fn toggle_default_account(&mut self, account: AccountStateItem) -> Result<()> {
+        if AccountStateItem::toggle_default(&self.db, account.id).await? {
+            self.update_message(format!("既定のアカウントにしました。: {}", account.label));
+        } else {
+            self.update_message("既定のアカウントを外しました。".to_string());
+        }
+        self.update_settings().await
+    }
+
+    /// 書き込み先の板で使うアカウントを順に切り替える。
+    /// 最後のアカウントの次は、既定のアカウントを使う状態に戻る。
+    async fn next_account(&mut self) -> Result<()> {
+        let url = match self.post_target() {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+        let board_url = board_url_of(&url, self.layout.focus_pane == Pane::Side);
+        let accounts = AccountStateItem::get_all(&self.db).await?;
+        let current = AccountStateItem::get_by_board(&self.db, &board_url)
+            .await?
+            .and_then(|c| accounts.iter().position(|a| a.id == c.id));
+        let next = match current {
+            Some(i) => accounts.get(i + 1),
+            None => accounts.first(),
+        }
+        .cloned();
+        AccountStateItem::set_board(&self.db, &board_url, next.as_ref().map(|a| a.id)).await?;
+
+        self.post_account = AccountStateItem::get_for_board(&self.db, &board_url).await?;
+        match (&next, &self.post_account) {
+            (Some(account), _) => {
+                self.update_message(format!("アカウントを切り替えました。: {}", account.label));
+            }
+            (None, Some(account)) => {
+                self.update_message(format!("既定のアカウントを使います。: {}", account.label));
+            }
+            (None, None) => self.update_message("ログインせずに書き込みます。".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 板で使うアカウントでログインしたCookieを返す。
+    /// ログインしない設定か、アカウントが無ければNone
+    /// 一度ログインしたら、終了するまで同じCookieを使う。
+    async fn account_jar(&mut self, board_url: &str) -> Result<Option<Arc<Jar>>> {
+        if !self.config.login {
+            return Ok(None);
+        }
+        let account = match AccountStateItem::get_for_board(&self.db, board_url).await? {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+        if let Some(jar) = self.sessions.get(&account.id) {
+            return Ok(Some(Arc::clone(jar)));
+        }
+        let jar = credentials::Account::load(account.id).get_jar().await?;
+        self.sessions.insert(account.id, Arc::clone(&jar));
+        Ok(Some(jar))
+    }
+
+    /// 書き込み先の板で使う名前を順に切り替え、名前とメール欄に入れる。
+    /// 最後の名前の次は、名前を使わない状態に戻る。
+    async fn next_identity(&mut self) -> Result<()> {
+        let mut draft = match self.get_form() {
+            Some(draft) => draft,
+            None => return Ok(()),
+        };
+        let board_url = board_url_of(&draft.url, self.layout.focus_pane == Pane::Side);
+        let identities = IdentityStateItem::get_all(&self.db).await?;
+        let current = self
+            .post_identity
+            .as_ref()
+            .and_then(|c| identities.iter().position(|i| i.id == c.id));
+        let next = match current {
+            Some(i) => identities.get(i + 1),
+            None => identities.first(),
+        }
+        .cloned();
+        IdentityStateItem::set_board(&self.db, &board_url, next.as_ref().map(|i| i.id)).await?;
+
+        match &next {
+            Some(identity) => {
+                draft.name = identity.name.clone();
+                draft.email = identity.email.clone();
+                self.update_message(format!("名前を切り替えました。: {}", identity.label));
+            }
+            None => {
+                draft.name = String::new();
+                draft.email = String::new();
+                self.update_message("名前を使わずに書き込みます。".to_string());
+            }
+        }
+        let which = (self.thread_textareas_which, self.board_textareas_which);
+        self.set_form(&draft);
+        (self.thread_textareas_which, self.board_textareas_which) = which;
+        self.post_identity = next;
+        self.check_form();
+        Ok(())
+    }
+
+    /// フォーカスしているペインの書き込み先のURL
+    /// スレッドなら書き込み、板ならスレ立てになる。
+    fn post_target(&self) -> Option<String> {
+        match self.layout.focus_pane {
+            Pane::Main => self.current_thread.as_ref().map(|t| t.url.clone()),
+            Pane::Side if matches!(self.left_tabs.get(), LeftTabItem::Board(..)) => {
+                self.current_board.as_ref().map(|b| b.url.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// フォームの内容を下書きとして取り出す。
+    fn get_form(&self) -> Option<DraftStateItem> {
+        let url = self.post_target()?;
+        let text = |t: &TextArea| t.lines().join("\n");
+        match self.layout.focus_pane {
+            Pane::Main => {
+                Some(DraftStateItem {
+                    url,
+                    name: text(&self.thread_textareas[0]),
+                    email: text(&self.thread_textareas[1]),
+                    subject: None,
+                    message: text(&self.thread_textareas[2]),
+                })
+            }
+            _ => {
+                Some(DraftStateItem {
+                    url,
+                    name: text(&self.board_textareas[0]),
+                    email: text(&self.board_textareas[1]),
+                    subject: Some(text(&self.board_textareas[2])),
+                    message: text(&self.board_textareas[3]),
+                })
+            }
+        }
+    }
+
+    /// 下書きの内容をフォームに書き戻す。
+    fn set_form(&mut self, draft: &DraftStateItem) {
+        let textarea = |text: &str| {
+            if text.is_empty() {
+                TextArea::default()
+            } else {
+                TextArea::new(text.lines().map(String::from).collect())
+            }
+        };
+        match self.layout.focus_pane {
+            Pane::Main => {
+                self.thread_textareas = vec![
+                    textarea(&draft.name),
+                    textarea(&draft.email),
+                    textarea(&draft.message),
+                ];
+                self.thread_textareas_which = 0;
+            }
+            _ => {
+                self.board_textareas = vec![
+                    textarea(&draft.name),
+                    textarea(&draft.email),
+                    textarea(draft.subject.as_deref().unwrap_or_default()),
+                    textarea(&draft.message),
+                ];
+                self.board_textareas_which = 0;
+            }
+        }
+    }
+
+    /// 書き込みフォームを開き、書き込み先の下書きがあれば読み込む。
+    pub async fn open_post_form(&mut self) -> Result<()> {
+        let url = match self.post_target() {
+            Some(url) => url,
+            None => return Ok(()),
+        };
+        let board_url = board_url_of(&url, self.layout.focus_pane == Pane::Side);
+        self.post_identity = IdentityStateItem::get_by_board(&self.db, &board_url).await?;
+        self.post_account = AccountStateItem::get_for_board(&self.db, &board_url).await?;
+
+        // 下書きが無ければ、板で使う名前を入れておく。
+        let draft = match DraftStateItem::get(&self.db, &url).await? {
+            Some(draft) => draft,
+            None => {
+                DraftStateItem {
+                    name: self
+                        .post_identity
+                        .as_ref()
+                        .map(|i| i.name.clone())
+                        .unwrap_or_default(),
+                    email: self
+                        .post_identity
+                        .as_ref()
+                        .map(|i| i.email.clone())
+                        .unwrap_or_default(),
+                    ..Default::default()
+                }
+            }
+        };
+        self.set_form(&draft);
+        self.layout.visible_popup = true;
+
+        // 板の上限は一度だけ取得する。取得できなければ一般的な値で検査する。
+        if let Entry::Vacant(entry) = self.post_limits.entry(board_url) {
+            let limits = PostLimits::fetch(entry.key()).await.unwrap_or_else(|e| {
+                warn!("{}", e);
+                PostLimits::default()
+            });
+            entry.insert(limits);
+        }
+        self.confirm_ncr = false;
+        self.check_form();
+        Ok(())
+    }
+
+    /// フォームの内容を検査して、問題を`post_problems`に入れる。
+    pub fn check_form(&mut self) {
+        let draft = match self.get_form() {
+            Some(draft) => draft,
+            None => {
+                self.post_problems = vec![];
+                return;
+            }
+        };
+        let is_thread = self.layout.focus_pane == Pane::Side;
+        let limits = self
+            .post_limits
+            .get(&board_url_of(&draft.url, is_thread))
+            .cloned()
+            .unwrap_or_default();
+        self.post_problems = validate_post(
+            &draft.name,
+            &draft.email,
+            draft.subject.as_deref(),
+            &draft.message,
+            &limits,
+        );
+    }
+
+    /// 書き込みフォームの内容を、書き込んだ後の見た目のレスにする。
+    /// 名前はトリップを計算し、空なら板の名無しの名前にする。
+    pub fn preview_post(&self) -> Option<ThreadPostStateItem> {
+        let draft = self.get_form()?;
+        let limits = self
+            .post_limits
+            .get(&board_url_of(&draft.url, false))
+            .cloned()
+            .unwrap_or_default();
+        Some(ThreadPostStateItem {
+            index: self.thread.items.last().map_or(1, |p| p.index + 1),
+            post_id: "ID:????".to_string(),
+            message: preview_message(&draft.message),
+            date: chrono::Utc::now().timestamp(),
+            email: Some(draft.email),
+            name: format_name(&self.name_with_key(&draft.name), &limits.noname_name),
+            is_mine: true,
+            ..Default::default()
+        })
+    }
+
+    /// 書き込みフォームで名前を使っていれば、名前欄にトリップキーを付ける。
+    fn name_with_key(&self, name: &str) -> String {
+        match &self.post_identity {
+            Some(identity) => identity.name_with_key(name),
+            None => name.to_string(),
+        }
+    }
+
+    /// フォームの内容を検査し、送信できるものを返す。
+    /// 表現できない文字だけなら、二回目の送信で数値文字参照に置き換える。
+    fn checked_form(&mut self) -> Option<DraftStateItem> {
+        let mut draft = self.get_form()?;
+        self.check_form();
+        if let Some(problem) = self.post_problems.iter().find(|p| !p.is_substitutable()) {
+            self.update_message(format!("送信できません。: {}", problem));
+            return None;
+        }
+        if !self.post_problems.is_empty() {
+            if !self.confirm_ncr {
+                self.confirm_ncr = true;
+                self.update_message(format!(
+                    "Shift_JISで表現できない文字があります。もう一度{}を押すと数値文字参照に置き換えて送信します。",
+                    self.keymap.hint(Mode::Popup, Action::Post)
+                ));
+                return None;
+            }
+            draft.name = to_ncr(&draft.name);
+            draft.email = to_ncr(&draft.email);
+            draft.subject = draft.subject.map(|s| to_ncr(&s));
+            draft.message = to_ncr(&draft.message);
+        }
+        self.confirm_ncr = false;
+        Some(draft)
+    }
+
+    /// フォームの内容を外部エディタで編集する。
+    /// Shift_JISで表現できない文字などの問題があれば警告する。
+    pub fn edit_form_in_editor(&mut self) -> Result<()> {
+        let draft = match self.get_form() {
+            Some(draft) => draft,
+            None => return Ok(()),
+        };
+        let is_thread = self.layout.focus_pane == Pane::Side;
+        let draft = match editor::edit(&draft, is_thread) {
+            Ok(draft) => draft,
+            Err(e) => {
+                self.update_message(format!("エディタでの編集に失敗しました。: {}", e));
+                return Ok(());
+            }
+        };
+        self.set_form(&draft);
+
+        self.confirm_ncr = false;
+        self.check_form();
+        match self.post_problems.first() {
+            Some(problem) => self.update_message(format!("{}", problem)),
+            None => self.update_message("エディタの内容を反映しました。".to_string()),
+        }
+        Ok(())
+    }
+
+    /// 書きかけのフォームを保存する。
+    pub async fn save_draft(&mut self) -> Result<()> {
+        if let Some(draft) = self.get_form() {
+            draft.save(&self.db).await?;
+        }
+        Ok(())
+    }
+
+    /// 開いているスレッドに書き込む。
+    pub async fn post_reply(&mut self) -> Result<()> {
+        if let Some(draft) = self.checked_form() {
+            self.send_post(PostHistoryStateItem::from_draft(draft, false))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 開いている板にスレッドを立てる。
+    pub async fn post_thread(&mut self) -> Result<()> {
+        if let Some(draft) = self.checked_form() {
+            self.send_post(PostHistoryStateItem::from_draft(draft, true))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// 最後に失敗した書き込みを、保存したフォームの内容で再送する。
+    pub async fn retry_post(&mut self) -> Result<()> {
+        match PostHistoryStateItem::get_last_failed(&self.db).await? {
+            Some(post) => self.send_post(post).await,
+            None => {
+                self.update_message("再送する書き込みはありません。".to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// 書き込みを送信して履歴に残す。
+    /// 成功したら自分の書き込みとして記録し、失敗したらフォームの内容を下書きに残す。
+    async fn send_post(&mut self, post: PostHistoryStateItem) -> Result<()> {
+        if self.offline.enabled {
+            return self.queue_post(&post).await;
+        }
+        match self.deliver(&post).await? {
+            Delivery::Sent(message) => {
+                warn!("{}", message);
+                DraftStateItem::delete(&self.db, &post.url).await?;
+                self.close_form();
+                self.record_sent_post(&post).await?;
+            }
+            Delivery::Failed(reason) => {
+                post.add(&self.db, false, &reason).await?;
+                DraftStateItem::from(&post).save(&self.db).await?;
+                self.update_message(self.post_failed_message(&reason));
+            }
+            Delivery::Disconnected => {
+                self.offline.disconnect();
+                self.queue_post(&post).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 送れなかったときの表示 再送のキーを添える。
+    fn post_failed_message(&self, reason: &str) -> String {
+        format!(
+            "投稿に失敗しました。({}: 再送): {}",
+            self.keymap.hint(Mode::Normal, Action::RetryPost),
+            reason
+        )
+    }
+
+    /// 書き込みを送る。接続できなかったときは、失敗とは分けて返す。
+    async fn deliver(&mut self, post: &PostHistoryStateItem) -> Result<Delivery> {
+        // トリップキーは履歴や下書きに残さず、送信するときだけ付ける。
+        let board_url = board_url_of(&post.url, post.is_thread);
+        let name = match IdentityStateItem::get_by_board(&self.db, &board_url).await? {
+            Some(identity) => identity.name_with_key(&post.name),
+            None => post.name.clone(),
+        };
+        let name = Some(name).filter(|n| !n.is_empty());
+        let email = Some(post.email.clone()).filter(|m| !m.is_empty());
+        let jar = match self.account_jar(&board_url).await {
+            Ok(jar) => jar,
+            Err(e) => return Ok(Delivery::Failed(e.to_string())),
+        };
+        let res = if post.is_thread {
+            create_thread(
+                &post.url,
+                post.subject.as_deref().unwrap_or_default(),
+                &post.message,
+                name.as_deref(),
+                email.as_deref(),
+                &self.config.request_header,
+                jar,
+            )
+            .await
+        } else {
+            post_reply(
+                &post.url,
+                &post.message,
+                name,
+                email,
+                self.config.request_header.clone(),
+                jar,
+            )
+            .await
+        };
+        Ok(match res {
+            Ok(message) => Delivery::Sent(message),
+            Err(e) if is_disconnected(e.chain()) => Delivery::Disconnected,
+            Err(e) => Delivery::Failed(e.to_string()),
+        })
+    }
+
+    /// オフラインのあいだは書き込みを取っておき、接続が戻ってから送る。
+    async fn queue_post(&mut self, post: &PostHistoryStateItem) -> Result<()> {
+        QueuedPostStateItem::add(&self.db, post).await?;
+        DraftStateItem::delete(&self.db, &post.url).await?;
+        self.close_form();
+        self.offline.queued = QueuedPostStateItem::count(&self.db).await?;
+        self.update_message(format!(
+            "オフラインのため、接続が戻ってから送信します。(送信待ち{}件)",
+            self.offline.queued
+        ));
+        Ok(())
+    }
+
+    /// 送信を待っている書き込みを、書いた順に送る。
+    /// 接続できなければ残りはそのまま待たせ、失敗したものは下書きに戻す。
+    pub async fn send_queued_posts(&mut self) -> Result<()> {
+        for queued in QueuedPostStateItem::get_all(&self.db).await? {
+            let post = queued.post;
+            match self.deliver(&post).await? {
+                Delivery::Sent(message) => {
+                    warn!("{}", message);
+                    QueuedPostStateItem::delete(&self.db, queued.id).await?;
+                    self.record_sent_post(&post).await?;
+                }
+                Delivery::Failed(reason) => {
+                    QueuedPostStateItem::delete(&self.db, queued.id).await?;
+                    post.add(&self.db, false, &reason).await?;
+                    DraftStateItem::from(&post).save(&self.db).await?;
+                    self.update_message(self.post_failed_message(&reason));
+                }
+                Delivery::Disconnected => {
+                    self.offline.disconnect();
+                    break;
+                }
+            }
+        }
+        self.offline.queued = QueuedPostStateItem::count(&self.db).await?;
+        Ok(())
+    }
+
+    fn close_form(&mut self) {
+        if self.layout.visible_popup {
+            self.set_form(&DraftStateItem::default());
+            self.post_problems = vec![];
+            self.layout.visible_popup = false;
+            self.input_mode = false;
+        }
+    }
+
+    /// 送れた書き込みを履歴に残す。
+    async fn record_sent_post(&mut self, post: &PostHistoryStateItem) -> Result<()> {
+        post.add(&self.db, true, "").await?;
+        self.record_my_post(post).await?;
+        if post.is_thread {
+            self.update_message(format!(
+                "スレッドを作成しました。: {}",
+                post.subject.as_deref().unwrap_or_default()
+            ));
+        } else {
+            self.update_message("投稿に成功しました。".to_string());
+        }
+        Ok(())
+    }
+
+    /// 取得してデータベースを更新する。取得できなければ`None`を返す。
+    /// オフラインのときは取得せず、接続できなければオフラインに切り替えて、
+    /// どちらも保存済みのデータを表示できるようにする。
+    async fn fetch_online<T>(
+        &mut self,
+        fetch: impl Future<Output = Result<T>>,
+    ) -> Result<Option<T>> {
+        if self.offline.enabled {
+            return Ok(None);
+        }
+        match fetch.await {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if is_disconnected(e.chain()) => {
+                self.offline.disconnect();
+                self.update_message(
+                    "接続できないため、オフラインにしました。保存済みのデータを表示します。"
+                        .to_string(),
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// スレ一覧を取得し、前回の取得から変わったスレの数を知らせる。
+    async fn fetch_board(&mut self, fetch: impl Future<Output = Result<BoardSync>>) -> Result<()> {
+        let sync = self.fetch_online(fetch).await?;
+        self.offline.stale_board = sync.is_none();
+        if let Some(sync) = sync.filter(|sync| *sync != BoardSync::default()) {
+            self.update_message(format!(
+                "新着スレ{}件 新着レスのあるスレ{}件 dat落ち{}件",
+                sync.new, sync.updated, sync.dropped
+            ));
+        }
+        Ok(())
+    }
+
+    /// 開いている板か板一覧に繋がるか確かめ、繋がればオンラインに戻る。
+    pub async fn check_connection(&mut self) -> Result<()> {
+        let url = match &self.current_board {
+            Some(board) => board.url.clone(),
+            None => {
+                match self.bbsmenu.items.first() {
+                    Some(menu) => menu.url.clone(),
+                    None => return Ok(()),
+                }
+            }
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(3))
+            .build()?;
+        if client.head(&url).send().await.is_ok() {
+            self.update_message("接続が戻りました。".to_string());
+            self.set_online().await?;
+        }
+        Ok(())
+    }
+
+    /// 自分でオフラインにする。接続が戻っても自動ではオンラインに戻さない。
+    pub async fn set_offline(&mut self) -> Result<()> {
+        self.offline.enabled = true;
+        self.offline.auto = false;
+        self.offline.queued = QueuedPostStateItem::count(&self.db).await?;
+        Ok(())
+    }
+
+    /// オンラインに戻し、送信を待っていた書き込みを送る。
+    pub async fn set_online(&mut self) -> Result<()> {
+        self.offline.enabled = false;
+        self.offline.auto = false;
+        self.send_queued_posts().await
+    }
+
+    /// 成功した書き込みを、返信の通知のために記録する。
+    async fn record_my_post(&self, post: &PostHistoryStateItem) -> Result<()> {
+        if post.is_thread {
+            return MyPostStateItem::add_thread(
+                &self.db,
+                &post.url,
+                post.subject.as_deref().unwrap_or_default(),
+                &post.name,
+                &post.email,
+                &post.message,
+            )
+            .await;
+        }
+
+        let thread = match ThreadStateItem::get_by_url(&self.db, &post.url).await? {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        let after_count = ThreadPostStateItem::get_by_thread_id(&self.db, thread.id)
+            .await?
+            .len();
+        let board_url = ThreadParams::new(&post.url).build_board();
+        MyPostStateItem::add_reply(
+            &self.db,
+            &board_url,
+            &thread,
+            &post.name,
+            &post.email,
+            &post.message,
+            after_count as i32,
+        )
+        .await
+    }
+
+    /// 開いているスレッドの選択位置とスクロール位置をDBに保存する。
+    pub async fn save_read_position(&mut self) -> Result<()> {
+        if let Some(thread) = &self.current_thread {
+            ThreadStateItem::update_read_position(
+                &self.db,
+                thread.id,
+                self.thread.selected() as i32,
+                self.thread.state.offset as i32,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// 書き込みを送った結果
+enum Delivery {
+    Sent(String),
+    Failed(String),
+    /// 接続できなかった 送信を待たせる
+    Disconnected,
+}
+
+/// 書き込み先のURLから板のURLを求める。
+fn board_url_of(url: &str, is_thread: bool) -> String {
+    if is_thread {
+        url.to_string()
+    } else {
+        ThreadParams::new(url).build_board()
+    }
 }
@@ -0,0 +1,206 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+use sea_orm::DbConn;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    config::dirs::Dir,
+    event::Command,
+    export::image_file_name,
+    state::{
+        archive::ArchiveStateItem,
+        image::ImageStateItem,
+        offline::is_disconnected,
+        post::ThreadPostStateItem,
+        thread::ThreadStateItem,
+    },
+};
+
+/// 一つのスレを保存した結果
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub posts:  usize,
+    pub images: usize,
+    /// 取得できなかった画像の数
+    pub failed: usize,
+}
+
+/// 裏でアーカイブを保存した経過 `Command::Snapshot`で画面の側に送る。
+#[derive(Debug)]
+pub enum SnapshotProgress {
+    /// 一つのスレを保存した
+    Saved(String),
+    /// 接続できなくなったので、途中でやめた
+    Disconnected,
+    /// 保存できずにやめた
+    Failed(String),
+    /// 全て終わった 保存したスレの数
+    Done(usize),
+}
+
+/// まだ保存していないアーカイブを、`batch`件まで保存する。
+/// 画面の操作を止めないよう、別のタスクで自分のコネクションを使って呼び出す。
+pub async fn snapshot_pending(db: DbConn, batch: usize, tx: Sender<Command>) {
+    let send = |progress| tx.send(Command::Snapshot(progress));
+    let pending = match ArchiveStateItem::get_all(&db).await {
+        Ok(archives) => archives,
+        Err(e) => {
+            let _ = send(SnapshotProgress::Failed(e.to_string())).await;
+            return;
+        }
+    };
+    let mut saved = 0;
+    for archive in pending
+        .into_iter()
+        .filter(ArchiveStateItem::is_pending)
+        .take(batch)
+    {
+        match snapshot(&db, &archive.thread).await {
+            Ok(_) => {
+                saved += 1;
+                let _ = send(SnapshotProgress::Saved(archive.thread.name.clone())).await;
+            }
+            Err(e) if is_disconnected(e.chain()) => {
+                let _ = send(SnapshotProgress::Disconnected).await;
+                break;
+            }
+            Err(e) => {
+                let _ = send(SnapshotProgress::Failed(e.to_string())).await;
+                return;
+            }
+        }
+    }
+    let _ = send(SnapshotProgress::Done(saved)).await;
+}
+
+/// スレのレスを全て取得し、レスにある画像を全てアーカイブの場所に保存する。
+/// dat落ちなどで取得できなければ、保存済みのレスの画像だけを保存する。
+/// 接続できないときや取得できない画像があったときは保存した時刻を記録せず、次の機会に保存し直す。
+pub async fn snapshot(db: &DbConn, thread: &ThreadStateItem) -> Result<Snapshot> {
+    if let Err(e) = thread.fetch(db).await {
+        if is_disconnected(e.chain()) {
+            return Err(e);
+        }
+        warn!("Failed to fetch archived thread {}: {}", thread.url, e);
+    }
+    let posts = ThreadPostStateItem::get_by_thread_id(db, thread.id).await?;
+    let root = Dir::get_archive_path()?;
+    let dir = archive_dir(&root, &thread.url);
+    // 記録した場所は正規化してあるので、比べる側もそろえる。
+    let root = fs::canonicalize(&root).unwrap_or(root);
+
+    let mut snapshot = Snapshot {
+        posts: posts.len(),
+        ..Default::default()
+    };
+    let urls = posts
+        .iter()
+        .flat_map(|post| post.message.images.iter())
+        .collect::<Vec<_>>();
+    for (i, url) in urls.into_iter().enumerate() {
+        let path = dir.join(image_file_name(url, i));
+        if let Some(image) = ImageStateItem::get_saved(db, url).await? {
+            if Path::new(&image.save_path).starts_with(&root) {
+                snapshot.images += 1;
+                continue;
+            }
+            // キャッシュや書き出し先の画像は消されることがあるので、アーカイブの場所に置き直す。
+            match link_or_copy(Path::new(&image.save_path), &path) {
+                Ok(_) => {
+                    let path = fs::canonicalize(&path).unwrap_or(path);
+                    ImageStateItem {
+                        save_path: path.display().to_string(),
+                        ..image
+                    }
+                    .save(db)
+                    .await?;
+                    snapshot.images += 1;
+                    continue;
+                }
+                Err(e) => warn!("Failed to archive image {}: {}", url, e),
+            }
+        }
+        match ImageStateItem::download(db, url, &path).await {
+            Ok(_) => snapshot.images += 1,
+            Err(e) => {
+                warn!("Failed to download image {}: {}", url, e);
+                snapshot.failed += 1;
+            }
+        }
+    }
+    // 取得できなかった画像があれば保存待ちのままにして、次の機会に取得し直す。
+    ThreadStateItem::update_archived_at(db, thread.id, snapshot.failed == 0).await?;
+    Ok(snapshot)
+}
+
+/// スレの画像を置く場所 スレのキーは別の板と重なることがあるので、板ごとに分ける。
+fn archive_dir(root: &Path, url: &str) -> PathBuf {
+    let mut segments = url.trim_end_matches('/').rsplit('/');
+    let key = segments.next().unwrap_or_default();
+    let board = segments.next().unwrap_or_default();
+    root.join(board).join(key)
+}
+
+/// 同じファイルシステムならハードリンクを張り、できなければ複製する。
+fn link_or_copy(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if to.exists() {
+        fs::remove_file(to)?;
+    }
+    if fs::hard_link(from, to).is_err() {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::connect::temp_db;
+
+    #[test]
+    fn test_link_or_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let cached = dir.path().join("cache").join("a.png");
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, b"png").unwrap();
+
+        let archived = dir.path().join("archive").join("1").join("0_a.png");
+        link_or_copy(&cached, &archived).unwrap();
+        // キャッシュを消しても、アーカイブには残る。
+        fs::remove_file(&cached).unwrap();
+        assert_eq!(fs::read(&archived).unwrap(), b"png");
+    }
+
+    #[test]
+    fn test_archive_dir() {
+        let root = Path::new("/archive");
+        assert_eq!(
+            archive_dir(root, "https://egg.5ch.net/test/read.cgi/news/1234567890/"),
+            root.join("news").join("1234567890")
+        );
+        assert_ne!(
+            archive_dir(root, "https://egg.5ch.net/test/read.cgi/news/1234567890"),
+            archive_dir(root, "https://mi.5ch.net/test/read.cgi/news4vip/1234567890")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_pending_without_archives() {
+        let (_dir, db) = temp_db().await;
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+        tokio::spawn(snapshot_pending(db, 3, tx));
+        match rx.recv().await {
+            Some(Command::Snapshot(SnapshotProgress::Done(0))) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -1,8 +1,19 @@
 use std::error::Error;
 
-use termchan_tui::run::run;
+use termchan_tui::{
+    cli,
+    run::{prepare, run},
+    subcommand,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    run().await
+    let args = cli::parse();
+    match args.subcommand.clone() {
+        Some(command) => {
+            prepare(&args)?;
+            subcommand::run(&args, command).await
+        }
+        None => run(args).await,
+    }
 }
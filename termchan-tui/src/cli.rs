@@ -0,0 +1,315 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use log::LevelFilter;
+use termchan_core::export::ExportFormat;
+
+use crate::database::connect::DB_PATH_ENV;
+
+/// コマンドライン引数
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    /// 起動してすぐに開く板かスレのURL
+    pub url:         Option<String>,
+    /// 設定ファイルのパス
+    pub config_path: Option<PathBuf>,
+    /// データベースファイルのパス
+    pub db_path:     Option<PathBuf>,
+    /// ログの出力レベル 指定が無ければデバッグビルドだけInfoで出力する
+    pub log_level:   Option<LevelFilter>,
+    /// 取得せず、保存済みのデータだけで起動する
+    pub offline:     bool,
+    /// 指定されたら画面を開かずに実行して終了する
+    pub subcommand:  Option<Subcommand>,
+    /// サブコマンドの出力の形式
+    pub format:      Format,
+}
+
+/// 画面を開かずに実行するコマンド
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Subcommand {
+    /// 板一覧
+    Menu { url: String },
+    /// スレ一覧
+    Board { url: String },
+    /// スレのレス `since`より後のレス番号だけを出力する
+    Thread { url: String, since: usize },
+    /// `-`なら標準入力から本文を読む
+    Post {
+        url:          String,
+        message_file: PathBuf,
+        name:         Option<String>,
+        mail:         Option<String>,
+    },
+    /// 保存したスレを書き出す。`output`が無ければ標準出力に書く
+    Export {
+        url:    String,
+        format: ExportFormat,
+        output: Option<PathBuf>,
+    },
+    /// 他のブラウザのログのフォルダから`.dat`を取り込む
+    Import { dir: PathBuf, host: Option<String> },
+    /// 古いスレを消してデータベースを詰め、板ごとの容量を出力する
+    /// `days`が無ければ設定の`retention_days`を使う。`report`なら何も消さない
+    Maintenance {
+        days:   Option<u32>,
+        vacuum: bool,
+        report: bool,
+    },
+}
+
+/// サブコマンドの出力の形式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+    Tsv,
+}
+
+fn url_arg<'a>(help: &'a str) -> Arg<'a> {
+    Arg::new("url").value_name("URL").required(true).help(help)
+}
+
+fn format_arg<'a>() -> Arg<'a> {
+    Arg::new("format")
+        .long("format")
+        .value_name("FORMAT")
+        .value_parser(["text", "json", "tsv"])
+        .default_value("text")
+        .help("出力の形式")
+}
+
+pub fn parse() -> Args {
+    let matches = Command::new("termchan")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("2ch互換掲示板を閲覧できるCLIクライアント")
+        .arg(
+            Arg::new("url")
+                .value_name("URL")
+                .help("起動してすぐに開く板かスレのURL"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .global(true)
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help("設定ファイルのパス"),
+        )
+        .arg(
+            Arg::new("db")
+                .long("db")
+                .global(true)
+                .value_name("PATH")
+                .value_parser(value_parser!(PathBuf))
+                .help(&*format!(
+                    "データベースファイルのパス (環境変数 {} でも指定可)",
+                    DB_PATH_ENV
+                )),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .global(true)
+                .value_name("LEVEL")
+                .value_parser(["off", "error", "warn", "info", "debug", "trace"])
+                .help("ログの出力レベル データディレクトリのtermchan.logに出力する"),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .help("取得せず、保存済みのデータだけで起動する"),
+        )
+        .subcommand(
+            Command::new("menu")
+                .about("板一覧を出力する")
+                .arg(url_arg("bbsmenuのURL"))
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("board")
+                .about("スレ一覧を出力する")
+                .arg(url_arg("板のURL"))
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("thread")
+                .about("スレのレスを出力する")
+                .arg(url_arg("スレのURL"))
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .default_value("0")
+                        .help("このレス番号より後のレスだけを出力する"),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("post")
+                .about("スレに書き込む")
+                .arg(url_arg("スレのURL"))
+                .arg(
+                    Arg::new("message-file")
+                        .long("message-file")
+                        .value_name("PATH")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .help("本文のファイル -なら標準入力から読む"),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .value_name("NAME")
+                        .help("名前"),
+                )
+                .arg(
+                    Arg::new("mail")
+                        .long("mail")
+                        .value_name("MAIL")
+                        .help("メール欄"),
+                )
+                .arg(format_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("保存したスレを書き出す")
+                .arg(url_arg("スレのURL"))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .value_parser(["dat", "html", "md", "txt", "json"])
+                        .default_value("html")
+                        .help("書き出す形式"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("PATH")
+                        .value_parser(value_parser!(PathBuf))
+                        .help("書き出すファイル 無ければ標準出力に書く"),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("他のブラウザのログのフォルダからDATを取り込む")
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .value_parser(value_parser!(PathBuf))
+                        .required(true)
+                        .help("`.dat`と`.idx`のあるフォルダ 下のフォルダも探す"),
+                )
+                .arg(Arg::new("host").long("host").value_name("HOST").help(
+                    "登録されていない板や、同じ名前の板が複数あるときのサーバー 例: egg.5ch.net",
+                )),
+        )
+        .subcommand(
+            Command::new("maintenance")
+                .about("古いスレを消してデータベースを詰め、板ごとの容量を出力する")
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .value_name("N")
+                        .value_parser(value_parser!(u32))
+                        .help(
+                            "スレ一覧から消えた未読のスレを、立ってから何日で消すか 0なら消さない",
+                        ),
+                )
+                .arg(
+                    Arg::new("no-vacuum")
+                        .long("no-vacuum")
+                        .action(ArgAction::SetTrue)
+                        .help("空いた領域を詰めない"),
+                )
+                .arg(
+                    Arg::new("report")
+                        .long("report")
+                        .action(ArgAction::SetTrue)
+                        .help("何も消さずに容量だけを出力する"),
+                )
+                .arg(format_arg()),
+        )
+        .get_matches();
+
+    let url = |m: &ArgMatches| m.get_one::<String>("url").cloned().unwrap_or_default();
+    let (subcommand, format) = match matches.subcommand() {
+        Some((name, m)) => {
+            let subcommand = match name {
+                "menu" => Subcommand::Menu { url: url(m) },
+                "board" => Subcommand::Board { url: url(m) },
+                "thread" => {
+                    Subcommand::Thread {
+                        url:   url(m),
+                        since: m.get_one::<usize>("since").copied().unwrap_or_default(),
+                    }
+                }
+                "export" => {
+                    Subcommand::Export {
+                        url:    url(m),
+                        format: m
+                            .get_one::<String>("format")
+                            .and_then(|f| ExportFormat::parse(f))
+                            .unwrap_or(ExportFormat::Html),
+                        output: m.get_one::<PathBuf>("output").cloned(),
+                    }
+                }
+                "maintenance" => {
+                    let flag = |name| m.get_one::<bool>(name).copied().unwrap_or(false);
+                    Subcommand::Maintenance {
+                        days:   m.get_one::<u32>("days").copied(),
+                        vacuum: !flag("no-vacuum"),
+                        report: flag("report"),
+                    }
+                }
+                "import" => {
+                    Subcommand::Import {
+                        dir:  m.get_one::<PathBuf>("dir").cloned().unwrap_or_default(),
+                        host: m.get_one::<String>("host").cloned(),
+                    }
+                }
+                _ => {
+                    Subcommand::Post {
+                        url:          url(m),
+                        message_file: m
+                            .get_one::<PathBuf>("message-file")
+                            .cloned()
+                            .unwrap_or_default(),
+                        name:         m.get_one::<String>("name").cloned(),
+                        mail:         m.get_one::<String>("mail").cloned(),
+                    }
+                }
+            };
+            // `import`には出力の形式が無い。
+            let format = match m
+                .try_get_one::<String>("format")
+                .ok()
+                .flatten()
+                .map(String::as_str)
+            {
+                Some("json") => Format::Json,
+                Some("tsv") => Format::Tsv,
+                _ => Format::Text,
+            };
+            (Some(subcommand), format)
+        }
+        None => (None, Format::Text),
+    };
+
+    Args {
+        url: matches.get_one::<String>("url").cloned(),
+        config_path: matches.get_one::<PathBuf>("config").cloned(),
+        db_path: matches.get_one::<PathBuf>("db").cloned(),
+        log_level: matches
+            .get_one::<String>("log-level")
+            .and_then(|level| level.parse().ok()),
+        offline: matches.get_one::<bool>("offline").copied().unwrap_or(false),
+        subcommand,
+        format,
+    }
+}
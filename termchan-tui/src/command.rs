@@ -0,0 +1,301 @@
+use eyre::{bail, Result};
+use termchan_core::export::ExportFormat;
+
+use crate::config::ng::NgKind;
+
+/// `:`で入力するコマンド
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandLine {
+    /// `:123` スレではレス番号、それ以外の一覧では位置へ移動する
+    Jump(usize),
+    /// 板かスレのURLを開く
+    Open(String),
+    /// 開いている板をブックマークに追加する。falseなら削除する
+    Bookmark(bool),
+    /// 値が無ければ選択しているレスのIDか名前を使う
+    Ng(NgKind, Option<String>),
+    Unng(NgKind, String),
+    /// `.`で区切った設定の項目と値
+    Set(String, String),
+    /// 開いているスレを書き出す。場所が無ければデータディレクトリのexportに置く
+    Export(ExportFormat, Option<String>),
+    /// 取得せず保存済みのデータだけを表示する。値が無ければ切り替える
+    Offline(Option<bool>),
+    /// 開いているスレか板のアーカイブを切り替える
+    Archive {
+        board:    bool,
+        archived: bool,
+    },
+    /// アーカイブしていないスレのレスを消す。日数が無ければ設定の`prune_days`を使う
+    Prune(Option<u32>),
+    Reload,
+    Help,
+    Quit,
+}
+
+/// コマンドの名前と説明 補完と`:help`に使う
+pub const COMMANDS: &[(&str, &str)] = &[
+    ("open", "open <URL> 板かスレを開く"),
+    ("bookmark", "bookmark add|remove 開いている板のブックマーク"),
+    (
+        "ng",
+        "ng id|name|word [値] あぼーんに追加 値が無ければ選択しているレス",
+    ),
+    ("unng", "unng id|name|word <値> あぼーんから削除"),
+    ("set", "set <項目>=<値> 設定を変更して保存する"),
+    (
+        "export",
+        "export [dat|html|md|txt|json] [パス] 開いているスレを書き出す",
+    ),
+    (
+        "offline",
+        "offline [on|off] 取得せず保存済みのデータを表示する",
+    ),
+    (
+        "archive",
+        "archive [board] [off] 開いているスレか板のレスと画像を全て保存する",
+    ),
+    (
+        "prune",
+        "prune [日数] 古いスレのレスを消す アーカイブしたスレは残す",
+    ),
+    ("reload", "reload 再読み込み"),
+    ("help", "help キーの一覧"),
+    ("quit", "quit 終了"),
+];
+
+/// 入力した一行をコマンドにする。
+pub fn parse(line: &str) -> Result<CommandLine> {
+    let line = line.trim();
+    if let Ok(number) = line.parse::<usize>() {
+        return Ok(CommandLine::Jump(number));
+    }
+    let (name, args) = match line.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+    let command = match name {
+        "open" | "o" => {
+            if args.is_empty() {
+                bail!("open <URL> の形で入力してください。");
+            }
+            CommandLine::Open(args.to_string())
+        }
+        "bookmark" | "b" => {
+            match args {
+                "" | "add" => CommandLine::Bookmark(true),
+                "remove" | "rm" => CommandLine::Bookmark(false),
+                _ => bail!("bookmark add か bookmark remove を入力してください。"),
+            }
+        }
+        "ng" | "unng" => {
+            let (kind, value) = match args.split_once(char::is_whitespace) {
+                Some((kind, value)) => (kind, value.trim()),
+                None => (args, ""),
+            };
+            let kind = match NgKind::parse(kind) {
+                Some(kind) => kind,
+                None => bail!("{} id|name|word の形で入力してください。", name),
+            };
+            match (name, value) {
+                ("ng", "") => CommandLine::Ng(kind, None),
+                ("ng", value) => CommandLine::Ng(kind, Some(value.to_string())),
+                (_, "") => bail!("削除する値を入力してください。"),
+                (_, value) => CommandLine::Unng(kind, value.to_string()),
+            }
+        }
+        "set" => {
+            match args.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    CommandLine::Set(key.trim().to_string(), value.trim().to_string())
+                }
+                _ => bail!("set theme.text=Gray の形で入力してください。"),
+            }
+        }
+        "export" => {
+            let (format, path) = match args.split_once(char::is_whitespace) {
+                Some((format, path)) => (format, Some(path.trim().to_string())),
+                None if args.is_empty() => ("html", None),
+                None => (args, None),
+            };
+            match ExportFormat::parse(format) {
+                Some(format) => CommandLine::Export(format, path),
+                None => bail!("export dat|html|md|txt|json の形で入力してください。"),
+            }
+        }
+        "offline" => {
+            match args {
+                "" => CommandLine::Offline(None),
+                "on" => CommandLine::Offline(Some(true)),
+                "off" => CommandLine::Offline(Some(false)),
+                _ => bail!("offline on か offline off を入力してください。"),
+            }
+        }
+        "archive" => {
+            let mut board = false;
+            let mut archived = true;
+            for arg in args.split_whitespace() {
+                match arg {
+                    "board" => board = true,
+                    "thread" => board = false,
+                    "on" => archived = true,
+                    "off" => archived = false,
+                    _ => bail!("archive [thread|board] [on|off] の形で入力してください。"),
+                }
+            }
+            CommandLine::Archive { board, archived }
+        }
+        "prune" => {
+            match args {
+                "" => CommandLine::Prune(None),
+                days => {
+                    match days.parse::<u32>() {
+                        Ok(days) if days > 0 => CommandLine::Prune(Some(days)),
+                        _ => bail!("日数を1以上の数で入力してください。"),
+                    }
+                }
+            }
+        }
+        "reload" | "r" => CommandLine::Reload,
+        "help" | "h" => CommandLine::Help,
+        "quit" | "q" => CommandLine::Quit,
+        "" => bail!("コマンドを入力してください。"),
+        _ => bail!("不明なコマンドです。: {}", line),
+    };
+    Ok(command)
+}
+
+/// 入力中の一行を補完した候補 `set`の項目は`keys`から選ぶ
+pub fn complete(line: &str, keys: &[String]) -> Vec<String> {
+    let candidates = |prefix: &str, words: &mut dyn Iterator<Item = String>| {
+        words
+            .filter(|word| word.starts_with(prefix))
+            .collect::<Vec<_>>()
+    };
+    match line.split_once(' ') {
+        None => {
+            candidates(line, &mut COMMANDS.iter().map(|(name, _)| name.to_string()))
+                .into_iter()
+                .map(|name| format!("{} ", name))
+                .collect()
+        }
+        Some((name, arg)) if !arg.contains(' ') => {
+            let words: Vec<String> = match name {
+                "bookmark" => vec!["add".to_string(), "remove".to_string()],
+                "offline" => vec!["on".to_string(), "off".to_string()],
+                "archive" => {
+                    ["thread ", "board ", "off"]
+                        .iter()
+                        .map(|w| w.to_string())
+                        .collect()
+                }
+                "ng" | "unng" => NgKind::ALL.iter().map(|k| format!("{} ", k)).collect(),
+                "export" => {
+                    ExportFormat::ALL
+                        .iter()
+                        .map(|f| format!("{} ", f))
+                        .collect()
+                }
+                "set" if !arg.contains('=') => keys.iter().map(|k| format!("{}=", k)).collect(),
+                _ => vec![],
+            };
+            candidates(arg, &mut words.into_iter())
+                .into_iter()
+                .map(|word| format!("{} {}", name, word))
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// 候補に共通する先頭の部分
+pub fn common_prefix(candidates: &[String]) -> String {
+    let first = match candidates.first() {
+        Some(first) => first,
+        None => return String::new(),
+    };
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("123").unwrap(), CommandLine::Jump(123));
+        assert_eq!(
+            parse("open https://example.5ch.net/test/read.cgi/news/1/").unwrap(),
+            CommandLine::Open("https://example.5ch.net/test/read.cgi/news/1/".to_string())
+        );
+        assert_eq!(parse("bookmark add").unwrap(), CommandLine::Bookmark(true));
+        assert_eq!(
+            parse("ng id ABCD").unwrap(),
+            CommandLine::Ng(NgKind::Id, Some("ABCD".to_string()))
+        );
+        assert_eq!(
+            parse("ng name").unwrap(),
+            CommandLine::Ng(NgKind::Name, None)
+        );
+        assert_eq!(
+            parse("set theme.text = Gray").unwrap(),
+            CommandLine::Set("theme.text".to_string(), "Gray".to_string())
+        );
+        assert_eq!(
+            parse("export").unwrap(),
+            CommandLine::Export(ExportFormat::Html, None)
+        );
+        assert_eq!(
+            parse("export dat /tmp/a.dat").unwrap(),
+            CommandLine::Export(ExportFormat::Dat, Some("/tmp/a.dat".to_string()))
+        );
+        assert!(parse("export pdf").is_err());
+        assert_eq!(parse("offline").unwrap(), CommandLine::Offline(None));
+        assert_eq!(
+            parse("offline off").unwrap(),
+            CommandLine::Offline(Some(false))
+        );
+        assert_eq!(
+            parse("archive board off").unwrap(),
+            CommandLine::Archive {
+                board:    true,
+                archived: false,
+            }
+        );
+        assert_eq!(parse("prune 30").unwrap(), CommandLine::Prune(Some(30)));
+        assert!(parse("prune 0").is_err());
+        assert!(parse("unng word").is_err());
+        assert!(parse("ng foo").is_err());
+        assert!(parse("nothing").is_err());
+        assert_eq!(
+            parse("open").unwrap_err().to_string(),
+            "open <URL> の形で入力してください。"
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let keys = vec!["theme.text".to_string(), "theme.hint".to_string()];
+        assert_eq!(complete("bo", &keys), vec!["bookmark "]);
+        assert_eq!(complete("ng w", &keys), vec!["ng word "]);
+        let candidates = complete("set theme.", &keys);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(common_prefix(&candidates), "set theme.");
+        assert_eq!(
+            common_prefix(&complete("set theme.t", &keys)),
+            "set theme.text="
+        );
+        assert!(complete("open http", &keys).is_empty());
+    }
+}
@@ -1,24 +1,82 @@
 use std::{
-    fs::OpenOptions,
-    io::{BufReader, BufWriter},
+    fs::{self, OpenOptions},
+    io::{self, BufReader},
     path::{Path, PathBuf},
 };
 
-use eyre::Result;
+use eyre::{bail, eyre, Result};
 use serde::{Deserialize, Serialize};
+use toml::Value;
+use toml_edit::{Document, Item};
 
-use super::{dirs::Dir, theme::Theme};
+use super::{
+    dirs::Dir,
+    keymap::{Keymap, KeymapConfig},
+    ng::NgConfig,
+    secret_store::CredentialBackend,
+    theme::Theme,
+};
 
-// #[cfg(target_os = "linux")]
-// static DEFAULT_CONFIG_PATH: &str = "/etc/termchan.toml";
+/// 全ユーザーに共通の設定 ユーザーの設定ファイルで上書きする
+#[cfg(target_os = "linux")]
+fn system_config_path() -> Option<PathBuf> {
+    Some(PathBuf::from("/etc/termchan.toml"))
+}
 
-// #[cfg(target_os = "macos")]
-// static DEFAULT_CONFIG_PATH: &str = "/usr/local/etc/termchan.toml";
+#[cfg(target_os = "macos")]
+fn system_config_path() -> Option<PathBuf> {
+    Some(PathBuf::from("/usr/local/etc/termchan.toml"))
+}
+
+/// Windowsでは一般のユーザーもフォルダを作れる場所しか無いので、共通の設定は読まない。
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn system_config_path() -> Option<PathBuf> {
+    None
+}
 
-// #[cfg(target_os = "windows")]
-// static DEFAULT_CONFIG_PATH: &str = "C:\\Windows\\Temp\\termchan.toml";
+/// 既定の設定ファイルに書き出す項目の説明
+const FIELD_DOCS: &[(&str, &str)] = &[
+    ("bbsmenu_url_list", "起動時にメニューとして登録するbbsmenuのURL"),
+    (
+        "thumbnail_size",
+        "サムネイルのサイズ(wip)\nSmall: 16x10 | Medium: 32x20 | Large: 48x30",
+    ),
+    ("thumbnail_cache_size", "サムネイルのキャッシュサイズ 100M以上推奨"),
+    ("login", "アカウントでログインして書き込む"),
+    ("show_index", "スレ一覧に番号を表示する"),
+    ("request_header", "リクエストに付けるヘッダー 一行に一つの`名前: 値`"),
+    (
+        "db_path",
+        "データベースファイルのパス 未指定ならデータディレクトリ直下のtermchan.db\ndb_path = \"/path/to/termchan.db\"",
+    ),
+    (
+        "credential_backend",
+        "パスワードなどの保存先\nauto | keyring | file | pass",
+    ),
+    (
+        "keymap",
+        "キーの割り当て preset: vim | emacs\n[keymap.normal]、[keymap.popup]、[keymap.input]に\"C-q\" = \"quit\"のように書くとプリセットを上書きする\n\"g g\"は続けて押すキー、\"none\"は割り当てを外す 操作の一覧は?キーで表示される",
+    ),
+    (
+        "ng",
+        "本文を表示しないレスの条件 ids: ID、names: 名前に含む文字列、words: 本文に含む文字列\nスレで:ng id ABCDのように追加できる",
+    ),
+    (
+        "prune_days",
+        "アーカイブしていないスレのレスを、取得してから何日で消すか 0なら消さない\nスレの記録と既読の位置は残り、開けばまた取得する 読んだスレも対象になる",
+    ),
+    (
+        "retention_days",
+        "スレ一覧から消えた未読のスレを、立ってから何日でスレの記録ごと消すか 0なら消さない\n読んだスレ、アーカイブしたスレ、書き込んだスレは残す",
+    ),
+    (
+        "theme",
+        "色は\"LightCyan\"のような名前、\"#ff8000\"、0から255の番号\"208\"のどれか\nborder_type: Plain | Rounded | Double | Thick",
+    ),
+];
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct Config {
     pub bbsmenu_url_list: Vec<String>,
 
@@ -40,6 +98,42 @@ pub struct Config {
 
     pub theme:          Theme,
     pub request_header: String,
+
+    /// データベースファイルのパス
+    /// 未指定ならデータディレクトリ直下の`termchan.db`を使う。
+    #[serde(default)]
+    pub db_path: Option<PathBuf>,
+
+    /// パスワードなどの保存先
+    /// auto | keyring | file | pass
+    /// default: auto キーリングが使えなければ暗号化したファイル
+    #[serde(default)]
+    pub credential_backend: CredentialBackend,
+
+    /// キーの割り当て
+    #[serde(default)]
+    pub keymap: KeymapConfig,
+
+    /// あぼーんするレスの条件
+    #[serde(default)]
+    pub ng: NgConfig,
+
+    /// アーカイブしていないスレのレスを、取得してから何日で消すか
+    /// スレの記録と既読の位置は残し、開けばまた取得する。読んだスレも対象になる
+    /// 起動したときと`:prune`で消す。0なら消さない
+    #[serde(default)]
+    pub prune_days: u32,
+
+    /// スレ一覧から消えた未読のスレを、立ってから何日でスレの記録ごと消すか
+    /// 読んだスレ、アーカイブしたスレ、書き込んだスレは残す。0なら消さない
+    /// 起動したときと`termchan maintenance`で消す。`prune_days`とは別に働く
+    /// default: 30
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+}
+
+fn default_retention_days() -> u32 {
+    30
 }
 
 impl Default for Config {
@@ -63,38 +157,228 @@ upgrade-insecure-requests: 1
 user-agent: Mozilla/5.0 (Macintosh; Intel Mac OS X 10_10; rv:33.0) Gecko/20100101 Firefox/33.0
 "#
                 .to_string(),
+            db_path:              None,
+            credential_backend:   CredentialBackend::default(),
+            keymap:               KeymapConfig::default(),
+            ng:                   NgConfig::default(),
+            prune_days:           0,
+            retention_days:       default_retention_days(),
         }
     }
 }
 
 #[allow(dead_code)]
 impl Config {
+    /// システムとユーザーの設定ファイルを重ねて読み込む。
+    /// 読めない項目があれば、ファイルと行番号を付けたエラーを返す。
     pub fn load_config() -> Result<Config> {
         let config_path = Dir::get_config_path()?;
-        let config = Self::load_config_with_path(&config_path);
-        let config = config.unwrap_or_else(|_| Config::default());
-        Ok(config)
+        Self::load_config_with_path(&config_path)
     }
     fn load_config_with_path<P: AsRef<Path>>(config_path: P) -> Result<Config> {
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(config_path)?;
-        let reader = BufReader::new(file);
-        let config: Config = serde_json::from_reader(reader)?;
-        Ok(config)
+        let config_path = config_path.as_ref();
+        if !config_path.exists() {
+            Self::create_user_config(config_path)?;
+        }
+        let mut layers = vec![];
+        for path in Self::layers(config_path) {
+            match fs::read_to_string(&path) {
+                Ok(text) => layers.push((path, text)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(eyre!("{}: {}", path.display(), e)),
+            }
+        }
+        Self::from_layers(&layers)
+    }
+    /// 後ろのファイルほど優先する。
+    fn from_layers(layers: &[(PathBuf, String)]) -> Result<Config> {
+        let mut merged = Value::Table(Default::default());
+        for (path, text) in layers {
+            // 重ねた後では位置が分からないので、ファイルごとに検査する。
+            let layer =
+                toml::from_str::<Config>(text).map_err(|e| eyre!("{}: {}", path.display(), e))?;
+            Keymap::new(&layer.keymap).map_err(|e| eyre!("{}: {}", path.display(), e))?;
+            merge(&mut merged, toml::from_str(text)?);
+        }
+        Ok(merged.try_into()?)
     }
+    /// 読み込む設定ファイル 更新を監視するのにも使う
+    pub fn layers(config_path: &Path) -> Vec<PathBuf> {
+        system_config_path()
+            .into_iter()
+            .chain([config_path.to_path_buf()])
+            .collect()
+    }
+    /// ユーザーの設定ファイルを作る。
+    /// 以前のJSONの設定があれば移し、無ければ説明だけの設定ファイルにする。
+    fn create_user_config(config_path: &Path) -> Result<()> {
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let legacy_path = Dir::get_legacy_config_path()?;
+        if let Ok(file) = OpenOptions::new().read(true).open(&legacy_path) {
+            if let Ok(config) = serde_json::from_reader::<_, Config>(BufReader::new(file)) {
+                info!(
+                    "migrate {} to {}",
+                    legacy_path.display(),
+                    config_path.display()
+                );
+                return config.write_to(config_path);
+            }
+        }
+        fs::write(config_path, Self::default_file()?)?;
+        Ok(())
+    }
+    /// 全ての項目を説明付きでコメントアウトした設定ファイル
+    pub fn default_file() -> Result<String> {
+        let defaults = toml::to_string_pretty(&Value::try_from(Config::default())?)?;
+        let doc = |key: &str| {
+            FIELD_DOCS
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, doc)| {
+                    doc.lines()
+                        .map(|l| format!("## {}\n", l))
+                        .collect::<String>()
+                })
+                .unwrap_or_default()
+        };
+
+        let overrides = match system_config_path() {
+            Some(path) => format!("# {}の設定をこのファイルで上書きします。\n", path.display()),
+            None => String::new(),
+        };
+        let mut text = format!(
+            "# termchanの設定ファイル\n{}# 変更するときは、行頭の`# `を消してください。\n",
+            overrides
+        );
+        let mut documented = vec![];
+        for line in defaults.lines() {
+            let key = match line.strip_prefix('[') {
+                Some(table) => table.trim_end_matches(']'),
+                None => line.split(" = ").next().unwrap_or_default(),
+            };
+            if line.starts_with('[') && !documented.contains(&key) {
+                // 既定値の無い項目は、表より前に説明だけ書いておく。
+                let missing = FIELD_DOCS
+                    .iter()
+                    .map(|(k, _)| *k)
+                    .filter(|k| !defaults.contains(k) && !documented.contains(k))
+                    .collect::<Vec<_>>();
+                for k in missing {
+                    text.push_str(&format!("\n{}", doc(k)));
+                    documented.push(k);
+                }
+            }
+            if FIELD_DOCS.iter().any(|(k, _)| *k == key) && !documented.contains(&key) {
+                text.push_str(&format!("\n{}", doc(key)));
+                documented.push(key);
+            }
+            text.push_str(&format!("# {}\n", line));
+        }
+        Ok(text.replace("# \n", "#\n"))
+    }
+    /// 設定を保存する。
+    /// システムの設定と同じ項目は書き出さず、システムの設定の変更が反映されるようにする。
     pub fn write(&mut self) -> Result<()> {
         let config_path = Dir::get_config_path()?;
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(config_path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, self)?;
+        self.write_to(&config_path)
+    }
+    fn write_to(&self, config_path: &Path) -> Result<()> {
+        let mut base = Value::try_from(Config::default())?;
+        if let Some(text) = system_config_path().and_then(|path| fs::read_to_string(path).ok()) {
+            if let Ok(system) = toml::from_str(&text) {
+                merge(&mut base, system);
+            }
+        }
+        let text = match fs::read_to_string(config_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let text = self
+            .update_file(&text, &base)
+            .map_err(|e| eyre!("{}: {}", config_path.display(), e))?;
+        fs::write(config_path, text)?;
         Ok(())
     }
+    /// 設定ファイルの中身のうち、変わった項目だけを書き換える。
+    /// コメントや並び順はそのまま残す。
+    /// 項目が一つも無ければ、既定と違う項目だけをコメントの後ろに書き足す。
+    fn update_file(&self, text: &str, base: &Value) -> Result<String> {
+        let value = Value::try_from(self)?;
+        let mut doc = text.parse::<Document>()?;
+        if doc.as_table().is_empty() {
+            let value = diff(value, base).unwrap_or(Value::Table(Default::default()));
+            let header = match text.trim_end() {
+                "" => "# termchanの設定ファイル\n# 設定タブで変更すると書き換えられます。",
+                text => text,
+            };
+            return Ok(format!("{}\n\n{}", header, toml::to_string_pretty(&value)?));
+        }
+        let mut current = base.clone();
+        merge(&mut current, toml::from_str(text)?);
+        if let (Value::Table(value), Value::Table(current)) = (&value, &current) {
+            update_table(doc.as_table_mut(), value, current)?;
+        }
+        Ok(doc.to_string())
+    }
+    /// `theme.text`のように`.`で区切った項目を書き換えた設定を返す。
+    /// 値はTOMLとして読み、読めなければ文字列として扱う。
+    pub fn with_value(&self, key: &str, value: &str) -> Result<Config> {
+        let mut root = Value::try_from(self)?;
+        let path = key.split('.').collect::<Vec<_>>();
+        let (last, parents) = match path.split_last() {
+            Some((last, parents)) if !last.is_empty() => (*last, parents),
+            _ => bail!("項目の名前がありません。"),
+        };
+        let mut table = &mut root;
+        for name in parents {
+            table = match table.get_mut(*name) {
+                Some(t) if t.is_table() => t,
+                _ => bail!("不明な項目です。: {}", key),
+            };
+        }
+        let table = table
+            .as_table_mut()
+            .ok_or_else(|| eyre!("不明な項目です。: {}", key))?;
+        // キーの割り当てだけは新しい項目を追加できる。
+        if !table.contains_key(last) && parents.first() != Some(&"keymap") {
+            bail!("不明な項目です。: {}", key);
+        }
+        let value = toml::from_str::<toml::value::Table>(&format!("v = {}", value))
+            .ok()
+            .and_then(|mut t| t.remove("v"))
+            .unwrap_or_else(|| Value::String(value.to_string()));
+        table.insert(last.to_string(), value);
+
+        let config: Config = root.try_into().map_err(|e| eyre!("{}: {}", key, e))?;
+        Keymap::new(&config.keymap)?;
+        Ok(config)
+    }
+    /// `:set`で補完する項目の名前 値が表でない項目を`.`区切りで並べる。
+    pub fn keys() -> Vec<String> {
+        fn walk(prefix: &str, value: &Value, keys: &mut Vec<String>) {
+            match value.as_table() {
+                Some(table) => {
+                    for (key, value) in table {
+                        let key = if prefix.is_empty() {
+                            key.clone()
+                        } else {
+                            format!("{}.{}", prefix, key)
+                        };
+                        walk(&key, value, keys);
+                    }
+                }
+                None => keys.push(prefix.to_string()),
+            }
+        }
+        let mut keys = vec![];
+        if let Ok(value) = Value::try_from(Config::default()) {
+            walk("", &value, &mut keys);
+        }
+        keys
+    }
     pub fn pretty_json() -> Result<String> {
         let config = Self::load_config()?;
         let json = serde_json::to_string_pretty(&config)?;
@@ -105,6 +389,93 @@ impl Config {
         Ok(config_path)
     }
 }
+/// 表同士は項目ごとに重ね、それ以外は上書きする。
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Table(base), Value::Table(table)) => {
+            for (key, value) in table {
+                match base.get_mut(&key) {
+                    Some(v) => merge(v, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// 今の値と違う項目だけを`table`に書き込む。
+/// 値を書き換えるときは、その行に付いたコメントを残す。
+fn update_table(
+    table: &mut toml_edit::Table,
+    value: &toml::value::Table,
+    current: &toml::value::Table,
+) -> Result<()> {
+    for (key, value) in value {
+        let current = current.get(key);
+        if current == Some(value) {
+            continue;
+        }
+        match value {
+            Value::Table(value) if table.get(key).is_none_or(Item::is_table) => {
+                let item = table.entry(key).or_insert_with(|| {
+                    let mut table = toml_edit::Table::new();
+                    table.set_implicit(true);
+                    Item::Table(table)
+                });
+                let empty = toml::value::Table::new();
+                let current = current.and_then(Value::as_table).unwrap_or(&empty);
+                if let Some(table) = item.as_table_mut() {
+                    update_table(table, value, current)?;
+                }
+            }
+            value => {
+                let new = to_item(value)?;
+                match (table.get_mut(key).and_then(Item::as_value_mut), new) {
+                    (Some(old), Item::Value(mut new)) => {
+                        *new.decor_mut() = old.decor().clone();
+                        *old = new;
+                    }
+                    (_, new) => {
+                        table.insert(key, new);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 値をtoml_editの項目にする。表の配列などもそのまま書けるよう、一度TOMLの文字列にする。
+fn to_item(value: &Value) -> Result<Item> {
+    let mut table = toml::value::Table::new();
+    table.insert("v".to_string(), value.clone());
+    let mut doc = toml::to_string(&table)?.parse::<Document>()?;
+    Ok(doc.remove("v").unwrap_or_default())
+}
+
+/// `base`と違う項目だけを残す。同じならNone
+fn diff(value: Value, base: &Value) -> Option<Value> {
+    match (value, base) {
+        (Value::Table(table), Value::Table(base)) => {
+            let table = table
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    match base.get(&key) {
+                        Some(b) => diff(value, b).map(|v| (key, v)),
+                        None => Some((key, value)),
+                    }
+                })
+                .collect::<toml::value::Table>();
+            Some(Value::Table(table)).filter(|t| t.as_table().is_some_and(|t| !t.is_empty()))
+        }
+        (value, base) if value == *base => None,
+        (value, _) => Some(value),
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ThumbnailSize {
     Small,
@@ -112,6 +483,17 @@ pub enum ThumbnailSize {
     Large,
 }
 
+impl ThumbnailSize {
+    /// 設定タブで順に切り替える
+    pub fn next(&self) -> ThumbnailSize {
+        match self {
+            ThumbnailSize::Small => ThumbnailSize::Medium,
+            ThumbnailSize::Medium => ThumbnailSize::Large,
+            ThumbnailSize::Large => ThumbnailSize::Small,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -126,4 +508,104 @@ mod test {
         println!("{}", Config::pretty_json().unwrap());
         println!("{:?}", Config::path().unwrap());
     }
+
+    #[test]
+    fn test_from_layers() {
+        let layers = vec![
+            (
+                PathBuf::from("system.toml"),
+                "show_index = true\nlogin = true\n[theme]\ntext = \"Gray\"\n".to_string(),
+            ),
+            (
+                PathBuf::from("user.toml"),
+                "login = false\n[theme]\nhint = \"#ff8000\"\n".to_string(),
+            ),
+        ];
+        let config = Config::from_layers(&layers).unwrap();
+        assert!(config.show_index);
+        assert!(!config.login);
+        assert_eq!(config.theme.text, tui::style::Color::Gray);
+        assert_eq!(config.theme.hint, tui::style::Color::Rgb(255, 128, 0));
+
+        let layers = vec![(
+            PathBuf::from("user.toml"),
+            "\nshow_index = \"yes\"\n".to_string(),
+        )];
+        let e = Config::from_layers(&layers).unwrap_err().to_string();
+        assert!(e.starts_with("user.toml: "), "{}", e);
+        assert!(e.contains("line 2"), "{}", e);
+    }
+
+    #[test]
+    fn test_with_value() {
+        let config = Config::default();
+        let config = config.with_value("theme.text", "Gray").unwrap();
+        assert_eq!(config.theme.text, tui::style::Color::Gray);
+        let config = config.with_value("show_index", "true").unwrap();
+        assert!(config.show_index);
+        let config = config.with_value("keymap.normal.x", "\"quit\"").unwrap();
+        assert_eq!(config.keymap.normal.len(), 1);
+        assert!(config.with_value("show_index", "yes").is_err());
+        assert!(config.with_value("theme.nothing", "Gray").is_err());
+        assert!(Config::keys().contains(&"theme.text".to_string()));
+    }
+
+    #[test]
+    fn test_update_file() {
+        let base = Value::try_from(Config::default()).unwrap();
+        let text = "# 自分用の設定\n\n# ログインする\nlogin = true # 書き込み用\n\n[theme]\n# 文字の色\ntext = \"Gray\"\n";
+        let mut config =
+            Config::from_layers(&[(PathBuf::from("config.toml"), text.to_string())]).unwrap();
+        // 変えていなければそのまま
+        assert_eq!(config.update_file(text, &base).unwrap(), text);
+
+        config.login = false;
+        config.show_index = true;
+        config.theme.text = tui::style::Color::Yellow;
+        let updated = config.update_file(text, &base).unwrap();
+        assert!(updated.starts_with("# 自分用の設定\n"), "{}", updated);
+        assert!(
+            updated.contains("# ログインする\nlogin = false # 書き込み用\n"),
+            "{}",
+            updated
+        );
+        assert!(
+            updated.contains("# 文字の色\ntext = \"Yellow\"\n"),
+            "{}",
+            updated
+        );
+        let reloaded =
+            Config::from_layers(&[(PathBuf::from("config.toml"), updated.clone())]).unwrap();
+        assert!(reloaded.show_index);
+        assert!(!reloaded.login);
+        assert_eq!(reloaded.theme.text, tui::style::Color::Yellow);
+
+        // コメントだけのファイルには、既定と違う項目だけを書き足す。
+        let created = config.update_file("# コメント\n", &base).unwrap();
+        assert!(
+            created.starts_with("# コメント\n\nshow_index = true"),
+            "{}",
+            created
+        );
+        assert!(!created.contains("login"), "{}", created);
+    }
+
+    #[test]
+    fn test_default_file() {
+        let text = Config::default_file().unwrap();
+        assert!(text.contains("## アカウントでログインして書き込む\n# login = false\n"));
+        assert_eq!(text.matches("db_path").count(), 1);
+        let config = Config::from_layers(&[(PathBuf::from("config.toml"), text.clone())]).unwrap();
+        assert!(!config.show_index);
+
+        // コメントを外せばそのまま読める。
+        let uncommented = text
+            .lines()
+            .skip(3)
+            .filter(|l| !l.starts_with("##"))
+            .map(|l| l.trim_start_matches("# ").trim_start_matches('#'))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Config::from_layers(&[(PathBuf::from("config.toml"), uncommented)]).unwrap();
+    }
 }
@@ -1,49 +1,132 @@
 use std::sync::Arc;
 
 use eyre::Result;
-use keyring::{self, Entry};
 use reqwest::cookie::Jar;
-use termchan_core::post::login::do_login;
+use termchan_core::post::login::{jar_from_cookies, login_with_jar, session_cookies};
 
+use super::secret_store;
+
+/// 複数アカウントに対応する前の、一つだけのアカウントの保存先
 pub const PASS_USER: &str = "termchan_pass";
 pub const EM_USER: &str = "termchan_email";
 pub const KEYRING_SERVICE_NAME: &str = "termchan";
+/// トリップキーは`termchan_trip_{名前のID}`で保存する。
+pub const TRIP_USER_PREFIX: &str = "termchan_trip_";
+/// アカウントごとの保存先は`{prefix}{アカウントのID}`
+pub const ACCOUNT_PASS_PREFIX: &str = "termchan_pass_";
+pub const ACCOUNT_EM_PREFIX: &str = "termchan_email_";
+pub const ACCOUNT_SESSION_PREFIX: &str = "termchan_session_";
+
+/// 保存先から値を取得する。無いか空ならNone
+fn get_secret(user: &str) -> Option<String> {
+    secret_store::get(user)
+}
+
+/// 保存先に値を保存する。空なら削除する。
+fn set_secret(user: &str, value: &str) -> Result<()> {
+    secret_store::set(user, value)
+}
+
+fn delete_secret(user: &str) -> Result<()> {
+    secret_store::delete(user)
+}
 
 pub struct Account {
     em: Option<String>,
     pw: Option<String>,
+    /// セッションのCookieを保存するアカウントのID
+    id: Option<i32>,
 }
 #[allow(dead_code)]
 impl Account {
+    /// 複数アカウントに対応する前のアカウントを読み込む。
     pub fn new() -> Account {
-        let entry = Entry::new(&KEYRING_SERVICE_NAME, &PASS_USER);
-        let pw = match entry.get_password() {
-            Ok(pw) => Some(pw),
-            Err(_) => None,
-        };
-
-        let entry = Entry::new(&KEYRING_SERVICE_NAME, &EM_USER);
-        let em = match entry.get_password() {
-            Ok(p) => Some(p),
-            Err(_) => None,
-        };
-        Account { em, pw }
+        Account {
+            em: get_secret(EM_USER),
+            pw: get_secret(PASS_USER),
+            id: None,
+        }
+    }
+    /// IDのアカウントを読み込む。
+    pub fn load(id: i32) -> Account {
+        Account {
+            em: get_secret(&format!("{}{}", ACCOUNT_EM_PREFIX, id)),
+            pw: get_secret(&format!("{}{}", ACCOUNT_PASS_PREFIX, id)),
+            id: Some(id),
+        }
+    }
+    pub fn email(&self) -> Option<&str> {
+        self.em.as_deref()
+    }
+    pub fn has_credentials(&self) -> bool {
+        self.em.is_some() && self.pw.is_some()
+    }
+    /// IDのアカウントとして保存する。パスワードがNoneなら変更しない。
+    pub fn save(id: i32, em: &str, pw: Option<&str>) -> Result<()> {
+        set_secret(&format!("{}{}", ACCOUNT_EM_PREFIX, id), em)?;
+        if let Some(pw) = pw {
+            set_secret(&format!("{}{}", ACCOUNT_PASS_PREFIX, id), pw)?;
+        }
+        // 別のアカウントのセッションを使わないように消しておく。
+        delete_secret(&format!("{}{}", ACCOUNT_SESSION_PREFIX, id))
+    }
+    /// 複数アカウントに対応する前のアカウントを、IDのアカウントに移す。
+    pub fn move_to(&self, id: i32) -> Result<()> {
+        Self::save(
+            id,
+            self.em.as_deref().unwrap_or_default(),
+            self.pw.as_deref(),
+        )?;
+        Self::delete_legacy()
+    }
+    pub fn delete(id: i32) -> Result<()> {
+        delete_secret(&format!("{}{}", ACCOUNT_EM_PREFIX, id))?;
+        delete_secret(&format!("{}{}", ACCOUNT_PASS_PREFIX, id))?;
+        delete_secret(&format!("{}{}", ACCOUNT_SESSION_PREFIX, id))
+    }
+    /// 複数アカウントに対応する前のアカウントを消す。
+    pub fn delete_legacy() -> Result<()> {
+        delete_secret(EM_USER)?;
+        delete_secret(PASS_USER)
     }
     pub fn set_passwd(&self, pw: &str) -> Result<()> {
-        let entry = Entry::new(&KEYRING_SERVICE_NAME, &PASS_USER);
-        entry.set_password(&pw)?;
-        Ok(())
+        set_secret(PASS_USER, pw)
     }
     pub fn set_em(&self, em: &str) -> Result<()> {
-        let entry = Entry::new(&KEYRING_SERVICE_NAME, &EM_USER);
-        entry.set_password(&em)?;
-        Ok(())
+        set_secret(EM_USER, em)
     }
+    /// ログインしたCookieを返す。
+    /// 前回のセッションが残っていれば、ログインし直さずにそれを使う。
     pub async fn get_jar(&self) -> Result<Arc<Jar>> {
-        if self.em.is_none() || self.pw.is_none() {
-            return Ok(Arc::new(Jar::default()));
+        let (em, pw) = match (&self.em, &self.pw) {
+            (Some(em), Some(pw)) => (em, pw),
+            _ => return Ok(Arc::new(Jar::default())),
+        };
+        let session_user = self
+            .id
+            .map(|id| format!("{}{}", ACCOUNT_SESSION_PREFIX, id));
+        let jar = match session_user.as_deref().and_then(get_secret) {
+            Some(cookies) => jar_from_cookies(&cookies),
+            None => Arc::new(Jar::default()),
+        };
+        let jar = login_with_jar(jar, em, pw).await?;
+        if let (Some(user), Some(cookies)) = (session_user, session_cookies(&jar)) {
+            set_secret(&user, &cookies)?;
         }
-        let jar = do_login(&self.em.as_ref().unwrap(), &self.pw.as_ref().unwrap()).await;
-        jar
+        Ok(jar)
     }
 }
+
+/// 名前に紐づくトリップキーを取得する。無ければNone
+pub fn get_trip_key(identity_id: i32) -> Option<String> {
+    get_secret(&format!("{}{}", TRIP_USER_PREFIX, identity_id))
+}
+
+/// 名前に紐づくトリップキーを保存する。空なら削除する。
+pub fn set_trip_key(identity_id: i32, key: &str) -> Result<()> {
+    set_secret(&format!("{}{}", TRIP_USER_PREFIX, identity_id), key)
+}
+
+pub fn delete_trip_key(identity_id: i32) -> Result<()> {
+    delete_secret(&format!("{}{}", TRIP_USER_PREFIX, identity_id))
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::OnceLock};
 
 use directories::ProjectDirs;
 use eyre::Result;
@@ -7,29 +7,48 @@ fn get_dirs() -> Option<ProjectDirs> {
     ProjectDirs::from("com", "haryoiro", "termchan")
 }
 
-static CONFIG_FILE_NAME: &str = "config.json";
+static CONFIG_FILE_NAME: &str = "config.toml";
+/// 以前のバージョンのJSONの設定ファイル
+static LEGACY_CONFIG_FILE_NAME: &str = "config.json";
 static CACHE_DIR_NAME: &str = "cache";
 static IMAGE_CACHE_DIR_NAME: &str = "image";
 static CACHE_FILE_NAME: &str = "cache.json";
 static LOG_FILE_NAME: &str = "termchan.log";
+static DB_FILE_NAME: &str = "termchan.db";
+static CREDENTIALS_FILE_NAME: &str = "credentials.enc";
+static EXPORT_DIR_NAME: &str = "export";
+static ARCHIVE_DIR_NAME: &str = "archive";
 
-#[cfg(target_os = "linux")]
-const DATABASE_URL: &str = "sqlite:///var/tmp/termchan.db?mode=rwc";
-
-#[cfg(target_os = "macos")]
-const DATABASE_URL: &str = "sqlite:///var/tmp/termchan.db?mode=rwc";
+/// 以前のバージョンでデータベースを置いていた場所
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const LEGACY_DB_PATH: &str = "/var/tmp/termchan.db";
 
 #[cfg(target_os = "windows")]
-const DATABASE_URL: &str = "sqlite:///C:\\Windows\\Temp\\termchan.db?mode=rwc";
+const LEGACY_DB_PATH: &str = "C:\\Windows\\Temp\\termchan.db";
+
+/// `--config`で指定した設定ファイル
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 pub struct Dir;
 #[allow(dead_code)]
 impl Dir {
+    /// 設定ファイルの場所を変える。読み込む前に一度だけ呼ぶ。
+    pub fn set_config_path(path: PathBuf) {
+        let _ = CONFIG_PATH.set(path);
+    }
     pub fn get_config_path() -> Result<PathBuf> {
+        if let Some(path) = CONFIG_PATH.get() {
+            return Ok(path.clone());
+        }
         let dirs = get_dirs().unwrap();
         let config_path = dirs.config_dir().join(CONFIG_FILE_NAME);
         Ok(config_path)
     }
+    pub fn get_legacy_config_path() -> Result<PathBuf> {
+        let dirs = get_dirs().unwrap();
+        let config_path = dirs.config_dir().join(LEGACY_CONFIG_FILE_NAME);
+        Ok(config_path)
+    }
     pub fn get_image_cache_path() -> Result<PathBuf> {
         let dirs = get_dirs().unwrap();
         let image_cache_path = dirs
@@ -43,8 +62,32 @@ impl Dir {
         let cache_path = dirs.cache_dir().join(CACHE_DIR_NAME).join(CACHE_FILE_NAME);
         Ok(cache_path)
     }
-    pub fn get_db_path() -> Result<String> {
-        Ok(DATABASE_URL.to_string())
+    pub fn get_db_path() -> Result<PathBuf> {
+        let dirs = get_dirs().unwrap();
+        let path = dirs.data_dir().join(DB_FILE_NAME);
+        Ok(path)
+    }
+    /// キーリングが使えないときに、認証情報を暗号化して保存するファイル
+    pub fn get_credentials_path() -> Result<PathBuf> {
+        let dirs = get_dirs().unwrap();
+        let path = dirs.config_dir().join(CREDENTIALS_FILE_NAME);
+        Ok(path)
+    }
+    pub fn get_legacy_db_path() -> PathBuf {
+        PathBuf::from(LEGACY_DB_PATH)
+    }
+    /// スレを書き出すときに、場所を指定しなければここに置く。
+    pub fn get_export_path() -> Result<PathBuf> {
+        let dirs = get_dirs().unwrap();
+        let path = dirs.data_dir().join(EXPORT_DIR_NAME);
+        Ok(path)
+    }
+    /// アーカイブしたスレの画像を置く場所
+    /// キャッシュとは別のデータディレクトリに置き、キャッシュを消しても残るようにする。
+    pub fn get_archive_path() -> Result<PathBuf> {
+        let dirs = get_dirs().unwrap();
+        let path = dirs.data_dir().join(ARCHIVE_DIR_NAME);
+        Ok(path)
     }
     pub fn get_log_path() -> Result<PathBuf> {
         let dirs = get_dirs().unwrap();
@@ -0,0 +1,507 @@
+use std::collections::BTreeMap;
+
+use derive_more::Display;
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tui_textarea::{Input, Key};
+
+/// キーの割り当ての種類
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    #[default]
+    Vim,
+    Emacs,
+}
+
+/// 入力の状態ごとのキーの割り当て
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[display(fmt = "通常")]
+    Normal,
+    #[display(fmt = "フォーム")]
+    Popup,
+    #[display(fmt = "入力")]
+    Input,
+}
+
+/// キーに割り当てる操作 `run`で`Event`に変換する
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// プリセットの割り当てを外す
+    None,
+    Quit,
+    Down,
+    Up,
+    Left,
+    Right,
+    Tab,
+    Reload,
+    ToggleFilter,
+    ToggleBookmark,
+    Enter,
+    OpenPostForm,
+    RetryPost,
+    Delete,
+    ToggleMark,
+    ToggleVisual,
+    QuoteReply,
+    QuoteReplyWithText,
+    RemoveHistory,
+    ClosePopup,
+    ScrollToTop,
+    ScrollToBottom,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Command,
+    Search,
+    NextHit,
+    PrevHit,
+    Help,
+    ToggleTextArea,
+    Post,
+    NextIdentity,
+    NextAccount,
+    OpenEditor,
+    EnableInputMode,
+    DisableInputMode,
+}
+
+impl Action {
+    /// キーの一覧に表示する説明
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::None => "なし",
+            Action::Quit => "終了",
+            Action::Down => "下へ移動",
+            Action::Up => "上へ移動",
+            Action::Left => "左へ移動",
+            Action::Right => "右へ移動",
+            Action::Tab => "次のタブ",
+            Action::Reload => "再読み込み",
+            Action::ToggleFilter => "並び替え",
+            Action::ToggleBookmark => "ブックマークの追加と削除",
+            Action::Enter => "選択",
+            Action::OpenPostForm => "書き込み、追加",
+            Action::RetryPost => "失敗した書き込みの再送",
+            Action::Delete => "削除",
+            Action::ToggleMark => "印を付ける、切り替え",
+            Action::ToggleVisual => "範囲選択",
+            Action::QuoteReply => "アンカーを付けて返信",
+            Action::QuoteReplyWithText => "本文を引用して返信",
+            Action::RemoveHistory => "前のタブに戻る",
+            Action::ClosePopup => "閉じる",
+            Action::ScrollToTop => "先頭へ移動",
+            Action::ScrollToBottom => "末尾へ移動",
+            Action::PageDown => "次のページ",
+            Action::PageUp => "前のページ",
+            Action::HalfPageDown => "半ページ下へ",
+            Action::HalfPageUp => "半ページ上へ",
+            Action::Command => "コマンド (数字だけならレス番号へ移動)",
+            Action::Search => "検索",
+            Action::NextHit => "次の検索結果",
+            Action::PrevHit => "前の検索結果",
+            Action::Help => "キーの一覧",
+            Action::ToggleTextArea => "次の入力欄",
+            Action::Post => "送信、保存",
+            Action::NextIdentity => "名前の切り替え",
+            Action::NextAccount => "アカウントの切り替え",
+            Action::OpenEditor => "エディタで編集",
+            Action::EnableInputMode => "入力モード",
+            Action::DisableInputMode => "通常モード",
+        }
+    }
+}
+
+/// 設定ファイルの`[keymap]`
+/// `"C-q" = "quit"`のように、キーと操作を対応させる。プリセットの割り当てを上書きする。
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct KeymapConfig {
+    pub preset: Preset,
+    pub normal: BTreeMap<String, Action>,
+    pub popup:  BTreeMap<String, Action>,
+    pub input:  BTreeMap<String, Action>,
+}
+
+/// 名前で書くキー
+const KEY_NAMES: &[&str] = &[
+    "Enter",
+    "Esc",
+    "Tab",
+    "Space",
+    "Backspace",
+    "Delete",
+    "Left",
+    "Right",
+    "Up",
+    "Down",
+    "Home",
+    "End",
+    "PageUp",
+    "PageDown",
+];
+
+/// 入力を`C-q`のような文字列にする。割り当てられないキーはNone
+pub fn chord_of(input: &Input) -> Option<String> {
+    let key = match input.key {
+        Key::Char(' ') => "Space".to_string(),
+        Key::Char('\t') | Key::Tab => "Tab".to_string(),
+        Key::Char('\n') | Key::Enter => "Enter".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::F(n) => format!("F{}", n),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Delete => "Delete".to_string(),
+        Key::Left => "Left".to_string(),
+        Key::Right => "Right".to_string(),
+        Key::Up => "Up".to_string(),
+        Key::Down => "Down".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Esc => "Esc".to_string(),
+        _ => return None,
+    };
+    let ctrl = if input.ctrl { "C-" } else { "" };
+    let alt = if input.alt { "A-" } else { "" };
+    Some(format!("{}{}{}", ctrl, alt, key))
+}
+
+/// 設定ファイルのキーを、続けて押すキーの列にする。
+/// `"g g"`や`"gg"`は`g`を二回、`"C-x C-c"`はCtrl+xの後にCtrl+c
+pub fn parse_keys(keys: &str) -> Result<Vec<String>> {
+    let mut chords = vec![];
+    for token in keys.split_whitespace() {
+        let mut rest = token;
+        let (mut ctrl, mut alt) = (false, false);
+        loop {
+            if let Some(r) = rest.strip_prefix("C-").filter(|r| !r.is_empty()) {
+                ctrl = true;
+                rest = r;
+            } else if let Some(r) = rest
+                .strip_prefix("A-")
+                .or_else(|| rest.strip_prefix("M-"))
+                .filter(|r| !r.is_empty())
+            {
+                alt = true;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let prefix = format!(
+            "{}{}",
+            if ctrl { "C-" } else { "" },
+            if alt { "A-" } else { "" }
+        );
+        let named = KEY_NAMES
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(rest))
+            .map(|name| name.to_string())
+            .or_else(|| {
+                rest.strip_prefix(['F', 'f'])
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .map(|n| format!("F{}", n))
+            });
+        match named {
+            Some(name) => chords.push(format!("{}{}", prefix, name)),
+            None if rest.chars().count() == 1 => chords.push(format!("{}{}", prefix, rest)),
+            // 修飾キーの無い`gg`は一文字ずつ押す。
+            None if prefix.is_empty() => chords.extend(rest.chars().map(String::from)),
+            None => bail!("unknown key `{}`", token),
+        }
+    }
+    if chords.is_empty() {
+        bail!("empty key");
+    }
+    Ok(chords)
+}
+
+/// 押したキーの列に対する結果
+pub enum KeyMatch {
+    Action(Action),
+    /// 続けて押すキーを待っている
+    Pending,
+    None,
+}
+
+/// 入力の状態ごとのキーの割り当て
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: BTreeMap<Mode, Vec<(Vec<String>, Action)>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&KeymapConfig::default()).unwrap()
+    }
+}
+
+impl Keymap {
+    /// プリセットに設定ファイルの割り当てを重ねる。キーが読めなければエラー
+    pub fn new(config: &KeymapConfig) -> Result<Keymap> {
+        let mut bindings = BTreeMap::new();
+        for (mode, name, overrides) in [
+            (Mode::Normal, "normal", &config.normal),
+            (Mode::Popup, "popup", &config.popup),
+            (Mode::Input, "input", &config.input),
+        ] {
+            let mut list = vec![];
+            let preset = preset(config.preset, mode)
+                .iter()
+                .map(|(keys, action)| (keys.to_string(), *action));
+            let overrides = overrides.iter().map(|(k, a)| (k.clone(), *a));
+            for (keys, action) in preset.chain(overrides) {
+                let keys = match parse_keys(&keys) {
+                    Ok(keys) => keys,
+                    Err(e) => bail!("keymap.{}: {}", name, e),
+                };
+                list.retain(|(k, _)| *k != keys);
+                if action != Action::None {
+                    list.push((keys, action));
+                }
+            }
+            bindings.insert(mode, list);
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// 押したキーを`pending`に足して割り当てを探す。
+    /// どの割り当てにも続かなければ、最後のキーだけでもう一度探す。
+    pub fn feed(&self, mode: Mode, pending: &mut Vec<String>, input: &Input) -> KeyMatch {
+        let chord = match chord_of(input) {
+            Some(chord) => chord,
+            None => {
+                pending.clear();
+                return KeyMatch::None;
+            }
+        };
+        pending.push(chord.clone());
+        let bindings = &self.bindings[&mode];
+        if let Some((_, action)) = bindings.iter().find(|(keys, _)| keys == pending) {
+            pending.clear();
+            return KeyMatch::Action(*action);
+        }
+        if bindings.iter().any(|(keys, _)| keys.starts_with(pending)) {
+            return KeyMatch::Pending;
+        }
+        if pending.len() > 1 {
+            pending.clear();
+            return self.feed(mode, pending, input);
+        }
+        pending.clear();
+        KeyMatch::None
+    }
+
+    /// 操作に割り当てたキー 表示用に`"g g"`のように並べる
+    pub fn keys(&self, mode: Mode, action: Action) -> Vec<String> {
+        self.bindings[&mode]
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(keys, _)| keys.join(" "))
+            .collect()
+    }
+
+    /// 操作に割り当てた最初のキー 無ければ空
+    pub fn hint(&self, mode: Mode, action: Action) -> String {
+        self.keys(mode, action)
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// キーの一覧 操作ごとにキーをまとめる
+    pub fn help(&self) -> Vec<(Mode, Action, Vec<String>)> {
+        let mut help: Vec<(Mode, Action, Vec<String>)> = vec![];
+        for (mode, bindings) in &self.bindings {
+            for (_, action) in bindings {
+                if !help.iter().any(|(m, a, _)| m == mode && a == action) {
+                    help.push((*mode, *action, self.keys(*mode, *action)));
+                }
+            }
+        }
+        help
+    }
+}
+
+/// プリセットの割り当て
+fn preset(preset: Preset, mode: Mode) -> &'static [(&'static str, Action)] {
+    match (preset, mode) {
+        (Preset::Vim, Mode::Normal) => {
+            &[
+                ("C-q", Action::Quit),
+                ("j", Action::Down),
+                ("Down", Action::Down),
+                ("k", Action::Up),
+                ("Up", Action::Up),
+                ("h", Action::Left),
+                ("Left", Action::Left),
+                ("l", Action::Right),
+                ("Right", Action::Right),
+                ("Tab", Action::Tab),
+                ("r", Action::Reload),
+                ("C-f", Action::ToggleFilter),
+                ("f", Action::ToggleBookmark),
+                ("Enter", Action::Enter),
+                ("w", Action::OpenPostForm),
+                ("R", Action::RetryPost),
+                ("d", Action::Delete),
+                ("Space", Action::ToggleMark),
+                ("v", Action::ToggleVisual),
+                ("a", Action::QuoteReply),
+                ("A", Action::QuoteReplyWithText),
+                ("Backspace", Action::RemoveHistory),
+                ("Esc", Action::ClosePopup),
+                ("g g", Action::ScrollToTop),
+                ("G", Action::ScrollToBottom),
+                ("PageDown", Action::PageDown),
+                ("PageUp", Action::PageUp),
+                ("C-d", Action::HalfPageDown),
+                ("C-u", Action::HalfPageUp),
+                (":", Action::Command),
+                ("/", Action::Search),
+                ("n", Action::NextHit),
+                ("N", Action::PrevHit),
+                ("?", Action::Help),
+            ]
+        }
+        (Preset::Emacs, Mode::Normal) => {
+            &[
+                ("C-x C-c", Action::Quit),
+                ("C-q", Action::Quit),
+                ("C-n", Action::Down),
+                ("Down", Action::Down),
+                ("C-p", Action::Up),
+                ("Up", Action::Up),
+                ("C-b", Action::Left),
+                ("Left", Action::Left),
+                ("C-f", Action::Right),
+                ("Right", Action::Right),
+                ("Tab", Action::Tab),
+                ("g", Action::Reload),
+                ("A-s", Action::ToggleFilter),
+                ("f", Action::ToggleBookmark),
+                ("Enter", Action::Enter),
+                ("w", Action::OpenPostForm),
+                ("R", Action::RetryPost),
+                ("d", Action::Delete),
+                ("m", Action::ToggleMark),
+                ("v", Action::ToggleVisual),
+                ("a", Action::QuoteReply),
+                ("A", Action::QuoteReplyWithText),
+                ("Backspace", Action::RemoveHistory),
+                ("C-g", Action::ClosePopup),
+                ("Esc", Action::ClosePopup),
+                ("A-<", Action::ScrollToTop),
+                ("A->", Action::ScrollToBottom),
+                ("C-v", Action::PageDown),
+                ("PageDown", Action::PageDown),
+                ("A-v", Action::PageUp),
+                ("PageUp", Action::PageUp),
+                ("A-x", Action::Command),
+                ("C-s", Action::Search),
+                ("A-n", Action::NextHit),
+                ("A-p", Action::PrevHit),
+                ("?", Action::Help),
+            ]
+        }
+        (Preset::Vim, Mode::Popup) => {
+            &[
+                ("Esc", Action::ClosePopup),
+                ("Tab", Action::ToggleTextArea),
+                ("C-s", Action::Post),
+                ("C-n", Action::NextIdentity),
+                ("C-a", Action::NextAccount),
+                ("C-e", Action::OpenEditor),
+                ("Enter", Action::EnableInputMode),
+            ]
+        }
+        (Preset::Emacs, Mode::Popup) => {
+            &[
+                ("C-g", Action::ClosePopup),
+                ("Esc", Action::ClosePopup),
+                ("Tab", Action::ToggleTextArea),
+                ("C-s", Action::Post),
+                ("C-n", Action::NextIdentity),
+                ("C-a", Action::NextAccount),
+                ("C-e", Action::OpenEditor),
+                ("Enter", Action::EnableInputMode),
+            ]
+        }
+        (Preset::Vim, Mode::Input) => {
+            &[
+                ("Esc", Action::DisableInputMode),
+                ("C-x", Action::ToggleTextArea),
+            ]
+        }
+        (Preset::Emacs, Mode::Input) => {
+            &[
+                ("C-g", Action::DisableInputMode),
+                ("Esc", Action::DisableInputMode),
+                ("C-x", Action::ToggleTextArea),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(c: char) -> Input {
+        Input {
+            key:  Key::Char(c),
+            ctrl: false,
+            alt:  false,
+        }
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(parse_keys("C-q").unwrap(), vec!["C-q"]);
+        assert_eq!(parse_keys("gg").unwrap(), vec!["g", "g"]);
+        assert_eq!(parse_keys("C-x C-c").unwrap(), vec!["C-x", "C-c"]);
+        assert_eq!(parse_keys("M-<").unwrap(), vec!["A-<"]);
+        assert_eq!(parse_keys("esc").unwrap(), vec!["Esc"]);
+        assert_eq!(parse_keys("-").unwrap(), vec!["-"]);
+        assert!(parse_keys("C-foo").is_err());
+    }
+
+    #[test]
+    fn test_feed() {
+        let mut config = KeymapConfig::default();
+        config.normal.insert("j".to_string(), Action::None);
+        config.normal.insert("J".to_string(), Action::Down);
+        let keymap = Keymap::new(&config).unwrap();
+        let mut pending = vec![];
+
+        assert!(matches!(
+            keymap.feed(Mode::Normal, &mut pending, &input('j')),
+            KeyMatch::None
+        ));
+        assert!(matches!(
+            keymap.feed(Mode::Normal, &mut pending, &input('J')),
+            KeyMatch::Action(Action::Down)
+        ));
+        assert!(matches!(
+            keymap.feed(Mode::Normal, &mut pending, &input('g')),
+            KeyMatch::Pending
+        ));
+        assert!(matches!(
+            keymap.feed(Mode::Normal, &mut pending, &input('g')),
+            KeyMatch::Action(Action::ScrollToTop)
+        ));
+        // 続かないキーは、それだけで探し直す。
+        keymap.feed(Mode::Normal, &mut pending, &input('g'));
+        assert!(matches!(
+            keymap.feed(Mode::Normal, &mut pending, &input('G')),
+            KeyMatch::Action(Action::ScrollToBottom)
+        ));
+        assert!(pending.is_empty());
+    }
+}
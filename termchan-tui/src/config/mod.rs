@@ -2,7 +2,11 @@ pub mod cache;
 pub mod config;
 pub mod credentials;
 pub mod dirs;
+pub mod keymap;
+pub mod ng;
+pub mod secret_store;
 pub mod theme;
+pub mod watcher;
 
 #[cfg(test)]
 mod config_test {
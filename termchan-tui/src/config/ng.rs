@@ -0,0 +1,99 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+/// あぼーんする条件の種類
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+pub enum NgKind {
+    #[display(fmt = "id")]
+    Id,
+    #[display(fmt = "name")]
+    Name,
+    #[display(fmt = "word")]
+    Word,
+}
+
+impl NgKind {
+    pub const ALL: [NgKind; 3] = [NgKind::Id, NgKind::Name, NgKind::Word];
+
+    pub fn parse(s: &str) -> Option<NgKind> {
+        NgKind::ALL.into_iter().find(|kind| kind.to_string() == s)
+    }
+}
+
+/// 設定ファイルの`[ng]` 一致したレスは本文を表示しない。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct NgConfig {
+    /// `ID:`を除いたID
+    pub ids:   Vec<String>,
+    /// 名前に含まれる文字列
+    pub names: Vec<String>,
+    /// 本文に含まれる文字列
+    pub words: Vec<String>,
+}
+
+/// `ID:abcd`でも`abcd`でも同じIDとして扱う。
+fn normalize_id(id: &str) -> &str {
+    id.trim().trim_start_matches("ID:")
+}
+
+impl NgConfig {
+    pub fn is_ng(&self, post_id: &str, name: &str, message: &str) -> bool {
+        let post_id = normalize_id(post_id);
+        (!post_id.is_empty() && self.ids.iter().any(|id| normalize_id(id) == post_id))
+            || self.names.iter().any(|n| name.contains(n.as_str()))
+            || self.words.iter().any(|w| message.contains(w.as_str()))
+    }
+
+    fn list(&mut self, kind: NgKind) -> &mut Vec<String> {
+        match kind {
+            NgKind::Id => &mut self.ids,
+            NgKind::Name => &mut self.names,
+            NgKind::Word => &mut self.words,
+        }
+    }
+
+    /// 追加する。既にあればfalse
+    pub fn add(&mut self, kind: NgKind, value: &str) -> bool {
+        let value = match kind {
+            NgKind::Id => normalize_id(value),
+            _ => value,
+        };
+        if value.is_empty() || self.list(kind).iter().any(|v| v == value) {
+            return false;
+        }
+        self.list(kind).push(value.to_string());
+        true
+    }
+
+    /// 削除する。無ければfalse
+    pub fn remove(&mut self, kind: NgKind, value: &str) -> bool {
+        let value = match kind {
+            NgKind::Id => normalize_id(value),
+            _ => value,
+        };
+        let list = self.list(kind);
+        let len = list.len();
+        list.retain(|v| v != value);
+        list.len() != len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ng() {
+        let mut ng = NgConfig::default();
+        assert!(ng.add(NgKind::Id, "ID:abcd1234"));
+        assert!(!ng.add(NgKind::Id, "abcd1234"));
+        assert!(ng.add(NgKind::Word, "宣伝"));
+        assert!(ng.is_ng("ID:abcd1234", "名無し", "こんにちは"));
+        assert!(ng.is_ng("ID:zzzz", "名無し", "宣伝です"));
+        assert!(!ng.is_ng("", "名無し", "こんにちは"));
+        assert!(ng.remove(NgKind::Id, "ID:abcd1234"));
+        assert!(!ng.is_ng("ID:abcd1234", "名無し", "こんにちは"));
+    }
+}
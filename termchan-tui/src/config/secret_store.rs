@@ -0,0 +1,483 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Mutex, OnceLock},
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305,
+    Key,
+    Nonce,
+};
+use eyre::{bail, eyre, Result};
+use keyring::Entry;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::{credentials::KEYRING_SERVICE_NAME, dirs::Dir};
+
+/// パスフレーズを入れておく環境変数 無ければ初めて使うときに尋ねる
+pub const PASSPHRASE_ENV: &str = "TERMCHAN_PASSPHRASE";
+/// `pass`に保存するときのディレクトリ
+const PASS_DIR: &str = "termchan";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// 暗号化したファイルの中身 salt、鍵、名前と値
+type FileSecrets = ([u8; SALT_LEN], [u8; 32], BTreeMap<String, String>);
+
+/// 秘密情報の保存先の設定
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialBackend {
+    /// キーリングが使えればキーリング、使えなければ暗号化したファイル
+    #[default]
+    Auto,
+    Keyring,
+    File,
+    Pass,
+}
+
+/// 実際に使っている保存先
+enum Store {
+    Keyring,
+    File {
+        path:     PathBuf,
+        state:    Mutex<FileState>,
+        /// キーリングが使えずに切り替えたか
+        fallback: bool,
+    },
+    Pass,
+}
+
+/// 暗号化したファイルは、初めて読み書きするときに開く。
+enum FileState {
+    Closed,
+    Open(FileSecrets),
+    /// パスフレーズが違うなどで開けなかった 読むだけのときは尋ね直さない
+    Failed,
+}
+
+impl FileState {
+    /// 開いた中身を返す。開いていなければパスフレーズを尋ねて開く。
+    /// ファイルが無く`create`でなければ、保存した値は無いので尋ねずにNoneを返す。
+    fn secrets(
+        &mut self,
+        path: &Path,
+        create: bool,
+        retry: bool,
+    ) -> Result<Option<&mut FileSecrets>> {
+        let open = match self {
+            FileState::Open(_) => false,
+            FileState::Failed => retry,
+            FileState::Closed => true,
+        };
+        if open && (create || path.exists()) {
+            match open_file(path) {
+                Ok(secrets) => *self = FileState::Open(secrets),
+                Err(e) => {
+                    *self = FileState::Failed;
+                    return Err(e);
+                }
+            }
+        }
+        match self {
+            FileState::Open(secrets) => Ok(Some(secrets)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// パスフレーズを尋ねる関数 画面を開いたあとは`set_prompt`で差し替える。
+type Prompt = Box<dyn Fn(&str) -> Result<String> + Send + Sync>;
+
+static STORE: OnceLock<Store> = OnceLock::new();
+static PROMPT: OnceLock<Prompt> = OnceLock::new();
+
+fn store() -> &'static Store {
+    STORE.get_or_init(|| Store::Keyring)
+}
+
+/// 保存先を決める。暗号化したファイルはここでは開かず、初めて読み書きするときに
+/// パスフレーズを環境変数か端末から読んで開く。
+pub fn init(backend: CredentialBackend) -> Result<()> {
+    let file = |fallback| -> Result<Store> {
+        Ok(Store::File {
+            path: Dir::get_credentials_path()?,
+            state: Mutex::new(FileState::Closed),
+            fallback,
+        })
+    };
+    let store = match backend {
+        CredentialBackend::Keyring => Store::Keyring,
+        CredentialBackend::Pass => Store::Pass,
+        CredentialBackend::File => file(false)?,
+        CredentialBackend::Auto if keyring_available() => Store::Keyring,
+        CredentialBackend::Auto => file(true)?,
+    };
+    if STORE.set(store).is_err() {
+        bail!("credential store is already initialized");
+    }
+    Ok(())
+}
+
+/// パスフレーズの尋ね方を差し替える。生の入力モードで画面を描いている間に使う。
+pub fn set_prompt(prompt: impl Fn(&str) -> Result<String> + Send + Sync + 'static) {
+    let _ = PROMPT.set(Box::new(prompt));
+}
+
+fn prompt_passphrase(message: &str) -> Result<String> {
+    match PROMPT.get() {
+        Some(prompt) => prompt(message),
+        None => Ok(rpassword::prompt_password(message)?),
+    }
+}
+
+/// Secret Serviceなどが応答するか
+fn keyring_available() -> bool {
+    match Entry::new(KEYRING_SERVICE_NAME, "termchan_probe").get_password() {
+        Ok(_) | Err(keyring::Error::NoEntry) => true,
+        Err(e) => {
+            warn!("keyring is not available: {}", e);
+            false
+        }
+    }
+}
+
+/// 保存先の説明 設定タブに表示する
+pub fn describe() -> String {
+    let store = match store() {
+        Store::Keyring => "キーリング".to_string(),
+        Store::File { path, fallback, .. } => {
+            let reason = if *fallback {
+                "キーリングが使えないため"
+            } else {
+                ""
+            };
+            format!("{}暗号化ファイル ({})", reason, path.display())
+        }
+        Store::Pass => format!("pass ({}/)", PASS_DIR),
+    };
+    format!("保存先: {} 環境変数 TERMCHAN_* があれば優先", store)
+}
+
+/// 保存先の名前に対応する環境変数
+/// `termchan_pass_1`なら`TERMCHAN_PASS_1`
+fn env_name(user: &str) -> String {
+    user.to_uppercase()
+}
+
+/// 値を取得する。環境変数があればそちらを使う。無いか空ならNone
+pub fn get(user: &str) -> Option<String> {
+    if let Ok(value) = env::var(env_name(user)) {
+        return Some(value).filter(|v| !v.is_empty());
+    }
+    let value = match store() {
+        Store::Keyring => {
+            match Entry::new(KEYRING_SERVICE_NAME, user).get_password() {
+                Ok(value) => Some(value),
+                Err(keyring::Error::NoEntry) => None,
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            }
+        }
+        Store::File { path, state, .. } => {
+            match state.lock().unwrap().secrets(path, false, false) {
+                Ok(secrets) => secrets.and_then(|(_, _, secrets)| secrets.get(user).cloned()),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            }
+        }
+        Store::Pass => pass_show(user),
+    };
+    value.filter(|v| !v.is_empty())
+}
+
+/// 値を保存する。空なら削除する。
+pub fn set(user: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return delete(user);
+    }
+    match store() {
+        Store::Keyring => Entry::new(KEYRING_SERVICE_NAME, user).set_password(value)?,
+        Store::File { path, state, .. } => {
+            let mut state = state.lock().unwrap();
+            let file = state
+                .secrets(path, true, true)?
+                .ok_or_else(|| eyre!("認証情報のファイルを開けません。"))?;
+            reload(path, file)?;
+            let (salt, key, secrets) = file;
+            secrets.insert(user.to_string(), value.to_string());
+            write_file(path, salt, key, secrets)?;
+        }
+        Store::Pass => pass_insert(user, value)?,
+    }
+    Ok(())
+}
+
+pub fn delete(user: &str) -> Result<()> {
+    match store() {
+        Store::Keyring => {
+            match Entry::new(KEYRING_SERVICE_NAME, user).delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Store::File { path, state, .. } => {
+            let mut state = state.lock().unwrap();
+            if let Some(file) = state.secrets(path, false, true)? {
+                reload(path, file)?;
+                let (salt, key, secrets) = file;
+                if secrets.remove(user).is_some() {
+                    write_file(path, salt, key, secrets)?;
+                }
+            }
+        }
+        Store::Pass => {
+            if pass_show(user).is_some() {
+                pass_command(&["rm", "--force", &pass_name(user)], None)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 暗号化したファイルを開く。無ければ新しいパスフレーズで空の中身を作る。
+fn open_file(path: &Path) -> Result<FileSecrets> {
+    let exists = path.exists();
+    let passphrase = match env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = prompt_passphrase(&format!("{} のパスフレーズ: ", path.display()))?;
+            if !exists {
+                let confirm = prompt_passphrase("もう一度入力してください: ")?;
+                if passphrase != confirm {
+                    bail!("パスフレーズが一致しません。");
+                }
+            }
+            passphrase
+        }
+    };
+
+    if exists {
+        decrypt(&passphrase, &fs::read(path)?)
+    } else {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Ok((salt, derive_key(&passphrase, &salt)?, BTreeMap::new()))
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| eyre!("{}", e))?;
+    Ok(key)
+}
+
+/// salt、nonce、暗号文の順に並べる。
+fn encrypt(
+    salt: &[u8; SALT_LEN],
+    key: &[u8; 32],
+    secrets: &BTreeMap<String, String>,
+) -> Result<Vec<u8>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let plain = serde_json::to_vec(secrets)?;
+    let encrypted = cipher
+        .encrypt(Nonce::from_slice(&nonce), plain.as_ref())
+        .map_err(|e| eyre!("{}", e))?;
+
+    let mut bytes = Vec::with_capacity(SALT_LEN + NONCE_LEN + encrypted.len());
+    bytes.extend_from_slice(salt);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&encrypted);
+    Ok(bytes)
+}
+
+fn decrypt(passphrase: &str, bytes: &[u8]) -> Result<FileSecrets> {
+    if bytes.len() < SALT_LEN + NONCE_LEN {
+        bail!("認証情報のファイルが壊れています。");
+    }
+    let salt = &bytes[..SALT_LEN];
+    let key = derive_key(passphrase, salt)?;
+    Ok((salt.try_into()?, key, decrypt_with_key(&key, bytes)?))
+}
+
+/// 鍵が分かっているファイルを読む。書き込む前に読み直すときに使う。
+fn decrypt_with_key(key: &[u8; 32], bytes: &[u8]) -> Result<BTreeMap<String, String>> {
+    if bytes.len() < SALT_LEN + NONCE_LEN {
+        bail!("認証情報のファイルが壊れています。");
+    }
+    let (nonce, encrypted) = bytes[SALT_LEN..].split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let plain = cipher
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| eyre!("パスフレーズが違うか、認証情報のファイルが壊れています。"))?;
+    Ok(serde_json::from_slice(&plain)?)
+}
+
+/// 別のプロセスが書き込んだ値を消さないよう、書き込む前にファイルを読み直す。
+/// 作り直されて鍵が変わっていれば、パスフレーズを尋ねて開き直す。
+fn reload(path: &Path, file: &mut FileSecrets) -> Result<()> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if bytes.get(..SALT_LEN) == Some(&file.0[..]) {
+        file.2 = decrypt_with_key(&file.1, &bytes)?;
+    } else {
+        *file = open_file(path)?;
+    }
+    Ok(())
+}
+
+/// 書き込みの途中で止まっても元のファイルが残るよう、同じ場所の一時ファイルに書いてから置き換える。
+fn write_file(
+    path: &Path,
+    salt: &[u8; SALT_LEN],
+    key: &[u8; 32],
+    secrets: &BTreeMap<String, String>,
+) -> Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let bytes = encrypt(salt, key, secrets)?;
+    let temp = dir.join(format!(
+        ".{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // 前に失敗したときの一時ファイルが残っていれば消す。
+    let _ = fs::remove_file(&temp);
+    let res = options.open(&temp).and_then(|mut file| {
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if let Err(e) = res {
+        let _ = fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn pass_name(user: &str) -> String {
+    format!("{}/{}", PASS_DIR, user)
+}
+
+fn pass_show(user: &str) -> Option<String> {
+    let output = pass_command(&["show", &pass_name(user)], None).ok()?;
+    output.lines().next().map(String::from)
+}
+
+fn pass_insert(user: &str, value: &str) -> Result<()> {
+    pass_command(
+        &["insert", "--multiline", "--force", &pass_name(user)],
+        Some(value),
+    )?;
+    Ok(())
+}
+
+/// `pass`を実行して標準出力を返す。
+fn pass_command(args: &[&str], input: Option<&str>) -> Result<String> {
+    let mut child = Command::new("pass")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(input) = input {
+        child.stdin.take().unwrap().write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "pass {}: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_file() {
+        let salt = [1u8; SALT_LEN];
+        let key = derive_key("passphrase", &salt).unwrap();
+        let mut secrets = BTreeMap::new();
+        secrets.insert("termchan_pass_1".to_string(), "secret".to_string());
+
+        let bytes = encrypt(&salt, &key, &secrets).unwrap();
+        let (_, _, decrypted) = decrypt("passphrase", &bytes).unwrap();
+        assert_eq!(decrypted, secrets);
+        assert!(decrypt("wrong", &bytes).is_err());
+        assert!(decrypt("passphrase", &bytes[..10]).is_err());
+    }
+
+    #[test]
+    fn test_file_state_without_file() {
+        // 保存した値が無ければ、パスフレーズを尋ねずに空として扱う。
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials");
+        let mut state = FileState::Closed;
+        assert!(state.secrets(&path, false, true).unwrap().is_none());
+        assert!(matches!(state, FileState::Closed));
+    }
+
+    #[test]
+    fn test_reload_before_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials");
+        let salt = [2u8; SALT_LEN];
+        let key = derive_key("passphrase", &salt).unwrap();
+        let mut file = (salt, key, BTreeMap::new());
+        file.2.insert("a".to_string(), "1".to_string());
+        write_file(&path, &file.0, &file.1, &file.2).unwrap();
+
+        // 別のプロセスが値を足した。
+        let mut other = file.2.clone();
+        other.insert("b".to_string(), "2".to_string());
+        write_file(&path, &salt, &key, &other).unwrap();
+
+        reload(&path, &mut file).unwrap();
+        assert_eq!(file.2, other);
+        // 一時ファイルは残らない。
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_env_name() {
+        assert_eq!(env_name("termchan_pass_1"), "TERMCHAN_PASS_1");
+    }
+}
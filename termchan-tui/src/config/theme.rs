@@ -1,6 +1,35 @@
 use serde::{Deserialize, Serialize};
 use tui::{style::Color, widgets::BorderType as TuiBorderType};
 
+/// 色は`parse_color`で読める文字列で書き出す。
+/// TOMLでは`Color::Rgb`をそのまま書き出せないため。以前の`{ "Rgb": [..] }`の形も読める。
+mod color_serde {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use tui::style::Color;
+
+    use super::{format_color, parse_color};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ColorRepr {
+        Str(String),
+        Color(Color),
+    }
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_color(*color))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        match ColorRepr::deserialize(deserializer)? {
+            ColorRepr::Str(s) => {
+                parse_color(&s).ok_or_else(|| D::Error::custom(format!("unknown color `{}`", s)))
+            }
+            ColorRepr::Color(color) => Ok(color),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum BorderType {
     Plain,
@@ -9,6 +38,18 @@ pub enum BorderType {
     Thick,
 }
 
+impl BorderType {
+    /// 設定タブで順に切り替える
+    pub fn next(&self) -> BorderType {
+        match self {
+            BorderType::Plain => BorderType::Rounded,
+            BorderType::Rounded => BorderType::Double,
+            BorderType::Double => BorderType::Thick,
+            BorderType::Thick => BorderType::Plain,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum ThumbnailSize {
     Small,
@@ -16,24 +57,40 @@ pub enum ThumbnailSize {
     Large,
 }
 
+/// 設定ファイルで省略した色は既定の色になる
 #[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(default)]
 pub struct Theme {
+    #[serde(with = "color_serde")]
     pub status_bar:   Color,
+    #[serde(with = "color_serde")]
     pub error_border: Color,
+    #[serde(with = "color_serde")]
     pub error_text:   Color,
+    #[serde(with = "color_serde")]
     pub hint:         Color,
+    #[serde(with = "color_serde")]
     pub hovered:      Color,
+    #[serde(with = "color_serde")]
     pub active:       Color,
+    #[serde(with = "color_serde")]
     pub inactive:     Color,
+    #[serde(with = "color_serde")]
     pub selected:     Color,
+    #[serde(with = "color_serde")]
     pub text:         Color,
 
     // text
+    #[serde(with = "color_serde")]
     pub active_unselected_text:   Color,
+    #[serde(with = "color_serde")]
     pub active_selected_text:     Color,
+    #[serde(with = "color_serde")]
     pub inactive_unselected_text: Color,
+    #[serde(with = "color_serde")]
     pub inactive_selected_text:   Color,
 
+    #[serde(with = "color_serde")]
     pub reset: Color,
 
     pub active_item_symbol:   String,
@@ -42,9 +99,13 @@ pub struct Theme {
     pub read_symbol:          String,
     pub posted_symbol:        String,
 
+    #[serde(with = "color_serde")]
     pub ikioi_low:         Color,
+    #[serde(with = "color_serde")]
     pub ikioi_middle:      Color,
+    #[serde(with = "color_serde")]
     pub ikioi_middle_high: Color,
+    #[serde(with = "color_serde")]
     pub ikioi_high:        Color,
 
     /// border_type: Plain | Rounded | Double | Thick
@@ -89,7 +150,107 @@ impl Default for Theme {
     }
 }
 
+/// 設定タブで変更できる色の名前と、Themeの項目を対応させる。
+macro_rules! theme_colors {
+    ($($name:ident),* $(,)?) => {
+        impl Theme {
+            pub const COLOR_NAMES: &'static [&'static str] = &[$(stringify!($name)),*];
+
+            pub fn color(&self, name: &str) -> Option<Color> {
+                match name {
+                    $(stringify!($name) => Some(self.$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn set_color(&mut self, name: &str, color: Color) -> bool {
+                match name {
+                    $(stringify!($name) => self.$name = color,)*
+                    _ => return false,
+                }
+                true
+            }
+        }
+    };
+}
+
+theme_colors!(
+    status_bar,
+    error_border,
+    error_text,
+    hint,
+    hovered,
+    active,
+    inactive,
+    selected,
+    text,
+    active_unselected_text,
+    active_selected_text,
+    inactive_unselected_text,
+    inactive_selected_text,
+    reset,
+    ikioi_low,
+    ikioi_middle,
+    ikioi_middle_high,
+    ikioi_high,
+);
+
+/// 名前で指定できる色
+const NAMED_COLORS: [(&str, Color); 17] = [
+    ("Reset", Color::Reset),
+    ("Black", Color::Black),
+    ("Red", Color::Red),
+    ("Green", Color::Green),
+    ("Yellow", Color::Yellow),
+    ("Blue", Color::Blue),
+    ("Magenta", Color::Magenta),
+    ("Cyan", Color::Cyan),
+    ("Gray", Color::Gray),
+    ("DarkGray", Color::DarkGray),
+    ("LightRed", Color::LightRed),
+    ("LightGreen", Color::LightGreen),
+    ("LightYellow", Color::LightYellow),
+    ("LightBlue", Color::LightBlue),
+    ("LightMagenta", Color::LightMagenta),
+    ("LightCyan", Color::LightCyan),
+    ("White", Color::White),
+];
+
+/// `LightCyan`のような名前、`#rrggbb`、0から255の番号を色にする。
+pub fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let v = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((v >> 16) as u8, (v >> 8) as u8, v as u8));
+    }
+    if let Ok(i) = s.parse::<u8>() {
+        return Some(Color::Indexed(i));
+    }
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, color)| *color)
+}
+
+/// `parse_color`で読める形にする。
+pub fn format_color(color: Color) -> String {
+    match color {
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        Color::Indexed(i) => i.to_string(),
+        color => format!("{:?}", color),
+    }
+}
+
 impl Theme {
+    pub fn border_type_name(&self) -> String {
+        format!("{:?}", self.border_type)
+    }
+    pub fn next_border_type(&mut self) {
+        self.border_type = self.border_type.next();
+    }
     pub fn border_type(&self) -> TuiBorderType {
         match self.border_type {
             BorderType::Plain => TuiBorderType::Plain,
@@ -99,3 +260,20 @@ impl Theme {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("lightcyan"), Some(Color::LightCyan));
+        assert_eq!(parse_color("#ff8000"), Some(Color::Rgb(255, 128, 0)));
+        assert_eq!(parse_color("208"), Some(Color::Indexed(208)));
+        assert_eq!(parse_color("#ff80"), None);
+        assert_eq!(parse_color("orange"), None);
+        for color in [Color::DarkGray, Color::Rgb(1, 2, 3), Color::Indexed(7)] {
+            assert_eq!(parse_color(&format_color(color)), Some(color));
+        }
+    }
+}
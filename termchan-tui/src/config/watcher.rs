@@ -0,0 +1,45 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// 更新を調べる間隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 設定ファイルの更新を調べる。
+/// 通知の仕組みには頼らず、一定の間隔で更新日時を比べる。
+pub struct ConfigWatcher {
+    paths:    Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    checked:  Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let modified = paths.iter().map(modified_time).collect();
+        ConfigWatcher {
+            paths,
+            modified,
+            checked: Instant::now(),
+        }
+    }
+
+    /// 前回調べたときから、どれかのファイルが更新、作成、削除されていればtrue
+    pub fn changed(&mut self) -> bool {
+        if self.checked.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+        self.checked = Instant::now();
+        let modified = self.paths.iter().map(modified_time).collect::<Vec<_>>();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
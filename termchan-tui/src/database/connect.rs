@@ -1,22 +1,166 @@
-use eyre::{bail, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, WrapErr};
 use migration::{Migrator, MigratorTrait};
-use sea_orm::{Database, DbConn};
+use sea_orm::{ConnectOptions, ConnectionTrait, Database, DbBackend, DbConn, Statement};
+
+use crate::config::{config::Config, dirs::Dir};
+
+/// データベースのパスを上書きする環境変数
+pub const DB_PATH_ENV: &str = "TERMCHAN_DB";
+
+/// 同時に開くコネクションの上限
+const MAX_CONNECTIONS: u32 = 8;
+
+/// データベースファイルの場所を決める。
+/// 優先順位: コマンドライン引数 > 環境変数 > 設定ファイル > データディレクトリ
+pub fn resolve_db_path(arg: Option<PathBuf>, config: &Config) -> Result<PathBuf> {
+    if let Some(path) = arg {
+        return Ok(path);
+    }
+    if let Some(path) = std::env::var_os(DB_PATH_ENV).filter(|p| !p.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+    if let Some(path) = config.db_path.clone() {
+        return Ok(path);
+    }
+
+    let path = Dir::get_db_path()?;
+    migrate_legacy_db(&Dir::get_legacy_db_path(), &path)?;
+    Ok(path)
+}
 
-use crate::config::dirs::Dir;
+/// 旧バージョンの`/var/tmp/termchan.db`を新しい場所へ一度だけ移動する。
+/// 移動先に既にデータベースがある場合は何もしない。
+/// `/var/tmp`は他のユーザーと共有なので、自分のものでないデータベースは移動しない。
+fn migrate_legacy_db(legacy: &Path, path: &Path) -> Result<()> {
+    if path.exists() || !legacy.exists() {
+        return Ok(());
+    }
+    // WALモードのときに作られるファイルも一緒に移動する。
+    let files = ["", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| {
+            (
+                PathBuf::from(format!("{}{}", legacy.display(), suffix)),
+                PathBuf::from(format!("{}{}", path.display(), suffix)),
+            )
+        })
+        .filter(|(from, _)| from.exists())
+        .collect::<Vec<_>>();
+    if let Some((from, _)) = files.iter().find(|(from, _)| !is_owned(from)) {
+        warn!("Skipped moving {:?}: owned by another user", from);
+        return Ok(());
+    }
 
-pub async fn establish_connection() -> Result<DbConn> {
-    let path = match Dir::get_db_path() {
-        Ok(path) => path,
-        Err(e) => {
-            error!("{}", e);
-            bail!(e);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    for (from, to) in files {
+        // 別のファイルシステムへはrenameできないのでコピーしてから消す。
+        if fs::rename(&from, &to).is_err() {
+            copy_and_remove(&from, &to)?;
         }
-    };
+    }
+    info!("Moved database from {:?} to {:?}", legacy, path);
+    Ok(())
+}
+
+/// 今のユーザーのファイルか
+#[cfg(unix)]
+fn is_owned(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).is_ok_and(|meta| meta.uid() == unsafe { libc::getuid() })
+}
+
+#[cfg(not(unix))]
+fn is_owned(_path: &Path) -> bool {
+    true
+}
+
+/// コピーした元のファイルを消す。消せなくても、コピーできていれば移動したものとする。
+fn copy_and_remove(from: &Path, to: &Path) -> Result<()> {
+    fs::copy(from, to).wrap_err_with(|| format!("failed to move {:?} to {:?}", from, to))?;
+    if let Err(e) = fs::remove_file(from) {
+        warn!("Failed to remove {:?}: {}", from, e);
+    }
+    Ok(())
+}
+
+/// 起動時に一度だけ呼び出す。
+/// 返されたコネクションプールを`App`経由で使い回す。
+pub async fn establish_connection(path: &Path) -> Result<DbConn> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+    let mut opt = ConnectOptions::new(url);
+    opt.max_connections(MAX_CONNECTIONS);
+    let db = Database::connect(opt).await?;
+
+    // バックグラウンドの更新とUIからの読み込みが競合しないようにWALモードにする。
+    db.execute(Statement::from_string(
+        DbBackend::Sqlite,
+        "PRAGMA journal_mode=WAL;".to_string(),
+    ))
+    .await?;
 
-    let db = Database::connect(path).await?;
     Migrator::up(&db, None).await?;
 
-    info!("Database connection established");
+    info!("Database connection established: {:?}", path);
 
     Ok(db)
 }
+
+/// テストのために一時ディレクトリにデータベースを作る。
+/// ディレクトリは返した`TempDir`を落としたときに、テストが失敗しても消える。
+#[cfg(test)]
+pub async fn temp_db() -> (tempfile::TempDir, DbConn) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = establish_connection(&dir.path().join("termchan.db"))
+        .await
+        .unwrap();
+    (dir, db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_legacy_db() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("legacy").join("termchan.db");
+        let path = dir.path().join("data").join("termchan.db");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, b"db").unwrap();
+
+        migrate_legacy_db(&legacy, &path).unwrap();
+        assert!(!legacy.exists());
+        assert_eq!(fs::read(&path).unwrap(), b"db");
+
+        // 移動先が既にあるときは上書きしない。
+        fs::write(&legacy, b"old").unwrap();
+        migrate_legacy_db(&legacy, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"db");
+    }
+
+    /// 元のファイルを消せなくても、起動は止めない。
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_copy_and_remove_without_permission() {
+        let dir = tempfile::tempdir().unwrap();
+        let to = dir.path().join("termchan.db");
+
+        // procfsのファイルは誰にも消せない。
+        let from = Path::new("/proc/self/cmdline");
+        copy_and_remove(from, &to).unwrap();
+        assert!(from.exists());
+        assert!(to.exists());
+    }
+}
@@ -6,14 +6,14 @@ use log4rs::{
     encode::pattern::PatternEncoder,
 };
 
-pub fn init_log(path: String) -> Result<()> {
+pub fn init_log(path: String, level: LevelFilter) -> Result<()> {
     let logfile = FileAppender::builder()
         .encoder(Box::new(PatternEncoder::new("{l} - {m}\n")))
         .build(path)?;
 
     let config = Config::builder()
         .appender(Appender::builder().build("logfile", Box::new(logfile)))
-        .build(Root::builder().appender("logfile").build(LevelFilter::Info))?;
+        .build(Root::builder().appender("logfile").build(level))?;
 
     log4rs::init_config(config)?;
 
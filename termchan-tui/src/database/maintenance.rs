@@ -0,0 +1,271 @@
+use entity::{my_post, prelude::*, thread, thread_post};
+use eyre::Result;
+use migration::Query;
+use sea_orm::{
+    ColumnTrait,
+    ConnectionTrait,
+    DbBackend,
+    DbConn,
+    EntityTrait,
+    FromQueryResult,
+    QueryFilter,
+    Statement,
+};
+use serde::{Deserialize, Serialize};
+
+/// 一度に消すスレの数 SQLiteの変数の上限を超えないようにする。
+const DELETE_CHUNK: usize = 500;
+
+/// 整理した結果
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Cleanup {
+    pub threads: u64,
+    pub posts:   u64,
+    /// 小さくなった大きさ(バイト)
+    pub freed:   i64,
+}
+
+/// 板ごとの容量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, FromQueryResult)]
+pub struct BoardUsage {
+    pub board_id: i32,
+    pub name:     String,
+    pub threads:  i64,
+    pub posts:    i64,
+    /// レスの本文と名前などの大きさ(バイト) 索引などは含まない
+    pub bytes:    i64,
+}
+
+/// 古いスレを消し、`vacuum`なら空いた領域を詰める。
+/// `days`が0ならスレは消さない。
+pub async fn clean(db: &DbConn, days: u32, vacuum: bool) -> Result<Cleanup> {
+    let size = database_size(db).await?;
+    let mut cleanup = Cleanup::default();
+    if days > 0 {
+        let before = chrono::Utc::now().timestamp() - i64::from(days) * 24 * 60 * 60;
+        (cleanup.threads, cleanup.posts) = remove_stale_threads(db, before).await?;
+    }
+    if vacuum {
+        db.execute(Statement::from_string(
+            DbBackend::Sqlite,
+            "VACUUM;".to_string(),
+        ))
+        .await?;
+    }
+    cleanup.freed = size - database_size(db).await?;
+    Ok(cleanup)
+}
+
+/// スレ一覧から消えたスレのうち、`before`より前に立ったスレをレスごと消す。
+/// 読んだスレ、アーカイブしたスレ、自分が書き込んだスレは残す。
+/// スレは残してレスだけを消す`ThreadStateItem::prune`と違い、スレの記録ごと無くなる。
+pub async fn remove_stale_threads(db: &DbConn, before: i64) -> Result<(u64, u64)> {
+    let ids = thread::Entity::find()
+        .filter(thread::Column::Stopdone.eq(true))
+        .filter(thread::Column::IsRead.eq(false))
+        .filter(thread::Column::Archived.eq(false))
+        .filter(thread::Column::CreatedTime.lt(before))
+        .filter(thread::Column::FetchedAt.lt(before))
+        .filter(
+            thread::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(my_post::Column::ThreadId)
+                    .from(my_post::Entity)
+                    // NULLが混ざると`NOT IN`が一件も一致しなくなる。
+                    .and_where(my_post::Column::ThreadId.is_not_null())
+                    .to_owned(),
+            ),
+        )
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|thread| thread.id)
+        .collect::<Vec<_>>();
+
+    let (mut threads, mut posts) = (0, 0);
+    for chunk in ids.chunks(DELETE_CHUNK) {
+        posts += ThreadPost::delete_many()
+            .filter(thread_post::Column::ThreadId.is_in(chunk.to_vec()))
+            .exec(db)
+            .await?
+            .rows_affected;
+        threads += Thread::delete_many()
+            .filter(thread::Column::Id.is_in(chunk.to_vec()))
+            .exec(db)
+            .await?
+            .rows_affected;
+    }
+    Ok((threads, posts))
+}
+
+/// データベースファイルの大きさ(バイト)
+pub async fn database_size(db: &DbConn) -> Result<i64> {
+    let mut size = 1;
+    for pragma in ["page_count", "page_size"] {
+        let row = db
+            .query_one(Statement::from_string(
+                DbBackend::Sqlite,
+                format!("PRAGMA {};", pragma),
+            ))
+            .await?;
+        size *= match row {
+            Some(row) => row.try_get::<i64>("", pragma)?,
+            None => 0,
+        };
+    }
+    Ok(size)
+}
+
+/// 板ごとのスレとレスの数、大きさ 大きい順に並べる。
+pub async fn board_usage(db: &DbConn) -> Result<Vec<BoardUsage>> {
+    let sql = r#"
+        SELECT
+            board.id AS board_id,
+            board.name AS name,
+            COUNT(DISTINCT thread.id) AS threads,
+            COUNT(thread_post.id) AS posts,
+            COALESCE(SUM(
+                LENGTH(CAST(thread_post.message AS BLOB))
+                + LENGTH(CAST(thread_post.name AS BLOB))
+                + LENGTH(CAST(thread_post.email AS BLOB))
+            ), 0) AS bytes
+        FROM board
+        JOIN thread ON thread.board_id = board.id
+        LEFT JOIN thread_post ON thread_post.thread_id = thread.id
+        GROUP BY board.id
+        ORDER BY bytes DESC, threads DESC
+    "#;
+    Ok(
+        BoardUsage::find_by_statement(Statement::from_string(DbBackend::Sqlite, sql.to_string()))
+            .all(db)
+            .await?,
+    )
+}
+
+/// `1.2 MB`のように、人が読みやすい大きさにする。
+pub fn format_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ActiveModelTrait, Set};
+    use termchan_core::url::thread::BoardParams;
+
+    use super::*;
+    use crate::{database::connect::temp_db, state::board::BoardStateItem};
+
+    /// 読んだか、アーカイブしたかを指定して、レスが一つあるdat落ちのスレを登録する。
+    async fn insert_thread(
+        db: &DbConn,
+        board_id: i32,
+        key: i64,
+        read: bool,
+        archived: bool,
+    ) -> i32 {
+        let thread = thread::ActiveModel {
+            index: Set(0),
+            name: Set(key.to_string()),
+            url: Set(format!("https://egg.5ch.net/test/read.cgi/news/{}", key)),
+            count: Set(1),
+            ikioi: Set(Some(0.0)),
+            stopdone: Set(true),
+            is_read: Set(read),
+            before_read: Set(0),
+            created_time: Set(Some(key)),
+            board_id: Set(board_id),
+            last_read_index: Set(0),
+            scroll_offset: Set(0),
+            archived: Set(archived),
+            archived_at: Set(0),
+            fetched_at: Set(key),
+            is_new: Set(false),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        thread_post::ActiveModel {
+            index: Set(1),
+            name: Set("名無し".to_string()),
+            email: Set("sage".to_string()),
+            post_id: Set("ID:AAAA".to_string()),
+            message: Set("本文".to_string()),
+            date: Set(None),
+            thread_id: Set(thread.id),
+            thread_id_index: Set(format!("{}-1", thread.id)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+        thread.id
+    }
+
+    async fn insert_my_post(db: &DbConn, thread_id: Option<i32>) {
+        my_post::ActiveModel {
+            board_url: Set("https://egg.5ch.net/news/".to_string()),
+            thread_url: Set(None),
+            thread_id: Set(thread_id),
+            subject: Set(None),
+            post_index: Set(None),
+            name: Set(String::new()),
+            email: Set(String::new()),
+            message: Set("本文".to_string()),
+            posted_at: Set(0),
+            after_count: Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_threads() {
+        let (_dir, db) = temp_db().await;
+        let board =
+            BoardStateItem::find_or_register(&db, &BoardParams::from("https://egg.5ch.net/news/"))
+                .await
+                .unwrap();
+
+        let stale = insert_thread(&db, board.id, 100, false, false).await;
+        insert_thread(&db, board.id, 101, true, false).await;
+        insert_thread(&db, board.id, 102, false, true).await;
+        let posted = insert_thread(&db, board.id, 103, false, false).await;
+        insert_my_post(&db, Some(posted)).await;
+        // スレを作る書き込みなど、スレの決まっていない書き込み
+        insert_my_post(&db, None).await;
+        // まだ古くないスレ
+        insert_thread(&db, board.id, 1000, false, false).await;
+
+        let usage = board_usage(&db).await.unwrap();
+        assert_eq!((usage[0].threads, usage[0].posts), (5, 5));
+
+        assert_eq!(remove_stale_threads(&db, 500).await.unwrap(), (1, 1));
+        assert!(Thread::find_by_id(stale).one(&db).await.unwrap().is_none());
+
+        let usage = board_usage(&db).await.unwrap();
+        assert_eq!(usage[0].board_id, board.id);
+        assert_eq!((usage[0].threads, usage[0].posts), (4, 4));
+        assert!(usage[0].bytes > 0);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
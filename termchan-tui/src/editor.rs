@@ -0,0 +1,169 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{bail, Result, WrapErr};
+
+use crate::state::draft::DraftStateItem;
+
+/// ヘッダーと本文の区切り
+const SEPARATOR: &str = "-------- この行より下が本文です --------";
+const NAME: &str = "名前:";
+const EMAIL: &str = "メール:";
+const SUBJECT: &str = "タイトル:";
+
+/// `$VISUAL`、`$EDITOR`の順に使うエディタを決める。どちらも無ければvi。
+fn editor_command() -> Vec<String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // `code -w`のように引数付きで指定されることがある。
+    editor.split_whitespace().map(String::from).collect()
+}
+
+/// 編集に使う一時ファイル 抜けるときに必ず消す。
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// 他のユーザーが先に作ったファイルやシンボリックリンクには書かないよう、
+    /// 新しく自分だけが読み書きできるファイルを作る。
+    fn create() -> Result<(TempFile, File)> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        for i in 0..100u32 {
+            let path = std::env::temp_dir().join(format!(
+                "termchan-{}-{}-{}.txt",
+                std::process::id(),
+                nanos,
+                i
+            ));
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(file) => return Ok((TempFile { path }, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        bail!("failed to create a temporary file for the editor")
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// フォームの内容を一時ファイルに書き出し、外部エディタで編集した結果を返す。
+/// 呼び出す側で、端末の描画と入力の読み込みを止めておくこと。
+pub fn edit(draft: &DraftStateItem, is_thread: bool) -> Result<DraftStateItem> {
+    let (temp, mut file) = TempFile::create()?;
+    file.write_all(to_text(draft, is_thread).as_bytes())?;
+    drop(file);
+
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(&temp.path)
+        .status()
+        .wrap_err_with(|| format!("failed to run editor: {}", command.join(" ")));
+    let text = fs::read_to_string(&temp.path);
+    drop(temp);
+
+    if !status?.success() {
+        bail!("editor exited with an error: {}", command.join(" "));
+    }
+    Ok(parse(&text?, draft))
+}
+
+fn to_text(draft: &DraftStateItem, is_thread: bool) -> String {
+    let mut text = format!("{} {}\n{} {}\n", NAME, draft.name, EMAIL, draft.email);
+    if is_thread {
+        text.push_str(&format!(
+            "{} {}\n",
+            SUBJECT,
+            draft.subject.as_deref().unwrap_or_default()
+        ));
+    }
+    text.push_str(SEPARATOR);
+    text.push('\n');
+    text.push_str(&draft.message);
+    text.push('\n');
+    text
+}
+
+/// 区切りが消されていたら、全体を本文として扱う。
+fn parse(text: &str, draft: &DraftStateItem) -> DraftStateItem {
+    let mut draft = draft.clone();
+    let lines = text.lines().collect::<Vec<_>>();
+    let body = match lines.iter().position(|l| l.trim() == SEPARATOR) {
+        Some(i) => {
+            for line in &lines[..i] {
+                let value = |key: &str| line.strip_prefix(key).map(|v| v.trim().to_string());
+                if let Some(name) = value(NAME) {
+                    draft.name = name;
+                } else if let Some(email) = value(EMAIL) {
+                    draft.email = email;
+                } else if let Some(subject) = value(SUBJECT) {
+                    draft.subject = Some(subject);
+                }
+            }
+            &lines[i + 1..]
+        }
+        None => &lines[..],
+    };
+    // エディタが付ける末尾の改行は取り除く。
+    draft.message = body.join("\n").trim_end_matches('\n').to_string();
+    draft
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let draft = DraftStateItem {
+            url:     "https://example.com/test/read.cgi/news/1/".to_string(),
+            name:    "名無し".to_string(),
+            email:   "sage".to_string(),
+            subject: Some("スレタイ".to_string()),
+            message: "　 ∧＿∧\n　（　´∀｀）".to_string(),
+        };
+        let parsed = parse(&to_text(&draft, true), &draft);
+        assert_eq!(parsed.name, draft.name);
+        assert_eq!(parsed.subject, draft.subject);
+        assert_eq!(parsed.message, draft.message);
+
+        let parsed = parse("本文だけ\n\n", &draft);
+        assert_eq!(parsed.email, "sage");
+        assert_eq!(parsed.message, "本文だけ");
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let (temp, _) = TempFile::create().unwrap();
+        let path = temp.path.clone();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        drop(temp);
+        assert!(!path.exists());
+    }
+}
@@ -1,14 +1,20 @@
-use std::{io, time::Duration};
+use std::{
+    io::{self, Read},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 use termion::{
-    event::{Event as TermionEvent, Key},
+    event::{Event as TermionEvent, Key, MouseButton, MouseEvent},
     input::TermRead,
     raw::IntoRawMode,
 };
-use tokio::sync::mpsc::{self, Receiver};
-use tui_textarea::Input;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tui_textarea::{Input, Key as InputKey};
+
+use crate::{archive::SnapshotProgress, ui::stateful_list::Motion};
 
 #[macro_export]
 macro_rules! ctrl {
@@ -61,6 +67,8 @@ pub enum Command {
     Input(Key),
     Tick,
     Event(TermionEvent),
+    /// 裏で進めているアーカイブの保存
+    Snapshot(SnapshotProgress),
 }
 
 #[allow(dead_code)]
@@ -85,16 +93,101 @@ pub enum Event {
     NextTab,
     ScrollToTop,
     ScrollToBottom,
+    /// 選択を動かす
+    Move(Motion),
+    /// スレではレス番号、それ以外では1から数えた位置へ移動する
+    Jump(usize),
+    /// 文字列を探して、最初に一致した項目へ移動する
+    Search(String),
+    /// 最後に探した文字列の次の一致へ移動する falseなら前
+    NextHit(bool),
     Message(String),
     ClosePopup,
     ToggleInputMode,
     EnableInputMode,
     DisableInputMode,
     ToggleTextArea,
+    /// 書き込みフォームを開く
+    OpenPostForm,
+    /// 最後に失敗した書き込みを再送する
+    RetryPost,
+    /// 選択している項目を削除する
+    Delete,
+    /// 書き込みに使う名前を切り替える
+    NextIdentity,
+    /// 書き込みに使うアカウントを切り替える
+    NextAccount,
+    /// 選択しているレスに印を付ける、または外す
+    ToggleMark,
+    /// 範囲選択を始める、または終える
+    ToggleVisual,
+    /// 選択しているレスへのアンカーを入れて書き込みフォームを開く
+    /// trueなら本文も引用する
+    QuoteReply(bool),
     Input(Input),
 }
+/// 端末の入力を、入力欄や操作のキーに使う形にする。
+pub fn to_input(event: TermionEvent) -> Input {
+    let (key, ctrl, alt) = match event {
+        TermionEvent::Key(key) => {
+            match key {
+                Key::Char('\n' | '\r') => (InputKey::Enter, false, false),
+                Key::Char(c) => (InputKey::Char(c), false, false),
+                Key::Ctrl(c) => (InputKey::Char(c), true, false),
+                Key::Alt(c) => (InputKey::Char(c), false, true),
+                Key::Backspace => (InputKey::Backspace, false, false),
+                Key::Left => (InputKey::Left, false, false),
+                Key::Right => (InputKey::Right, false, false),
+                Key::Up => (InputKey::Up, false, false),
+                Key::Down => (InputKey::Down, false, false),
+                Key::Home => (InputKey::Home, false, false),
+                Key::End => (InputKey::End, false, false),
+                Key::PageUp => (InputKey::PageUp, false, false),
+                Key::PageDown => (InputKey::PageDown, false, false),
+                Key::BackTab => (InputKey::Tab, false, false),
+                Key::Delete => (InputKey::Delete, false, false),
+                Key::Esc => (InputKey::Esc, false, false),
+                Key::F(n) => (InputKey::F(n), false, false),
+                _ => (InputKey::Null, false, false),
+            }
+        }
+        TermionEvent::Mouse(MouseEvent::Press(MouseButton::WheelUp, ..)) => {
+            (InputKey::MouseScrollUp, false, false)
+        }
+        TermionEvent::Mouse(MouseEvent::Press(MouseButton::WheelDown, ..)) => {
+            (InputKey::MouseScrollDown, false, false)
+        }
+        _ => (InputKey::Null, false, false),
+    };
+    Input { key, ctrl, alt }
+}
+
+/// 外部エディタの実行中など、端末の入力を他に譲っている間はtrueにする。
+pub static INPUT_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// 標準入力が読めるようになるまで待つ。タイムアウトしたらfalseを返す。
+fn wait_stdin(timeout_ms: i32) -> bool {
+    let mut fds = libc::pollfd {
+        fd:      libc::STDIN_FILENO,
+        events:  libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fds, 1, timeout_ms) > 0 }
+}
+
+/// 届いている入力をまとめて読み、イベントに変換する。
+/// 内部バッファより大きく読むことで、標準入力のバッファに読み残しを作らない。
+fn read_events(stdin: &io::Stdin) -> Vec<TermionEvent> {
+    let mut buf = [0u8; 16 * 1024];
+    match stdin.lock().read(&mut buf) {
+        Ok(n) if n > 0 => buf[..n].events().filter_map(|evt| evt.ok()).collect(),
+        _ => vec![],
+    }
+}
+
 // send event to event_handler
-pub async fn event_sender() -> Receiver<Command> {
+/// 入力と一定間隔の`Tick`を送る。返した`Sender`で、裏のタスクからも送れる。
+pub async fn event_sender() -> (Sender<Command>, Receiver<Command>) {
     let (tx, rx) = mpsc::channel(10);
     let key_tx = tx.clone();
     tokio::spawn(async move {
@@ -105,52 +198,63 @@ pub async fn event_sender() -> Receiver<Command> {
         let mut scrollup_count = 0;
         let mut scrolldown_count = 0;
         let mut before_click_time = std::time::Instant::now();
-        for evt in stdin.events().map(|evt| evt.unwrap()) {
-            let ev = evt.clone();
-            match ev {
-                TermionEvent::Key(_) => {
-                    // マウスの中ボタンを押したとき、Enterが連続で発生するのを防ぐ
-                    let now = std::time::Instant::now();
-                    if before_click_time.elapsed().as_millis() - now.elapsed().as_millis() > 500 {
-                        if let Err(e) = tx.send(Command::Event(evt)).await {
-                            eprintln!("{}", e);
-                            break;
+        'read: loop {
+            // 一時停止中は読まずに待つ。読んでしまうと外部エディタへの入力を奪ってしまう。
+            if INPUT_PAUSED.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                continue;
+            }
+            if !wait_stdin(100) || INPUT_PAUSED.load(Ordering::SeqCst) {
+                continue;
+            }
+            for evt in read_events(&stdin) {
+                let ev = evt.clone();
+                match ev {
+                    TermionEvent::Key(_) => {
+                        // マウスの中ボタンを押したとき、Enterが連続で発生するのを防ぐ
+                        let now = std::time::Instant::now();
+                        if before_click_time.elapsed().as_millis() - now.elapsed().as_millis() > 500
+                        {
+                            if let Err(e) = tx.send(Command::Event(evt)).await {
+                                eprintln!("{}", e);
+                                break 'read;
+                            }
+                        } else {
+                            before_click_time = std::time::Instant::now();
                         }
-                    } else {
-                        before_click_time = std::time::Instant::now();
                     }
-                }
-                // マウスのuスクロールイベントはTermionではUnsupportedとなっている
-                // TODO! Windows, Macでのo挙動を確認する
-                TermionEvent::Unsupported(s) => {
-                    match s.clone().as_slice() {
-                        [0x1b, 0x4f, 0x42] => {
-                            if scrolldown_count >= 3 {
-                                tx.send(Command::Event(TermionEvent::Key(Key::Down)))
-                                    .await
-                                    .unwrap();
-                                scrolldown_count = 0;
-                            } else {
-                                scrolldown_count += 1;
+                    // マウスのuスクロールイベントはTermionではUnsupportedとなっている
+                    // TODO! Windows, Macでのo挙動を確認する
+                    TermionEvent::Unsupported(s) => {
+                        match s.clone().as_slice() {
+                            [0x1b, 0x4f, 0x42] => {
+                                if scrolldown_count >= 3 {
+                                    tx.send(Command::Event(TermionEvent::Key(Key::Down)))
+                                        .await
+                                        .unwrap();
+                                    scrolldown_count = 0;
+                                } else {
+                                    scrolldown_count += 1;
+                                }
                             }
-                        }
-                        [0x1b, 0x4f, 0x41] => {
-                            if scrollup_count >= 3 {
-                                tx.send(Command::Event(TermionEvent::Key(Key::Up)))
-                                    .await
-                                    .unwrap();
-                                scrollup_count = 0;
-                            } else {
-                                scrollup_count += 1;
+                            [0x1b, 0x4f, 0x41] => {
+                                if scrollup_count >= 3 {
+                                    tx.send(Command::Event(TermionEvent::Key(Key::Up)))
+                                        .await
+                                        .unwrap();
+                                    scrollup_count = 0;
+                                } else {
+                                    scrollup_count += 1;
+                                }
+                            }
+                            a => {
+                                panic!("unsupported {:?}", a);
                             }
-                        }
-                        a => {
-                            panic!("unsupported {:?}", a);
                         }
                     }
-                }
 
-                _ => {}
+                    _ => {}
+                }
             }
         }
     });
@@ -163,7 +267,7 @@ pub async fn event_sender() -> Receiver<Command> {
             let _ = interval.tick().await;
         }
     });
-    rx
+    (tx, rx)
 }
 
 #[derive(Debug, Clone, Display, Serialize, Deserialize)]
@@ -191,3 +295,22 @@ pub enum Order {
     #[display(fmt = "降順")]
     Desc,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_input() {
+        let input = to_input(TermionEvent::Key(Key::Ctrl('s')));
+        assert!(matches!(input.key, InputKey::Char('s')) && input.ctrl && !input.alt);
+        let input = to_input(TermionEvent::Key(Key::Char('\n')));
+        assert!(matches!(input.key, InputKey::Enter));
+        let input = to_input(TermionEvent::Mouse(MouseEvent::Press(
+            MouseButton::WheelDown,
+            1,
+            1,
+        )));
+        assert!(matches!(input.key, InputKey::MouseScrollDown));
+    }
+}
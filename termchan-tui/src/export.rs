@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+use sea_orm::DbConn;
+use termchan_core::{
+    export::{export, ExportFormat, ExportThread},
+    get::thread::ThreadPost,
+};
+
+use crate::{
+    config::dirs::Dir,
+    state::{image::ImageStateItem, post::ThreadPostStateItem, thread::ThreadStateItem},
+};
+
+/// 場所を指定しなかったときの書き出し先 スレのキーをファイル名にする。
+pub fn default_path(thread: &ThreadStateItem, format: ExportFormat) -> Result<PathBuf> {
+    let key = thread
+        .url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    Ok(Dir::get_export_path()?.join(format!("{}.{}", key, format.extension())))
+}
+
+/// 保存したスレを読み込んで、形式に合わせたバイト列にする。
+/// 書き出し先があれば、HTMLとMarkdownの画像をその隣の`<名前>_files`に置いて相対パスで参照する。
+pub async fn export_thread(
+    db: &DbConn,
+    thread: &ThreadStateItem,
+    format: ExportFormat,
+    path: Option<&Path>,
+) -> Result<Vec<u8>> {
+    let posts = ThreadPostStateItem::get_by_thread_id(db, thread.id).await?;
+    let mut export_thread = ExportThread {
+        title:  thread.name.clone(),
+        url:    thread.url.clone(),
+        posts:  posts
+            .into_iter()
+            .map(|post| {
+                ThreadPost {
+                    post_id: post.post_id,
+                    name: post.name,
+                    email: post.email.unwrap_or_default(),
+                    date: post.date,
+                    message: post.message,
+                    index: post.index.max(0) as usize,
+                    ..Default::default()
+                }
+            })
+            .collect(),
+        images: HashMap::new(),
+    };
+    if matches!(format, ExportFormat::Html | ExportFormat::Markdown) {
+        export_thread.images = local_images(db, &export_thread, path).await?;
+    }
+    Ok(export(&export_thread, format))
+}
+
+/// 書き出してファイルに保存する。
+pub async fn write_thread(
+    db: &DbConn,
+    thread: &ThreadStateItem,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<()> {
+    let bytes = export_thread(db, thread, format, Some(path)).await?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// 画像のURLと、書き出し先からの相対パス
+/// 画像は全て書き出し先の隣の`<名前>_files`に置く。保存済みの画像はそこへ複製し、無ければ取得する。
+/// 書き出し先が無いときや取得できなかった画像は、元のURLのまま表示する。
+async fn local_images(
+    db: &DbConn,
+    thread: &ExportThread,
+    path: Option<&Path>,
+) -> Result<HashMap<String, String>> {
+    let mut images = HashMap::new();
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(images),
+    };
+    let urls = thread
+        .posts
+        .iter()
+        .flat_map(|post| post.message.images.iter())
+        .collect::<Vec<_>>();
+    for (i, url) in urls.into_iter().enumerate() {
+        if images.contains_key(url) {
+            continue;
+        }
+        let (save_path, relative) = image_path(url, path, i);
+        if let Some(image) = ImageStateItem::get_saved(db, url).await? {
+            match copy_image(Path::new(&image.save_path), &save_path) {
+                Ok(_) => {
+                    images.insert(url.clone(), relative);
+                }
+                Err(e) => warn!("Failed to copy image {}: {}", url, e),
+            }
+            continue;
+        }
+        match ImageStateItem::download(db, url, &save_path).await {
+            Ok(_) => {
+                images.insert(url.clone(), relative);
+            }
+            Err(e) => warn!("Failed to download image {}: {}", url, e),
+        }
+    }
+    Ok(images)
+}
+
+/// 保存済みの画像を書き出し先の隣へ複製する。既に同じ場所にあれば何もしない。
+fn copy_image(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let same = fs::canonicalize(from)
+        .ok()
+        .is_some_and(|from| fs::canonicalize(to).is_ok_and(|to| from == to));
+    if !same {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// 画像を保存する場所と、書き出し先からの相対パス
+fn image_path(url: &str, path: &Path, i: usize) -> (PathBuf, String) {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let dir_name = format!("{}_files", stem);
+    let file_name = image_file_name(url, i);
+    (
+        path.with_file_name(&dir_name).join(&file_name),
+        format!("{}/{}", dir_name, file_name),
+    )
+}
+
+/// 同じ名前の画像が重ならないよう、何枚目かを頭に付ける。
+pub fn image_file_name(url: &str, i: usize) -> String {
+    format!(
+        "{}_{}",
+        i,
+        url.rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .replace(['?', '#', ':'], "_")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("images").join("a.png");
+        fs::create_dir_all(saved.parent().unwrap()).unwrap();
+        fs::write(&saved, b"png").unwrap();
+
+        let (save_path, relative) =
+            image_path("https://example.com/a.png", &dir.path().join("t.html"), 0);
+        assert_eq!(relative, "t_files/0_a.png");
+        copy_image(&saved, &save_path).unwrap();
+        assert_eq!(fs::read(dir.path().join(&relative)).unwrap(), b"png");
+
+        // 既に書き出し先にあるときは、そのまま使う。
+        copy_image(&save_path, &save_path).unwrap();
+        assert_eq!(fs::read(&save_path).unwrap(), b"png");
+    }
+}
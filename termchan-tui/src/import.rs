@@ -0,0 +1,328 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{bail, eyre, Result, WrapErr};
+use sea_orm::DbConn;
+use termchan_core::{
+    get::thread::parse_dat,
+    url::{resolve::resolve, thread::BoardParams},
+    util::encoding::sjis_to_utf8,
+};
+
+use crate::state::{board::BoardStateItem, thread::ThreadStateItem};
+
+/// 取り込んだ結果
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub threads: usize,
+    pub posts:   usize,
+    /// 取り込めなかったファイルと理由
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// `.idx`から読んだスレの情報
+/// ブラウザによって形式が違うので、分かる項目だけを拾う。
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IdxInfo {
+    pub title: Option<String>,
+    pub url:   Option<String>,
+    /// 何レス目まで読んだか
+    pub read:  Option<i32>,
+}
+
+/// 既読の位置として読む項目 ブラウザによって名前が違う
+const READ_KEYS: &[&str] = &["readpos", "lastread", "newread", "kokomade", "read"];
+
+/// `.idx`を読む。
+/// JaneStyleのように`<>`で区切った1行のレコードと、`名前=値`の行の形式がある。
+pub fn parse_idx(text: &str) -> IdxInfo {
+    let mut info = IdxInfo::default();
+    for line in text.lines() {
+        if line.contains("<>") {
+            parse_idx_record(line, &mut info);
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+            None => continue,
+        };
+        if value.is_empty() {
+            continue;
+        }
+        match key.as_str() {
+            "title" | "subject" => info.title = Some(value.to_string()),
+            "url" | "threadurl" => info.url = Some(value.to_string()),
+            key if READ_KEYS.contains(&key) => {
+                if let Ok(read) = value.parse::<i32>() {
+                    info.read = info.read.max(Some(read));
+                }
+            }
+            _ => {}
+        }
+    }
+    info
+}
+
+/// `<>`で区切ったレコードから、スレタイとURLを拾う。
+/// 項目の並びはバージョンで変わるので、数値の項目は既読の位置として使わない。
+fn parse_idx_record(line: &str, info: &mut IdxInfo) {
+    for field in line.split("<>").map(str::trim).filter(|f| !f.is_empty()) {
+        if field.starts_with("http://") || field.starts_with("https://") {
+            info.url.get_or_insert_with(|| field.to_string());
+        } else if !field
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_punctuation() || c == ' ')
+        {
+            info.title.get_or_insert_with(|| field.to_string());
+        }
+    }
+}
+
+/// フォルダの下にある`.dat`を全て取り込む。
+/// 板はフォルダの名前から探し、見つからなければ`host`の板として登録する。
+/// 同じスレを取り込み直しても、レスは重複しない。
+pub async fn import_dir(db: &DbConn, dir: &Path, host: Option<&str>) -> Result<ImportSummary> {
+    if !dir.is_dir() {
+        bail!("フォルダではありません。: {}", dir.display());
+    }
+    let mut summary = ImportSummary::default();
+    let mut boards: HashMap<PathBuf, BoardStateItem> = HashMap::new();
+    let mut files = vec![];
+    find_dat_files(dir, &mut files, &mut summary.skipped);
+    files.sort();
+    for path in files {
+        match import_dat(db, &path, host, &mut boards).await {
+            Ok(posts) => {
+                summary.threads += 1;
+                summary.posts += posts;
+            }
+            Err(e) => summary.skipped.push((path, e.to_string())),
+        }
+    }
+    Ok(summary)
+}
+
+/// `.dat`を探す。読めないフォルダは`skipped`に入れて、残りを探し続ける。
+/// リンクしたフォルダは、自分を指していると終わらないので辿らない。
+fn find_dat_files(dir: &Path, files: &mut Vec<PathBuf>, skipped: &mut Vec<(PathBuf, String)>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            skipped.push((dir.to_path_buf(), e.to_string()));
+            return;
+        }
+    };
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                skipped.push((dir.to_path_buf(), e.to_string()));
+                continue;
+            }
+        };
+        let is_dir = fs::symlink_metadata(&path).is_ok_and(|meta| meta.is_dir());
+        if is_dir {
+            find_dat_files(&path, files, skipped);
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("dat"))
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// 一つの`.dat`を取り込み、保存したレスの数を返す。
+async fn import_dat(
+    db: &DbConn,
+    path: &Path,
+    host: Option<&str>,
+    boards: &mut HashMap<PathBuf, BoardStateItem>,
+) -> Result<usize> {
+    let key = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .filter(|key| !key.is_empty() && key.chars().all(|c| c.is_ascii_digit()))
+        .ok_or_else(|| eyre!("ファイル名がスレのキーではありません。"))?;
+    // 壊れたDATなら、板を登録する前にやめる。
+    let dat = normalize(&decode(&fs::read(path)?))?;
+    let idx = fs::read(path.with_extension("idx"))
+        .map(|bytes| parse_idx(&decode(&bytes)))
+        .unwrap_or_default();
+
+    let board = match idx.url.as_deref().and_then(resolve) {
+        Some((params, _)) => BoardStateItem::find_or_register(db, &params).await?,
+        None => {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            match boards.get(dir) {
+                Some(board) => board.clone(),
+                None => {
+                    let board = board_of_dir(db, dir, host).await?;
+                    boards.insert(dir.to_path_buf(), board.clone());
+                    board
+                }
+            }
+        }
+    };
+    let params = BoardParams::from(board.url.as_str());
+    let url = format!(
+        "{}://{}/test/read.cgi/{}/{}",
+        params.scheme, params.host, params.board_key, key
+    );
+    let dat_url = format!(
+        "{}://{}/{}/dat/{}.dat",
+        params.scheme, params.host, params.board_key, key
+    );
+
+    let mut res = parse_dat(&dat, &dat_url).wrap_err("本文を読み込めませんでした。")?;
+    if res.detail.title.is_empty() {
+        res.detail.title = idx.title.clone().unwrap_or_default();
+    }
+    let count = res.posts.len();
+    let thread = ThreadStateItem::register(db, board.id, &url).await?;
+    thread.save_response(db, res).await?;
+    ThreadStateItem::update_imported(db, thread.id, count as i32, idx.read).await?;
+    Ok(count)
+}
+
+/// ログのフォルダの名前から板を探す。
+/// 同じ名前の板が複数あるときは、`host`のサーバーの板に絞る。
+async fn board_of_dir(db: &DbConn, dir: &Path, host: Option<&str>) -> Result<BoardStateItem> {
+    let name = dir
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut boards = BoardStateItem::find_by_name_or_key(db, &name).await?;
+    if let Some(host) = host {
+        boards.retain(|board| BoardParams::from(board.url.as_str()).host == host);
+    }
+    match (boards.len(), host) {
+        (1, _) => Ok(boards.remove(0)),
+        (0, Some(host)) if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            let params = BoardParams::from(format!("https://{}/{}/", host, name).as_str());
+            BoardStateItem::find_or_register(db, &params).await
+        }
+        (0, _) => {
+            bail!(
+                "板が分かりません。--host でサーバーを指定してください。: {}",
+                name
+            )
+        }
+        _ => {
+            bail!(
+                "同じ名前の板が複数あります。--host でサーバーを指定してください。: {}",
+                name
+            )
+        }
+    }
+}
+
+/// 古いブラウザのログはShift_JIS、新しいものはUTF-8で保存されている。
+fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => sjis_to_utf8(bytes),
+    }
+}
+
+/// 改行をそろえる。壊れたDATで板を登録しないよう、区切りの数を先に確かめる。
+fn normalize(dat: &str) -> Result<String> {
+    let dat = dat.trim_end_matches(['\r', '\n']).replace("\r\n", "\n");
+    if dat.is_empty() {
+        bail!("空のDATです。");
+    }
+    for (i, line) in dat.lines().enumerate() {
+        let fields = line.split("<>").count();
+        if fields < 4 || (i == 0 && fields < 5) {
+            bail!("{}行目の形式が正しくありません。", i + 1);
+        }
+    }
+    Ok(dat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::connect::temp_db;
+
+    #[test]
+    fn test_parse_idx() {
+        let info = parse_idx("[Setting]\nTitle=スレタイ\nReadPos=120\nSize=4096\nURL=\n");
+        assert_eq!(
+            info,
+            IdxInfo {
+                title: Some("スレタイ".to_string()),
+                url:   None,
+                read:  Some(120),
+            }
+        );
+        assert_eq!(parse_idx(""), IdxInfo::default());
+
+        // JaneStyleの形式 1行のレコードを`<>`で区切る。
+        let info = parse_idx(
+            "【速報】スレタイ Part2<>1658812729<>120<>45678<>2022/07/26 14:18:49<><>0<>https://egg.5ch.net/test/read.cgi/news/1658812729/\r\n",
+        );
+        assert_eq!(
+            info,
+            IdxInfo {
+                title: Some("【速報】スレタイ Part2".to_string()),
+                url:   Some("https://egg.5ch.net/test/read.cgi/news/1658812729/".to_string()),
+                read:  None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_dat_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let board = dir.path().join("news");
+        fs::create_dir(&board).unwrap();
+        fs::write(board.join("1658812729.dat"), "").unwrap();
+        fs::write(board.join("1658812729.idx"), "").unwrap();
+        // 親を指すリンクがあっても、探し終わる。
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), board.join("loop")).unwrap();
+
+        let mut files = vec![];
+        let mut skipped = vec![];
+        find_dat_files(dir.path(), &mut files, &mut skipped);
+        assert_eq!(files, vec![board.join("1658812729.dat")]);
+        assert!(skipped.is_empty());
+
+        // 読めないフォルダは飛ばして、理由を残す。
+        let missing = dir.path().join("missing");
+        find_dat_files(&missing, &mut files, &mut skipped);
+        assert_eq!(files.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, missing);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let dat =
+            normalize("名無し<>sage<>2022/07/26(火) 14:18:49 ID:a<>本文<>タイトル\r\n").unwrap();
+        let res = parse_dat(&dat, "https://egg.example.net/news/dat/1.dat").unwrap();
+        assert_eq!(res.detail.title, "タイトル");
+        assert!(normalize("名無し<>sage<>本文\n").is_err());
+        assert!(normalize("").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_board_of_dir() {
+        let (dir, db) = temp_db().await;
+        for url in ["https://egg.5ch.net/news/", "https://mi.5ch.net/news/"] {
+            BoardStateItem::find_or_register(&db, &BoardParams::from(url))
+                .await
+                .unwrap();
+        }
+
+        // 別のサーバーに同じキーの板があるときは、サーバーを指定させる。
+        let logs = dir.path().join("logs").join("news");
+        assert!(board_of_dir(&db, &logs, None).await.is_err());
+        let board = board_of_dir(&db, &logs, Some("mi.5ch.net")).await.unwrap();
+        assert_eq!(board.url, "https://mi.5ch.net/news/");
+    }
+}
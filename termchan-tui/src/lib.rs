@@ -1,12 +1,19 @@
 #[macro_use] extern crate log;
 
 mod application;
+mod archive;
+pub mod cli;
+mod command;
 mod config;
 mod database;
+mod editor;
 mod event;
+mod export;
+mod import;
 mod renderer;
 pub mod run;
 mod state;
 mod style;
+pub mod subcommand;
 mod ui;
 mod words;
@@ -1,10 +1,23 @@
-use std::io::Write;
+use std::{
+    io::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use anyhow::Result;
-use termion::screen::AlternateScreen;
+use termion::{
+    cursor,
+    screen::{AlternateScreen, ToAlternateScreen, ToMainScreen},
+};
 use tui::{backend::TermionBackend, Terminal};
 
 use crate::{application::App, ui};
+
+/// 画面の外で端末を使ったので、次の描画で全体を描き直す。
+static CLEAR_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn request_clear() {
+    CLEAR_REQUESTED.store(true, Ordering::SeqCst);
+}
 pub struct Renderer<W: Write> {
     terminal: Terminal<TermionBackend<AlternateScreen<W>>>,
 }
@@ -16,7 +29,25 @@ impl<W: Write> Renderer<W> {
         Ok(Renderer { terminal })
     }
     pub fn render(&mut self, app: &mut App) -> Result<()> {
-        self.terminal.draw(|mut f| ui::draw(&mut f, app))?;
+        if CLEAR_REQUESTED.swap(false, Ordering::SeqCst) {
+            self.terminal.clear()?;
+        }
+        draw_app(&mut self.terminal, app)
+    }
+    /// 外部のプログラムに端末を渡すため、通常の画面に戻す。
+    /// rawモードはそのままなので、呼び出すプログラム側で端末の設定を行う必要がある。
+    pub fn suspend(&mut self) -> Result<()> {
+        let backend = self.terminal.backend_mut();
+        write!(backend, "{}{}", ToMainScreen, cursor::Show)?;
+        backend.flush()?;
+        Ok(())
+    }
+    /// suspendから戻り、画面全体を描き直す。
+    pub fn resume(&mut self) -> Result<()> {
+        let backend = self.terminal.backend_mut();
+        write!(backend, "{}{}", ToAlternateScreen, cursor::Hide)?;
+        backend.flush()?;
+        self.terminal.clear()?;
         Ok(())
     }
     pub fn exit(&mut self) -> Result<()> {
@@ -26,6 +57,15 @@ impl<W: Write> Renderer<W> {
     }
 }
 
+/// 描画で状態が変わらないよう複製したAppで描き、描いたリストの高さだけを戻す。
+/// ページ単位の移動は、この高さを使う。
+pub fn draw_app<B: tui::backend::Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+    let mut drawn = app.clone();
+    terminal.draw(|f| ui::draw(f, &mut drawn))?;
+    app.keep_list_heights(&drawn);
+    Ok(())
+}
+
 impl<W: Write> Drop for Renderer<W> {
     fn drop(&mut self) {
         self.terminal.show_cursor().expect("Failed to show cursor");
@@ -35,3 +75,34 @@ impl<W: Write> Drop for Renderer<W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tui::backend::TestBackend;
+
+    use super::*;
+    use crate::{
+        config::config::Config,
+        database::connect::temp_db,
+        state::{tab::LeftTabItem, thread::ThreadStateItem},
+        ui::stateful_list::Motion,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_draw_app_keeps_list_height() {
+        let (_dir, db) = temp_db().await;
+        let mut app = App::new(db, Config::default());
+        app.board.set_items(vec![ThreadStateItem::default(); 100]);
+        app.left_tabs
+            .history_add(LeftTabItem::Board("test".to_string()));
+        app.left_tabs.next();
+
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        draw_app(&mut terminal, &mut app).unwrap();
+
+        let height = app.board.state.height;
+        assert!(height > 1);
+        app.board.motion(Motion::PageDown(1));
+        assert_eq!(app.board.selected(), height);
+    }
+}
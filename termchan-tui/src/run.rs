@@ -1,114 +1,317 @@
-use std::{error::Error, io, process};
+use std::{
+    error::Error,
+    io::{self, Write},
+    process,
+    sync::atomic::Ordering,
+};
 
-use termion::raw::{IntoRawMode, RawTerminal};
-use tui_textarea::Input;
+use eyre::eyre;
+use log::LevelFilter;
+use termion::{
+    cursor,
+    input::TermRead,
+    raw::{IntoRawMode, RawTerminal},
+    screen::{ToAlternateScreen, ToMainScreen},
+};
+use tui_textarea::Key;
 
 use crate::{
     application::App,
-    config::cache::CacheState,
-    ctrl,
-    database::logger::init_log,
-    event::{event_sender, Command, Event},
-    key,
-    renderer::Renderer,
-    state::layout::Pane,
+    cli::Args,
+    config::{
+        cache::CacheState,
+        config::Config,
+        dirs::Dir,
+        keymap::{Action, KeyMatch, Mode},
+        secret_store,
+        watcher::ConfigWatcher,
+    },
+    database::{
+        connect::{establish_connection, resolve_db_path},
+        logger::init_log,
+    },
+    event::{event_sender, to_input, Command, Event, INPUT_PAUSED},
+    renderer::{self, Renderer},
+    state::{
+        home::HomeStateItem,
+        layout::Pane,
+        prompt::{Prompt, PromptKind},
+    },
+    ui::stateful_list::Motion,
 };
 
-pub async fn run() -> Result<(), Box<dyn Error>> {
+/// ログと設定ファイルの場所を引数に合わせる。画面もサブコマンドも最初に呼ぶ。
+pub fn prepare(args: &Args) -> Result<(), Box<dyn Error>> {
+    match args.log_level {
+        Some(level) => init_log(Dir::get_log_path()?.display().to_string(), level)?,
+        #[cfg(debug_assertions)]
+        None => init_log("./termchan-tui.log".to_string(), LevelFilter::Info)?,
+        #[cfg(not(debug_assertions))]
+        None => {}
+    }
+    if let Some(path) = args.config_path.clone() {
+        Dir::set_config_path(path);
+    }
+    Ok(())
+}
+
+/// 暗号化した認証情報のファイルを初めて開くときに、パスフレーズを尋ねる。
+/// エディタを開くときと同じく、描画と入力の読み込みを止めて通常の画面で読む。
+fn prompt_passphrase(message: &str) -> eyre::Result<String> {
+    INPUT_PAUSED.store(true, Ordering::SeqCst);
+    let read = || -> eyre::Result<Option<String>> {
+        let mut stdout = io::stdout();
+        write!(stdout, "{}{}\r\n{}", ToMainScreen, cursor::Show, message)?;
+        stdout.flush()?;
+        let passphrase = io::stdin().read_passwd(&mut stdout)?;
+        write!(stdout, "\r\n{}{}", ToAlternateScreen, cursor::Hide)?;
+        stdout.flush()?;
+        Ok(passphrase)
+    };
+    let passphrase = read();
+    renderer::request_clear();
+    INPUT_PAUSED.store(false, Ordering::SeqCst);
+    passphrase?.ok_or_else(|| eyre!("パスフレーズの入力を取り消しました。"))
+}
+
+pub async fn run(args: Args) -> Result<(), Box<dyn Error>> {
     // setup terminal
-    #[cfg(debug_assertions)]
-    init_log("./termchan-tui.log".to_string())?;
+    prepare(&args)?;
+
+    // コネクションプールは起動時に一度だけ確立し、以降は使い回す。
+    // 設定ファイルに誤りがあれば、端末を切り替える前に行番号付きで知らせて終了する。
+    let config = Config::load_config()?;
+    let mut watcher = ConfigWatcher::new(Config::layers(&Dir::get_config_path()?));
+    let db_path = resolve_db_path(args.db_path, &config)?;
+    let db = establish_connection(&db_path).await?;
+
+    secret_store::init(config.credential_backend)?;
 
     let mut render = Renderer::new(RawTerminal::from(io::stdout().into_raw_mode()?))?;
+    secret_store::set_prompt(prompt_passphrase);
     info!("Renderer initialized");
 
     let cache = CacheState::get();
     let mut app = match cache {
-        Some(app) => app,
-        None => App::new(),
+        Some(mut app) => {
+            app.db = db;
+            // 追加された項目が反映されるように、Homeはキャッシュから復元しない。
+            app.home.set_items(HomeStateItem::all());
+            // 設定はキャッシュではなく、設定ファイルのものを使う。
+            app.reload_config(config).await?;
+            app
+        }
+        None => App::new(db, config),
     };
 
+    app.clean_database(false).await?;
+    if app.config.prune_days > 0 {
+        let days = app.config.prune_days;
+        app.prune(days).await?;
+    }
+
+    // 前回送れなかった書き込みがあれば、起動したときに送る。
+    if args.offline {
+        app.set_offline().await?;
+    } else {
+        app.send_queued_posts().await?;
+    }
+
     app.update(Event::Down).await?;
     app.update(Event::Up).await?;
     info!("State initialized");
 
+    if let Some(url) = &args.url {
+        if let Err(e) = app.open_url(url).await {
+            app.update_message(e.to_string());
+        }
+    }
+
+    // 続けて押すキーの途中
+    let mut pending = vec![];
+    let mut pending_mode = Mode::Normal;
+    // `10j`のように操作の前に入力した回数
+    let mut count: Option<usize> = None;
+
     'main: loop {
-        let _ = render.render(&mut app.clone());
-        let mut rx = event_sender().await;
+        let _ = render.render(&mut app);
+        let (tx, mut rx) = event_sender().await;
 
         while let Some(message) = rx.recv().await {
-            use tui_textarea::Key::*;
-            if !app.input_mode && !app.layout.visible_popup {
-                match message {
-                    Command::Event(evt) => {
-                        match evt.into() {
-                            ctrl!(Char('q')) => {
-                                let _ = CacheState::set(app.clone());
-                                render.exit()?;
-                                break 'main;
-                            }
-                            key!(Char('j')) | key!(Down) => app.update(Event::Down).await?,
-                            key!(Char('k')) | key!(Up) => app.update(Event::Up).await?,
-                            key!(Char('h')) | key!(Left) => app.update(Event::Left).await?,
-                            key!(Char('l')) | key!(Right) => app.update(Event::Right).await?,
-                            key!(Char('\t')) => app.update(Event::Tab).await?,
-                            key!(Char('r')) => {
-                                app.update(Event::Get).await?;
-                                match app.layout.focus_pane {
-                                    Pane::Main => app.update(Event::ScrollToBottom).await?,
-                                    Pane::Side => app.update(Event::ScrollToTop).await?,
-                                    _ => (),
-                                }
-                            }
-                            ctrl!(Char('f')) => app.update(Event::ToggleFilter).await?,
-                            key!(Char('f')) => app.update(Event::ToggleBookmark).await?,
-                            key!(Enter) => {
-                                app.update(Event::Enter).await?;
-                                app.update(Event::ScrollToTop).await?;
+            let evt = match message {
+                Command::Event(evt) => evt,
+                Command::Tick => {
+                    if watcher.changed() {
+                        match Config::load_config() {
+                            Ok(config) => {
+                                app.reload_config(config).await?;
+                                app.update_message(
+                                    "設定ファイルを読み込み直しました。".to_string(),
+                                );
                             }
-                            key!(Backspace) => app.update(Event::RemoveHistory).await?,
-                            key!(Esc) => app.update(Event::ClosePopup).await?,
-                            _ => (),
+                            Err(e) => app.update_message(e.to_string()),
                         }
-
-                        let _ = CacheState::set(app.clone());
-                        let _ = render.render(&mut app.clone());
+                        let _ = render.render(&mut app);
+                    }
+                    if app.offline.should_check() {
+                        app.check_connection().await?;
+                        let _ = render.render(&mut app);
+                    }
+                    app.snapshot_archives(&tx);
+                    continue;
+                }
+                Command::Snapshot(progress) => {
+                    if let Err(e) = app.on_snapshot(progress).await {
+                        app.update_message(e.to_string());
                     }
+                    let _ = render.render(&mut app);
+                    continue;
+                }
+                _ => continue,
+            };
+            let input = to_input(evt);
+
+            // キーの一覧は、どのキーでも閉じる。
+            if app.show_help {
+                app.show_help = false;
+                let _ = render.render(&mut app);
+                continue;
+            }
 
-                    Command::Tick => {}
-                    _ => {}
+            // `:`や`/`の入力中は、キーをすべて入力に使う。
+            if app.prompt.is_some() {
+                app.input_prompt(input).await?;
+                if app.should_quit {
+                    let _ = app.save_read_position().await;
+                    let _ = CacheState::set(app.clone());
+                    render.exit()?;
+                    break 'main;
                 }
-            } else if app.layout.visible_popup && !app.input_mode {
-                match message {
-                    Command::Event(evt) => {
-                        match evt.into() {
-                            key!(Esc) => app.update(Event::ClosePopup).await?,
-                            key!(Char('\t')) => app.update(Event::ToggleTextArea).await?,
-                            ctrl!(Char('s')) => app.update(Event::Post).await?,
-                            key!(Enter) => app.update(Event::EnableInputMode).await?,
-                            _ => (),
-                        }
+                let _ = render.render(&mut app);
+                continue;
+            }
 
-                        let _ = CacheState::set(app.clone());
-                        let _ = render.render(&mut app.clone());
+            let mode = if app.input_mode {
+                Mode::Input
+            } else if app.layout.visible_popup {
+                Mode::Popup
+            } else {
+                Mode::Normal
+            };
+            if mode != pending_mode {
+                pending.clear();
+                count = None;
+                pending_mode = mode;
+            }
+            if mode == Mode::Normal && pending.is_empty() && !input.ctrl && !input.alt {
+                if let Key::Char(c @ '0'..='9') = input.key {
+                    // 先頭の0は回数にしない。
+                    if c != '0' || count.is_some() {
+                        let digit = c.to_digit(10).unwrap_or_default() as usize;
+                        count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                        continue;
                     }
-                    Command::Tick => {}
-                    _ => {}
                 }
-            } else {
-                match message {
-                    Command::Event(event) => {
-                        match event.into() {
-                            key!(Esc) => app.update(Event::DisableInputMode).await?,
-                            ctrl!(Char('x')) => app.update(Event::ToggleTextArea).await?,
-                            input => app.update(Event::Input(input)).await?,
-                        }
-                        let _ = render.render(&mut app.clone());
+            }
+            let action = match app.keymap.feed(mode, &mut pending, &input) {
+                KeyMatch::Action(action) => action,
+                KeyMatch::Pending => continue,
+                KeyMatch::None => {
+                    count = None;
+                    // 入力モードでは割り当ての無いキーをそのまま入力する。
+                    if mode == Mode::Input {
+                        app.update(Event::Input(input)).await?;
+                        let _ = render.render(&mut app);
                     }
-                    Command::Tick => {}
-                    _ => {}
+                    continue;
+                }
+            };
+
+            let given = count.take();
+            let n = given.unwrap_or(1);
+            match action {
+                Action::Quit => {
+                    let _ = app.save_read_position().await;
+                    let _ = CacheState::set(app.clone());
+                    render.exit()?;
+                    break 'main;
                 }
+                Action::Down => app.update(Event::Move(Motion::Down(n))).await?,
+                Action::Up => app.update(Event::Move(Motion::Up(n))).await?,
+                Action::Left => app.update(Event::Left).await?,
+                Action::Right => app.update(Event::Right).await?,
+                Action::Tab => app.update(Event::Tab).await?,
+                Action::Reload => {
+                    app.update(Event::Get).await?;
+                    // スレッドは新着レスの先頭に移動済み。
+                    if app.layout.focus_pane == Pane::Side {
+                        app.update(Event::ScrollToTop).await?;
+                    }
+                }
+                Action::ToggleFilter => app.update(Event::ToggleFilter).await?,
+                Action::ToggleBookmark => app.update(Event::ToggleBookmark).await?,
+                Action::Enter => {
+                    app.update(Event::Enter).await?;
+                    // スレッドを開いたときは未読位置を保ったままにする。
+                    if app.layout.focus_pane == Pane::Side {
+                        app.update(Event::ScrollToTop).await?;
+                    }
+                }
+                Action::OpenPostForm => app.update(Event::OpenPostForm).await?,
+                Action::RetryPost => app.update(Event::RetryPost).await?,
+                Action::Delete => app.update(Event::Delete).await?,
+                Action::ToggleMark => app.update(Event::ToggleMark).await?,
+                Action::ToggleVisual => app.update(Event::ToggleVisual).await?,
+                Action::QuoteReply => app.update(Event::QuoteReply(false)).await?,
+                Action::QuoteReplyWithText => app.update(Event::QuoteReply(true)).await?,
+                Action::RemoveHistory => app.update(Event::RemoveHistory).await?,
+                Action::ClosePopup => app.update(Event::ClosePopup).await?,
+                // `10G`のように回数があれば、その番号へ移動する。
+                Action::ScrollToTop | Action::ScrollToBottom if given.is_some() => {
+                    app.update(Event::Jump(n)).await?
+                }
+                Action::ScrollToTop => app.update(Event::ScrollToTop).await?,
+                Action::ScrollToBottom => app.update(Event::ScrollToBottom).await?,
+                Action::PageDown => app.update(Event::Move(Motion::PageDown(n))).await?,
+                Action::PageUp => app.update(Event::Move(Motion::PageUp(n))).await?,
+                Action::HalfPageDown => app.update(Event::Move(Motion::HalfPageDown(n))).await?,
+                Action::HalfPageUp => app.update(Event::Move(Motion::HalfPageUp(n))).await?,
+                Action::Command if given.is_some() => app.update(Event::Jump(n)).await?,
+                Action::Command => app.prompt = Some(Prompt::new(PromptKind::Command)),
+                Action::Search => app.prompt = Some(Prompt::new(PromptKind::Search)),
+                Action::NextHit => {
+                    for _ in 0..n {
+                        app.update(Event::NextHit(true)).await?;
+                    }
+                }
+                Action::PrevHit => {
+                    for _ in 0..n {
+                        app.update(Event::NextHit(false)).await?;
+                    }
+                }
+                Action::Help => app.show_help = true,
+                Action::ToggleTextArea => app.update(Event::ToggleTextArea).await?,
+                Action::Post => app.update(Event::Post).await?,
+                Action::NextIdentity => app.update(Event::NextIdentity).await?,
+                Action::NextAccount => app.update(Event::NextAccount).await?,
+                Action::OpenEditor => {
+                    // エディタの終了まで描画と入力の読み込みを止める。
+                    INPUT_PAUSED.store(true, Ordering::SeqCst);
+                    render.suspend()?;
+                    let res = app.edit_form_in_editor();
+                    render.resume()?;
+                    INPUT_PAUSED.store(false, Ordering::SeqCst);
+                    res?;
+                }
+                Action::EnableInputMode => app.update(Event::EnableInputMode).await?,
+                Action::DisableInputMode => app.update(Event::DisableInputMode).await?,
+                Action::None => {}
+            }
+
+            if mode != Mode::Input {
+                let _ = CacheState::set(app.clone());
             }
+            let _ = render.render(&mut app);
         }
     }
 
@@ -1,9 +1,21 @@
+pub mod account;
+pub mod archive;
 pub mod bbsmenu;
 pub mod board;
 pub mod bookmark;
 pub mod categories;
+pub mod draft;
+pub mod history;
 pub mod home;
+pub mod identity;
+pub mod image;
 pub mod layout;
+pub mod my_post;
+pub mod notification;
+pub mod offline;
 pub mod post;
+pub mod post_queue;
+pub mod prompt;
+pub mod setting;
 pub mod tab;
 pub mod thread;
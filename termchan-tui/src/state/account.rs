@@ -0,0 +1,160 @@
+use entity::{account, board_account, prelude::*};
+use eyre::Result;
+use migration::OnConflict;
+use sea_orm::{sea_query::Expr, ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+
+use crate::config::credentials;
+
+/// 5chのアカウント
+/// メールアドレスとパスワードはキーリングにあり、キャッシュには書き出さない。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountStateItem {
+    pub id:         i32,
+    pub label:      String,
+    /// 板ごとに選んでいないときに使うか
+    pub is_default: bool,
+    #[serde(skip)]
+    pub email:      Option<String>,
+}
+
+impl From<account::Model> for AccountStateItem {
+    fn from(m: account::Model) -> Self {
+        AccountStateItem {
+            id:         m.id,
+            label:      m.label,
+            is_default: m.is_default,
+            email:      credentials::Account::load(m.id).email().map(String::from),
+        }
+    }
+}
+
+impl AccountStateItem {
+    /// アカウントの一覧を取得する。
+    /// まだ一つも無ければ、以前の一つだけのアカウントを既定のアカウントとして取り込む。
+    pub async fn get_all(db: &DbConn) -> Result<Vec<AccountStateItem>> {
+        let accounts = account::Entity::find()
+            .order_by_asc(account::Column::Id)
+            .all(db)
+            .await?;
+        if accounts.is_empty() {
+            let legacy = credentials::Account::new();
+            if legacy.has_credentials() {
+                let model = account::ActiveModel {
+                    label: Set("5ch".to_string()),
+                    is_default: Set(true),
+                    ..Default::default()
+                };
+                let id = Account::insert(model).exec(db).await?.last_insert_id;
+                legacy.move_to(id)?;
+                let account = account::Entity::find_by_id(id).one(db).await?;
+                return Ok(account.into_iter().map(Into::into).collect());
+            }
+        }
+        Ok(accounts.into_iter().map(Into::into).collect())
+    }
+
+    /// 板で選んでいるアカウントを取得する。選んでいなければNone
+    pub async fn get_by_board(db: &DbConn, board_url: &str) -> Result<Option<AccountStateItem>> {
+        let account = board_account::Entity::find()
+            .filter(board_account::Column::BoardUrl.eq(board_url))
+            .find_also_related(account::Entity)
+            .one(db)
+            .await?;
+        Ok(account.and_then(|(_, a)| a).map(Into::into))
+    }
+
+    /// 板で書き込むときに使うアカウント
+    /// 板で選んでいなければ既定のアカウントを使う。
+    pub async fn get_for_board(db: &DbConn, board_url: &str) -> Result<Option<AccountStateItem>> {
+        if let Some(account) = Self::get_by_board(db, board_url).await? {
+            return Ok(Some(account));
+        }
+        let account = account::Entity::find()
+            .filter(account::Column::IsDefault.eq(true))
+            .one(db)
+            .await?;
+        Ok(account.map(Into::into))
+    }
+
+    /// 板で使うアカウントを設定する。Noneなら既定のアカウントに戻す。
+    pub async fn set_board(db: &DbConn, board_url: &str, account_id: Option<i32>) -> Result<()> {
+        let account_id = match account_id {
+            Some(id) => id,
+            None => {
+                board_account::Entity::delete_many()
+                    .filter(board_account::Column::BoardUrl.eq(board_url))
+                    .exec(db)
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let model = board_account::ActiveModel {
+            board_url: Set(board_url.to_string()),
+            account_id: Set(account_id),
+            ..Default::default()
+        };
+        BoardAccount::insert(model)
+            .on_conflict(
+                OnConflict::column(board_account::Column::BoardUrl)
+                    .update_column(board_account::Column::AccountId)
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// 既定のアカウントを切り替える。既に既定なら、既定のアカウントを無くす。
+    pub async fn toggle_default(db: &DbConn, id: i32) -> Result<bool> {
+        let account = account::Entity::find_by_id(id).one(db).await?;
+        let is_default = !account.is_some_and(|a| a.is_default);
+        account::Entity::update_many()
+            .col_expr(account::Column::IsDefault, Expr::value(false))
+            .exec(db)
+            .await?;
+        if is_default {
+            account::Entity::update(account::ActiveModel {
+                id: Set(id),
+                is_default: Set(true),
+                ..Default::default()
+            })
+            .exec(db)
+            .await?;
+        }
+        Ok(is_default)
+    }
+
+    /// アカウントを保存する。idが0なら追加する。
+    /// パスワードがNoneなら変更しない。
+    pub async fn save(&self, db: &DbConn, email: &str, password: Option<&str>) -> Result<()> {
+        let id = if self.id == 0 {
+            let model = account::ActiveModel {
+                label: Set(self.label.clone()),
+                is_default: Set(false),
+                ..Default::default()
+            };
+            Account::insert(model).exec(db).await?.last_insert_id
+        } else {
+            account::Entity::update(account::ActiveModel {
+                id: Set(self.id),
+                label: Set(self.label.clone()),
+                ..Default::default()
+            })
+            .exec(db)
+            .await?;
+            self.id
+        };
+        credentials::Account::save(id, email, password)
+    }
+
+    pub async fn delete(db: &DbConn, id: i32) -> Result<()> {
+        board_account::Entity::delete_many()
+            .filter(board_account::Column::AccountId.eq(id))
+            .exec(db)
+            .await?;
+        account::Entity::delete_by_id(id).exec(db).await?;
+        credentials::Account::delete(id)
+    }
+}
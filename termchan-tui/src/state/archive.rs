@@ -0,0 +1,117 @@
+use std::{collections::HashMap, fmt::Display};
+
+use entity::board;
+use eyre::Result;
+use sea_orm::{DbConn, EntityTrait};
+use serde::{Deserialize, Serialize};
+
+use super::thread::ThreadStateItem;
+
+/// アーカイブの一覧に並べるスレと、保存の状況
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveStateItem {
+    pub thread:      ThreadStateItem,
+    pub board_name:  String,
+    /// 保存しているレスの数
+    pub saved:       usize,
+    /// 最後にレスと画像を全て保存した時刻 0ならまだ保存していない
+    pub archived_at: i64,
+}
+
+/// アーカイブしたスレの保存の状況
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveStatus {
+    /// まだ一度も保存していない
+    Waiting,
+    /// 保存してから新しいレスが付いた
+    Updated,
+    Saved,
+    /// dat落ちしたので、これ以上は増えない
+    Finished,
+}
+
+impl Display for ArchiveStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArchiveStatus::Waiting => write!(f, "保存待ち"),
+            ArchiveStatus::Updated => write!(f, "新着あり"),
+            ArchiveStatus::Saved => write!(f, "保存済み"),
+            ArchiveStatus::Finished => write!(f, "dat落ち・保存済み"),
+        }
+    }
+}
+
+impl ArchiveStateItem {
+    pub async fn get_all(db: &DbConn) -> Result<Vec<ArchiveStateItem>> {
+        let mut boards: HashMap<i32, String> = HashMap::new();
+        let mut items = vec![];
+        for thread in ThreadStateItem::get_archived(db).await? {
+            let board_name = match boards.get(&thread.board_id) {
+                Some(name) => name.clone(),
+                None => {
+                    let name = board::Entity::find_by_id(thread.board_id)
+                        .one(db)
+                        .await?
+                        .map(|board| board.name)
+                        .unwrap_or_default();
+                    boards.insert(thread.board_id, name.clone());
+                    name
+                }
+            };
+            let saved = ThreadStateItem::post_count(db, thread.id).await?;
+            let archived_at = thread.archived_at;
+            items.push(ArchiveStateItem {
+                thread: ThreadStateItem::from(thread),
+                board_name,
+                saved,
+                archived_at,
+            });
+        }
+        Ok(items)
+    }
+
+    pub fn status(&self) -> ArchiveStatus {
+        if self.archived_at == 0 {
+            ArchiveStatus::Waiting
+        } else if self.thread.stopdone {
+            ArchiveStatus::Finished
+        } else if (self.saved as i32) < self.thread.count {
+            ArchiveStatus::Updated
+        } else {
+            ArchiveStatus::Saved
+        }
+    }
+
+    /// 取得して保存し直す必要があるか
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self.status(),
+            ArchiveStatus::Waiting | ArchiveStatus::Updated
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let mut item = ArchiveStateItem {
+            thread: ThreadStateItem {
+                count: 10,
+                ..Default::default()
+            },
+            saved: 10,
+            ..Default::default()
+        };
+        assert_eq!(item.status(), ArchiveStatus::Waiting);
+        item.archived_at = 1;
+        assert_eq!(item.status(), ArchiveStatus::Saved);
+        item.thread.count = 12;
+        assert!(item.is_pending());
+        item.thread.stopdone = true;
+        assert_eq!(item.status(), ArchiveStatus::Finished);
+        assert!(!item.is_pending());
+    }
+}
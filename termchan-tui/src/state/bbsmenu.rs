@@ -4,13 +4,15 @@ use entity::{
     menu,
     prelude::{Board, Category, Menu},
 };
-use eyre::Result;
+use eyre::{bail, Result};
 use migration::OnConflict;
-use sea_orm::{ColumnTrait, EntityTrait, InsertResult, QueryFilter, Set};
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, InsertResult, QueryFilter, Set};
 use serde::{Deserialize, Serialize};
 use termchan_core::get::bbsmenu::Bbsmenu;
 
-use crate::database::connect::establish_connection;
+/// URLから開いた板を入れておくメニュー bbsmenuの一覧には出さない。
+pub const LOCAL_MENU_URL: &str = "termchan:local";
+pub const LOCAL_CATEGORY_NAME: &str = "URLから開いた板";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BbsMenuStateItem {
@@ -28,12 +30,11 @@ impl Default for BbsMenuStateItem {
 }
 
 impl BbsMenuStateItem {
-    pub async fn init(urls: Vec<String>) {
-        let db = establish_connection().await.unwrap();
+    pub async fn init(db: &DbConn, urls: Vec<String>) {
         for url in urls {
             let menu = Menu::find()
                 .filter(menu::Column::Url.eq(url.clone()))
-                .one(&db)
+                .one(db)
                 .await
                 .unwrap_or_default();
             if None == menu {
@@ -48,16 +49,41 @@ impl BbsMenuStateItem {
                             .do_nothing()
                             .to_owned(),
                     )
-                    .exec(&db)
+                    .exec(db)
                     .await
                     .unwrap();
             }
         }
     }
 
-    pub async fn get() -> Result<Vec<BbsMenuStateItem>> {
-        let db = establish_connection().await?;
-        let menus = menu::Entity::find().all(&db).await?;
+    /// URLから開いた板のメニューのID 無ければ作る。
+    pub async fn local_menu_id(db: &DbConn) -> Result<i32> {
+        Self::init(db, vec![LOCAL_MENU_URL.to_string()]).await;
+        match Menu::find()
+            .filter(menu::Column::Url.eq(LOCAL_MENU_URL))
+            .one(db)
+            .await?
+        {
+            Some(menu) => Ok(menu.id),
+            None => bail!("menu not found {}", LOCAL_MENU_URL),
+        }
+    }
+
+    /// メニューを削除する。取得済みのカテゴリや板があれば失敗する。
+    pub async fn delete(db: &DbConn, url: &str) -> Result<()> {
+        menu::Entity::delete_many()
+            .filter(menu::Column::Url.eq(url))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// 設定にあるメニュー 取得済みの板が残っていて消せなかった古いメニューは並べない。
+    pub async fn get(db: &DbConn, urls: &[String]) -> Result<Vec<BbsMenuStateItem>> {
+        let menus = menu::Entity::find()
+            .filter(menu::Column::Url.is_in(urls.iter().cloned()))
+            .all(db)
+            .await?;
         let mut bbs_menu_state_item = Vec::new();
         for menu in menus {
             bbs_menu_state_item.push(BbsMenuStateItem {
@@ -68,8 +94,7 @@ impl BbsMenuStateItem {
         Ok(bbs_menu_state_item)
     }
 
-    pub async fn update(&self) -> Result<()> {
-        let db = establish_connection().await?;
+    pub async fn update(&self, db: &DbConn) -> Result<()> {
         let res = Bbsmenu::new(self.url.to_string())?.get().await?;
 
         let menu_id_org = self.id;
@@ -92,7 +117,7 @@ impl BbsMenuStateItem {
                         .do_nothing()
                         .to_owned(),
                 )
-                .exec(&db)
+                .exec(db)
                 .await?;
             let category_val = match category_val {
                 InsertResult { last_insert_id } => last_insert_id,
@@ -122,7 +147,7 @@ impl BbsMenuStateItem {
                     .do_nothing()
                     .to_owned(),
             )
-            .exec(&db)
+            .exec(db)
             .await?;
 
         Ok(())
@@ -1,17 +1,14 @@
-use entity::{board, prelude::*, thread};
-use eyre::Result;
+use entity::{board, category, prelude::*, thread};
+use eyre::{bail, eyre, Result};
 use migration::OnConflict;
-use sea_orm::{
-    sea_query::{Expr, Value},
-    ColumnTrait,
-    EntityTrait,
-    QueryFilter,
-    Set,
-};
+use sea_orm::{sea_query::Expr, ColumnTrait, Condition, DbConn, EntityTrait, QueryFilter, Set};
 use serde::{Deserialize, Serialize};
-use termchan_core::get::board::Board;
+use termchan_core::{get::board::Board, url::thread::BoardParams};
 
-use crate::database::connect::establish_connection;
+use super::{
+    bbsmenu::{BbsMenuStateItem, LOCAL_CATEGORY_NAME},
+    thread::{BoardSync, ThreadStateItem},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BoardStateItem {
@@ -30,12 +27,21 @@ impl Default for BoardStateItem {
     }
 }
 
+impl From<board::Model> for BoardStateItem {
+    fn from(board: board::Model) -> Self {
+        BoardStateItem {
+            id:   board.id,
+            url:  board.url,
+            name: board.name,
+        }
+    }
+}
+
 impl BoardStateItem {
-    pub async fn get_by_category_id(category_id: i32) -> Result<Vec<BoardStateItem>> {
-        let db = establish_connection().await?;
+    pub async fn get_by_category_id(db: &DbConn, category_id: i32) -> Result<Vec<BoardStateItem>> {
         let boards = board::Entity::find()
             .filter(board::Column::CategoryId.eq(category_id))
-            .all(&db)
+            .all(db)
             .await?;
         let mut board_state_item = Vec::new();
         for board in boards {
@@ -48,56 +54,107 @@ impl BoardStateItem {
         Ok(board_state_item)
     }
 
-    /// 板URLからスレッド一覧を取得する。
-    pub async fn fetch(&self) -> Result<()> {
-        let db = establish_connection().await?;
-        let res = Board::new(self.url.to_string())?.get().await?;
-        let mut new_threads = vec![];
-        // 一旦スレッド一覧はi新規スレッドとして取得する。
-        for item in res {
-            new_threads.push(thread::ActiveModel {
-                index: Set(item.index),
-                name: Set(item.name.to_string()),
-                url: Set(item.url.to_string()),
-                count: Set(item.count),
-                ikioi: Set(Some(item.ikioi)),
-                created_time: Set(Some(item.created_time.timestamp())),
-                board_id: Set(self.id),
-                stopdone: Set(false),
-                is_read: Set(false),
-                ..Default::default()
-            });
-        }
-        // DBにあるスレッドはすべてDAT落ち判定にする。
-        let res = Thread::update_many()
-            .filter(thread::Column::BoardId.eq(self.id))
-            .col_expr(
-                thread::Column::Stopdone,
-                Expr::value(Value::Bool(Some(true))),
+    pub async fn get_by_url(db: &DbConn, url: &str) -> Result<Option<BoardStateItem>> {
+        let board = board::Entity::find()
+            .filter(board::Column::Url.eq(url))
+            .one(db)
+            .await?;
+        Ok(board.map(BoardStateItem::from))
+    }
+
+    /// 名前かキーが一致する板 ログのフォルダ名から板を探すときに使う。
+    /// 別のサーバーに同じキーの板があると、複数返る。
+    pub async fn find_by_name_or_key(db: &DbConn, name: &str) -> Result<Vec<BoardStateItem>> {
+        let boards = board::Entity::find()
+            .filter(
+                Condition::any()
+                    .add(board::Column::Name.eq(name))
+                    .add(board::Column::Url.ends_with(&format!("/{}/", name))),
             )
-            .exec(&db)
+            .all(db)
             .await?;
+        Ok(boards.into_iter().map(BoardStateItem::from).collect())
+    }
 
-        warn!("{:?}", res);
-        for thread in &new_threads {
-            info!("{:?}", thread);
+    /// 板を探し、無ければURLから開いた板として登録する。
+    /// サーバー名の分からないURLなら、同じドメインの同じ名前の板を探す。
+    pub async fn find_or_register(db: &DbConn, params: &BoardParams) -> Result<BoardStateItem> {
+        let url = params.build_board_url();
+        if let Some(board) = Self::get_by_url(db, &url).await? {
+            return Ok(board);
+        }
+        if params.host.matches('.').count() <= 1 {
+            let suffix = format!(".{}/{}/", params.host, params.board_key);
+            let board = board::Entity::find()
+                .filter(board::Column::Url.ends_with(&suffix))
+                .one(db)
+                .await?;
+            match board {
+                Some(board) => return Ok(board.into()),
+                None => bail!("サーバー名の入ったURLを入力してください。: {}", url),
+            }
         }
 
-        // Dat落ちしていないスレッドはDat落ちが解除される。
-        let res = Thread::insert_many(new_threads)
+        let menu_id = BbsMenuStateItem::local_menu_id(db).await?;
+        let category = category::ActiveModel {
+            name: Set(LOCAL_CATEGORY_NAME.to_string()),
+            menu_id: Set(menu_id),
+            m_category_name: Set(format!("{}{}", menu_id, LOCAL_CATEGORY_NAME)),
+            ..Default::default()
+        };
+        Category::insert(category)
             .on_conflict(
-                OnConflict::column(thread::Column::Url)
-                    .update_columns(vec![
-                        thread::Column::Count,
-                        thread::Column::Ikioi,
-                        thread::Column::Stopdone,
-                        thread::Column::Index,
-                    ])
+                OnConflict::column(category::Column::MCategoryName)
+                    .do_nothing()
                     .to_owned(),
             )
-            .exec(&db)
+            .exec(db)
+            .await
+            .ok();
+        let category = category::Entity::find()
+            .filter(
+                category::Column::MCategoryName.eq(format!("{}{}", menu_id, LOCAL_CATEGORY_NAME)),
+            )
+            .one(db)
+            .await?
+            .ok_or_else(|| eyre!("category not found"))?;
+        let board = board::ActiveModel {
+            name: Set(params.board_key.clone()),
+            url: Set(url.clone()),
+            category_id: Set(category.id),
+            menu_id: Set(menu_id),
+            mc_board_name: Set(format!("{}{}{}", menu_id, LOCAL_CATEGORY_NAME, url)),
+            ..Default::default()
+        };
+        board::Entity::insert(board).exec(db).await?;
+        Self::get_by_url(db, &url)
+            .await?
+            .ok_or_else(|| eyre!("board not found {}", url))
+    }
+
+    /// 板URLからスレッド一覧を取得する。
+    /// スレ一覧を取得して保存し、前回からの変化を返す。
+    pub async fn fetch(&self, db: &DbConn) -> Result<BoardSync> {
+        let subjects = Board::new(self.url.to_string())?.get().await?;
+        ThreadStateItem::sync_board(db, self.id, &subjects).await
+    }
+
+    /// 板と、板にある全てのスレのアーカイブを切り替える。
+    pub async fn set_archived(db: &DbConn, board_id: i32, archived: bool) -> Result<()> {
+        board::Entity::update_many()
+            .col_expr(board::Column::Archived, Expr::value(archived))
+            .filter(board::Column::Id.eq(board_id))
+            .exec(db)
+            .await?;
+        Self::archive_threads(db, board_id, archived).await
+    }
+
+    async fn archive_threads(db: &DbConn, board_id: i32, archived: bool) -> Result<()> {
+        Thread::update_many()
+            .col_expr(thread::Column::Archived, Expr::value(archived))
+            .filter(thread::Column::BoardId.eq(board_id))
+            .exec(db)
             .await?;
-        info!("{:?}", res);
         Ok(())
     }
 }
@@ -1,11 +1,11 @@
-use entity::{board, board_bookmark, prelude::*, thread};
+use entity::{board, board_bookmark, prelude::*};
 use eyre::{bail, Result};
-use migration::{Condition, Expr, OnConflict, Query};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use migration::OnConflict;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, Set};
 use serde::{Deserialize, Serialize};
 use termchan_core::get::board::Board;
 
-use crate::database::connect::establish_connection;
+use super::thread::{BoardSync, ThreadStateItem};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookmarkStateItem {
@@ -27,12 +27,11 @@ impl Default for BookmarkStateItem {
 }
 
 impl BookmarkStateItem {
-    pub async fn get_all() -> Result<Vec<BookmarkStateItem>> {
-        let db = establish_connection().await?;
+    pub async fn get_all(db: &DbConn) -> Result<Vec<BookmarkStateItem>> {
         let bookmarks: Vec<(board_bookmark::Model, Option<board::Model>)> =
             board_bookmark::Entity::find()
                 .find_also_related(Board)
-                .all(&db)
+                .all(db)
                 .await?;
 
         if bookmarks.len() == 0 {
@@ -59,12 +58,10 @@ impl BookmarkStateItem {
         Ok(bookmarks_state_item)
     }
 
-    pub async fn add(url: String) -> Result<()> {
-        let db = establish_connection().await?;
-
+    pub async fn add(db: &DbConn, url: String) -> Result<()> {
         let board = board::Entity::find()
             .filter(board::Column::Url.eq(url.clone()))
-            .one(&db)
+            .one(db)
             .await?;
 
         let board = match board {
@@ -84,79 +81,20 @@ impl BookmarkStateItem {
                     .do_nothing()
                     .to_owned(),
             )
-            .exec(&db)
+            .exec(db)
             .await?;
         Ok(())
     }
 
-    pub async fn delete(id: i32) -> Result<()> {
-        let db = establish_connection().await?;
-        BoardBookmark::delete_by_id(id).exec(&db).await.unwrap();
+    pub async fn delete(db: &DbConn, id: i32) -> Result<()> {
+        BoardBookmark::delete_by_id(id).exec(db).await.unwrap();
         Ok(())
     }
 
     /// 板URLからスレッド一覧を取得する。
-    pub async fn fetch(&self) -> Result<()> {
-        let db = establish_connection().await?;
+    pub async fn fetch(&self, db: &DbConn) -> Result<BoardSync> {
         info!("fetch board from: {}", self.url.to_string());
-        let res = Board::new(self.url.to_string())?.get().await?;
-        let mut new_threads = vec![];
-        // 一旦スレッド一覧はi新規スレッドとして取得する。
-        for item in res {
-            new_threads.push(thread::ActiveModel {
-                index: Set(item.index),
-                name: Set(item.name.to_string()),
-                url: Set(item.url.to_string()),
-                count: Set(item.count),
-                ikioi: Set(Some(item.ikioi)),
-                created_time: Set(Some(item.created_time.timestamp())),
-                board_id: Set(self.id),
-                stopdone: Set(false),
-                is_read: Set(false),
-                ..Default::default()
-            });
-        }
-
-        let res: Vec<(thread::Model, Option<board::Model>)> = Thread::find()
-            .find_also_related(Board)
-            .filter(board::Column::Url.eq(self.url.to_string()))
-            .all(&db)
-            .await?;
-        warn!("current thread len {:?}", res.len());
-        // update
-
-        let res = Thread::update_many()
-            .col_expr(thread::Column::Stopdone, Expr::value(true))
-            .filter(
-                Condition::any().add(
-                    thread::Column::BoardId.in_subquery(
-                        Query::select()
-                            .column(board::Column::Id)
-                            .from(Board)
-                            .and_where(Expr::col(board::Column::Url).eq(self.url.to_string()))
-                            .to_owned(),
-                    ),
-                ),
-            )
-            .exec(&db)
-            .await;
-        warn!("update thread len {:?}", res);
-
-        // Dat落ちしていないスレッドはDat落ちが解除される。
-        let res = Thread::insert_many(new_threads)
-            .on_conflict(
-                OnConflict::column(thread::Column::Url)
-                    .update_columns(vec![
-                        thread::Column::Count,
-                        thread::Column::Ikioi,
-                        thread::Column::Stopdone,
-                        thread::Column::Index,
-                    ])
-                    .to_owned(),
-            )
-            .exec(&db)
-            .await?;
-        info!("{:?}", res);
-        Ok(())
+        let subjects = Board::new(self.url.to_string())?.get().await?;
+        ThreadStateItem::sync_board(db, self.id, &subjects).await
     }
 }
@@ -1,9 +1,8 @@
 use entity::category;
 use eyre::Result;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter};
 use serde::{Deserialize, Serialize};
 
-use crate::database::connect::establish_connection;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CategoriesStateItem {
     pub id:   i32,
@@ -21,11 +20,10 @@ impl Default for CategoriesStateItem {
 
 impl CategoriesStateItem {
     /// 全てのカテゴリ名を取得する。
-    pub async fn get_by_menu_id(id: i32) -> Result<Vec<CategoriesStateItem>> {
-        let db = establish_connection().await?;
+    pub async fn get_by_menu_id(db: &DbConn, id: i32) -> Result<Vec<CategoriesStateItem>> {
         let categories = category::Entity::find()
             .filter(category::Column::MenuId.eq(id))
-            .all(&db)
+            .all(db)
             .await?;
         let mut categories_state_item = Vec::new();
         for category in categories {
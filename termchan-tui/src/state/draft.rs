@@ -0,0 +1,82 @@
+use entity::{draft, prelude::*};
+use eyre::Result;
+use migration::OnConflict;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+
+/// 書き込み先ごとの書きかけのフォーム
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DraftStateItem {
+    /// 書き込み先のスレッドか板のURL
+    pub url:     String,
+    pub name:    String,
+    pub email:   String,
+    pub subject: Option<String>,
+    pub message: String,
+}
+
+impl DraftStateItem {
+    /// 何も書かれていないか
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty()
+            && self.email.is_empty()
+            && self.subject.as_deref().unwrap_or_default().is_empty()
+            && self.message.is_empty()
+    }
+
+    pub async fn get(db: &DbConn, url: &str) -> Result<Option<DraftStateItem>> {
+        let draft = draft::Entity::find()
+            .filter(draft::Column::Url.eq(url))
+            .one(db)
+            .await?;
+        Ok(draft.map(|d| {
+            DraftStateItem {
+                url:     d.url,
+                name:    d.name,
+                email:   d.email,
+                subject: d.subject,
+                message: d.message,
+            }
+        }))
+    }
+
+    /// 下書きを保存する。空のフォームなら下書きを消す。
+    pub async fn save(&self, db: &DbConn) -> Result<()> {
+        if self.is_empty() {
+            return Self::delete(db, &self.url).await;
+        }
+
+        let draft = draft::ActiveModel {
+            url: Set(self.url.to_string()),
+            name: Set(self.name.to_string()),
+            email: Set(self.email.to_string()),
+            subject: Set(self.subject.clone()),
+            message: Set(self.message.to_string()),
+            updated_at: Set(chrono::Utc::now().timestamp()),
+            ..Default::default()
+        };
+        Draft::insert(draft)
+            .on_conflict(
+                OnConflict::column(draft::Column::Url)
+                    .update_columns(vec![
+                        draft::Column::Name,
+                        draft::Column::Email,
+                        draft::Column::Subject,
+                        draft::Column::Message,
+                        draft::Column::UpdatedAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(db: &DbConn, url: &str) -> Result<()> {
+        draft::Entity::delete_many()
+            .filter(draft::Column::Url.eq(url))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use entity::{post_history, prelude::*};
+use eyre::Result;
+use sea_orm::{DbConn, EntityTrait, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+
+use super::draft::DraftStateItem;
+
+/// 書き込みの履歴
+/// 失敗した書き込みは、保存したフォームの内容から再送できる。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostHistoryStateItem {
+    pub id:        i32,
+    /// 書き込み先のスレッドか板のURL
+    pub url:       String,
+    /// スレ立てかどうか
+    pub is_thread: bool,
+    pub name:      String,
+    pub email:     String,
+    pub subject:   Option<String>,
+    pub message:   String,
+    pub success:   bool,
+    /// 失敗したときのサーバーからの理由
+    pub reason:    String,
+    pub posted_at: i64,
+}
+
+impl From<post_history::Model> for PostHistoryStateItem {
+    fn from(history: post_history::Model) -> Self {
+        PostHistoryStateItem {
+            id:        history.id,
+            url:       history.url,
+            is_thread: history.is_thread,
+            name:      history.name,
+            email:     history.email,
+            subject:   history.subject,
+            message:   history.message,
+            success:   history.success,
+            reason:    history.reason,
+            posted_at: history.posted_at,
+        }
+    }
+}
+
+impl From<&PostHistoryStateItem> for DraftStateItem {
+    fn from(history: &PostHistoryStateItem) -> Self {
+        DraftStateItem {
+            url:     history.url.to_string(),
+            name:    history.name.to_string(),
+            email:   history.email.to_string(),
+            subject: history.subject.clone(),
+            message: history.message.to_string(),
+        }
+    }
+}
+
+impl PostHistoryStateItem {
+    /// フォームの内容から、まだ送信していない書き込みを作る。
+    pub fn from_draft(draft: DraftStateItem, is_thread: bool) -> Self {
+        PostHistoryStateItem {
+            url: draft.url,
+            is_thread,
+            name: draft.name,
+            email: draft.email,
+            subject: draft.subject,
+            message: draft.message,
+            ..Default::default()
+        }
+    }
+
+    /// 送信結果を付けて履歴に追加する。
+    pub async fn add(&self, db: &DbConn, success: bool, reason: &str) -> Result<()> {
+        let history = post_history::ActiveModel {
+            url: Set(self.url.to_string()),
+            is_thread: Set(self.is_thread),
+            name: Set(self.name.to_string()),
+            email: Set(self.email.to_string()),
+            subject: Set(self.subject.clone()),
+            message: Set(self.message.to_string()),
+            success: Set(success),
+            reason: Set(reason.to_string()),
+            posted_at: Set(chrono::Utc::now().timestamp()),
+            ..Default::default()
+        };
+        PostHistory::insert(history).exec(db).await?;
+        Ok(())
+    }
+
+    /// 新しい順に全て取得する。
+    pub async fn get_all(db: &DbConn) -> Result<Vec<PostHistoryStateItem>> {
+        let histories = post_history::Entity::find()
+            .order_by_desc(post_history::Column::PostedAt)
+            .order_by_desc(post_history::Column::Id)
+            .all(db)
+            .await?;
+        Ok(histories
+            .into_iter()
+            .map(PostHistoryStateItem::from)
+            .collect())
+    }
+
+    /// 最後の書き込みが失敗していれば、その内容を返す。
+    pub async fn get_last_failed(db: &DbConn) -> Result<Option<PostHistoryStateItem>> {
+        let last = post_history::Entity::find()
+            .order_by_desc(post_history::Column::PostedAt)
+            .order_by_desc(post_history::Column::Id)
+            .one(db)
+            .await?;
+        Ok(last.filter(|h| !h.success).map(PostHistoryStateItem::from))
+    }
+}
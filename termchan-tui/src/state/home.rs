@@ -5,6 +5,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HomeItem {
     Bookmark,
+    Notifications,
+    Archive,
+    History,
+    Identities,
     Settings,
     AllChannels,
 }
@@ -13,6 +17,10 @@ impl Display for HomeItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             HomeItem::Bookmark => write!(f, "Bookmark"),
+            HomeItem::Notifications => write!(f, "Notifications"),
+            HomeItem::Archive => write!(f, "Archive"),
+            HomeItem::History => write!(f, "Post History"),
+            HomeItem::Identities => write!(f, "Identities"),
             HomeItem::Settings => write!(f, "Settings"),
             HomeItem::AllChannels => write!(f, "All Channels"),
         }
@@ -28,4 +36,17 @@ impl HomeStateItem {
     pub fn new(item: HomeItem) -> Self {
         HomeStateItem { item }
     }
+
+    /// Homeに並べる項目
+    pub fn all() -> Vec<Self> {
+        vec![
+            HomeStateItem::new(HomeItem::Bookmark),
+            HomeStateItem::new(HomeItem::Notifications),
+            HomeStateItem::new(HomeItem::Archive),
+            HomeStateItem::new(HomeItem::History),
+            HomeStateItem::new(HomeItem::Identities),
+            HomeStateItem::new(HomeItem::AllChannels),
+            HomeStateItem::new(HomeItem::Settings),
+        ]
+    }
 }
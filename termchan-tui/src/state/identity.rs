@@ -0,0 +1,128 @@
+use entity::{board_identity, identity, prelude::*};
+use eyre::Result;
+use migration::OnConflict;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use termchan_core::util::tripcode::{tripcode, TRIP_MARK};
+
+use crate::config::credentials::{delete_trip_key, get_trip_key, set_trip_key};
+
+/// 書き込みに使う名前
+/// トリップキーは認証情報の保存先にあり、キャッシュには書き出さない。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdentityStateItem {
+    pub id:       i32,
+    pub label:    String,
+    pub name:     String,
+    /// メール欄の既定値
+    pub email:    String,
+    #[serde(skip)]
+    pub trip_key: Option<String>,
+}
+
+impl From<identity::Model> for IdentityStateItem {
+    fn from(m: identity::Model) -> Self {
+        IdentityStateItem {
+            id:       m.id,
+            label:    m.label,
+            name:     m.name,
+            email:    m.email,
+            trip_key: get_trip_key(m.id),
+        }
+    }
+}
+
+impl IdentityStateItem {
+    /// キーから計算したトリップ
+    pub fn trip(&self) -> Option<String> {
+        self.trip_key
+            .as_deref()
+            .map(|key| format!("{}{}", TRIP_MARK, tripcode(key)))
+    }
+
+    /// 名前欄がこの名前のままなら、送信するときにトリップキーを付ける。
+    pub fn name_with_key(&self, name: &str) -> String {
+        match &self.trip_key {
+            Some(key) if name == self.name => format!("{}#{}", name, key),
+            _ => name.to_string(),
+        }
+    }
+
+    pub async fn get_all(db: &DbConn) -> Result<Vec<IdentityStateItem>> {
+        let identities = identity::Entity::find()
+            .order_by_asc(identity::Column::Id)
+            .all(db)
+            .await?;
+        Ok(identities.into_iter().map(Into::into).collect())
+    }
+
+    /// 板で使う名前を取得する。選んでいなければNone
+    pub async fn get_by_board(db: &DbConn, board_url: &str) -> Result<Option<IdentityStateItem>> {
+        let identity = board_identity::Entity::find()
+            .filter(board_identity::Column::BoardUrl.eq(board_url))
+            .find_also_related(identity::Entity)
+            .one(db)
+            .await?;
+        Ok(identity.and_then(|(_, i)| i).map(Into::into))
+    }
+
+    /// 板で使う名前を設定する。Noneなら選択を外す。
+    pub async fn set_board(db: &DbConn, board_url: &str, identity_id: Option<i32>) -> Result<()> {
+        let identity_id = match identity_id {
+            Some(id) => id,
+            None => {
+                board_identity::Entity::delete_many()
+                    .filter(board_identity::Column::BoardUrl.eq(board_url))
+                    .exec(db)
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let model = board_identity::ActiveModel {
+            board_url: Set(board_url.to_string()),
+            identity_id: Set(identity_id),
+            ..Default::default()
+        };
+        BoardIdentity::insert(model)
+            .on_conflict(
+                OnConflict::column(board_identity::Column::BoardUrl)
+                    .update_column(board_identity::Column::IdentityId)
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// 名前を保存する。idが0なら追加する。
+    pub async fn save(&self, db: &DbConn) -> Result<()> {
+        let model = identity::ActiveModel {
+            label: Set(self.label.clone()),
+            name: Set(self.name.clone()),
+            email: Set(self.email.clone()),
+            ..Default::default()
+        };
+        let id = if self.id == 0 {
+            Identity::insert(model).exec(db).await?.last_insert_id
+        } else {
+            identity::Entity::update(identity::ActiveModel {
+                id: Set(self.id),
+                ..model
+            })
+            .exec(db)
+            .await?;
+            self.id
+        };
+        set_trip_key(id, self.trip_key.as_deref().unwrap_or_default())
+    }
+
+    pub async fn delete(db: &DbConn, id: i32) -> Result<()> {
+        board_identity::Entity::delete_many()
+            .filter(board_identity::Column::IdentityId.eq(id))
+            .exec(db)
+            .await?;
+        identity::Entity::delete_by_id(id).exec(db).await?;
+        delete_trip_key(id)
+    }
+}
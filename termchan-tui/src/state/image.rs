@@ -0,0 +1,76 @@
+use std::{fs, path::Path};
+
+use entity::{image, prelude::*};
+use eyre::Result;
+use migration::OnConflict;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, Set};
+
+/// 保存した画像
+#[derive(Debug, Clone, Default)]
+pub struct ImageStateItem {
+    pub url:       String,
+    pub save_path: String,
+    pub size:      i32,
+}
+
+impl From<image::Model> for ImageStateItem {
+    fn from(image: image::Model) -> Self {
+        ImageStateItem {
+            url:       image.url,
+            save_path: image.save_path,
+            size:      image.size,
+        }
+    }
+}
+
+impl ImageStateItem {
+    pub async fn get_by_url(db: &DbConn, url: &str) -> Result<Option<ImageStateItem>> {
+        let image = Image::find()
+            .filter(image::Column::Url.eq(url))
+            .one(db)
+            .await?;
+        Ok(image.map(Into::into))
+    }
+
+    /// 保存済みで、ファイルも残っている画像
+    pub async fn get_saved(db: &DbConn, url: &str) -> Result<Option<ImageStateItem>> {
+        let image = Self::get_by_url(db, url).await?;
+        Ok(image.filter(|image| Path::new(&image.save_path).exists()))
+    }
+
+    /// 画像を取得して`path`に保存し、保存した場所を記録する。
+    pub async fn download(db: &DbConn, url: &str, path: &Path) -> Result<ImageStateItem> {
+        let bytes = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &bytes)?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let image = ImageStateItem {
+            url:       url.to_string(),
+            save_path: path.display().to_string(),
+            size:      bytes.len().try_into().unwrap_or(i32::MAX),
+        };
+        image.save(db).await?;
+        Ok(image)
+    }
+
+    /// 保存した場所を記録する。同じURLなら新しい場所に置き換える。
+    pub async fn save(&self, db: &DbConn) -> Result<()> {
+        let image = image::ActiveModel {
+            url: Set(self.url.clone()),
+            save_path: Set(self.save_path.clone()),
+            size: Set(self.size),
+            ..Default::default()
+        };
+        Image::insert(image)
+            .on_conflict(
+                OnConflict::column(image::Column::Url)
+                    .update_columns(vec![image::Column::SavePath, image::Column::Size])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::HashSet;
+
+use entity::{my_post, prelude::*, thread};
+use eyre::Result;
+use migration::Expr;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+
+use super::{post::ThreadPostStateItem, thread::ThreadStateItem};
+
+/// スレ立ての時刻とスレッドの作成時刻のずれの許容範囲(秒)
+const THREAD_TIME_MARGIN: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MyPostStateItem {
+    pub id:         i32,
+    pub board_url:  String,
+    pub thread_url: Option<String>,
+    pub thread_id:  Option<i32>,
+    pub subject:    Option<String>,
+    pub post_index: Option<i32>,
+    pub name:       String,
+    pub email:      String,
+    pub message:    String,
+    pub posted_at:  i64,
+}
+
+impl From<my_post::Model> for MyPostStateItem {
+    fn from(post: my_post::Model) -> Self {
+        MyPostStateItem {
+            id:         post.id,
+            board_url:  post.board_url,
+            thread_url: post.thread_url,
+            thread_id:  post.thread_id,
+            subject:    post.subject,
+            post_index: post.post_index,
+            name:       post.name,
+            email:      post.email,
+            message:    post.message,
+            posted_at:  post.posted_at,
+        }
+    }
+}
+
+impl MyPostStateItem {
+    /// レスを書き込んだことを記録する。
+    /// after_countは書き込んだ時点で取得済みだったレス数。
+    pub async fn add_reply(
+        db: &DbConn,
+        board_url: &str,
+        thread: &ThreadStateItem,
+        name: &str,
+        email: &str,
+        message: &str,
+        after_count: i32,
+    ) -> Result<()> {
+        let post = my_post::ActiveModel {
+            board_url: Set(board_url.to_string()),
+            thread_url: Set(Some(thread.url.to_string())),
+            thread_id: Set(Some(thread.id)),
+            name: Set(strip_trip_key(name)),
+            email: Set(email.to_string()),
+            message: Set(message.to_string()),
+            posted_at: Set(chrono::Utc::now().timestamp()),
+            after_count: Set(after_count),
+            ..Default::default()
+        };
+        MyPost::insert(post).exec(db).await?;
+        Ok(())
+    }
+
+    /// スレ立てしたことを記録する。
+    /// スレッドは板を更新したときに`match_threads`で結びつける。
+    pub async fn add_thread(
+        db: &DbConn,
+        board_url: &str,
+        subject: &str,
+        name: &str,
+        email: &str,
+        message: &str,
+    ) -> Result<()> {
+        let post = my_post::ActiveModel {
+            board_url: Set(board_url.to_string()),
+            subject: Set(Some(subject.to_string())),
+            name: Set(strip_trip_key(name)),
+            email: Set(email.to_string()),
+            message: Set(message.to_string()),
+            posted_at: Set(chrono::Utc::now().timestamp()),
+            after_count: Set(0),
+            ..Default::default()
+        };
+        MyPost::insert(post).exec(db).await?;
+        Ok(())
+    }
+
+    /// スレッド内の自分のレス番号を取得する。
+    pub async fn get_indexes_by_thread_id(db: &DbConn, thread_id: i32) -> Result<Vec<i32>> {
+        let posts = my_post::Entity::find()
+            .filter(my_post::Column::ThreadId.eq(thread_id))
+            .filter(my_post::Column::PostIndex.is_not_null())
+            .all(db)
+            .await?;
+        Ok(posts.into_iter().filter_map(|p| p.post_index).collect())
+    }
+
+    /// スレ立ての記録を、板のスレッド一覧の中からタイトルと作成時刻で探して結びつける。
+    pub async fn match_threads(db: &DbConn, board_id: i32, board_url: &str) -> Result<()> {
+        let unmatched = my_post::Entity::find()
+            .filter(my_post::Column::BoardUrl.eq(board_url))
+            .filter(my_post::Column::ThreadId.is_null())
+            .filter(my_post::Column::Subject.is_not_null())
+            .all(db)
+            .await?;
+        if unmatched.is_empty() {
+            return Ok(());
+        }
+
+        let threads = thread::Entity::find()
+            .filter(thread::Column::BoardId.eq(board_id))
+            .all(db)
+            .await?;
+        for post in unmatched {
+            let subject = post.subject.clone().unwrap_or_default();
+            let found = threads.iter().find(|t| {
+                t.name.trim() == subject.trim()
+                    && t.created_time.unwrap_or_default() >= post.posted_at - THREAD_TIME_MARGIN
+            });
+            if let Some(thread) = found {
+                my_post::Entity::update_many()
+                    .col_expr(my_post::Column::ThreadId, Expr::value(thread.id))
+                    .col_expr(my_post::Column::ThreadUrl, Expr::value(thread.url.clone()))
+                    .filter(my_post::Column::Id.eq(post.id))
+                    .exec(db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 書き込みの記録を、スレッドのレスと本文で照合してレス番号を記録する。
+    /// 書き込んだ時点より後のレスのうち、まだ誰のものでもない最初のレスを自分のものとする。
+    pub async fn match_posts(db: &DbConn, thread_id: i32) -> Result<()> {
+        let unmatched = my_post::Entity::find()
+            .filter(my_post::Column::ThreadId.eq(thread_id))
+            .filter(my_post::Column::PostIndex.is_null())
+            .order_by_asc(my_post::Column::PostedAt)
+            .all(db)
+            .await?;
+        if unmatched.is_empty() {
+            return Ok(());
+        }
+
+        let posts = ThreadPostStateItem::get_by_thread_id(db, thread_id).await?;
+        let mut claimed: HashSet<i32> = Self::get_indexes_by_thread_id(db, thread_id)
+            .await?
+            .into_iter()
+            .collect();
+        for mine in unmatched {
+            let message = normalize(&mine.message);
+            let found = posts
+                .iter()
+                .skip(mine.after_count.max(0) as usize)
+                .find(|p| {
+                    !claimed.contains(&p.index) && normalize(&p.message.to_string()) == message
+                });
+            if let Some(post) = found {
+                claimed.insert(post.index);
+                my_post::Entity::update_many()
+                    .col_expr(my_post::Column::PostIndex, Expr::value(post.index))
+                    .filter(my_post::Column::Id.eq(mine.id))
+                    .exec(db)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// トリップキーを記録しないように、`#`以降を取り除く。
+fn strip_trip_key(name: &str) -> String {
+    name.split('#').next().unwrap_or_default().to_string()
+}
+
+/// 本文の照合用に、サーバー側で変わりうる空白とアンカーの記号を取り除く。
+fn normalize(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '>' | '＞'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use termchan_core::get::message::parse_msg;
+
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let posted = ">>12\nこんにちは  よろしく";
+        let fetched = parse_msg(" &gt;&gt;12 <br> こんにちは  よろしく ").to_string();
+        assert_eq!(normalize(posted), normalize(&fetched));
+        assert_eq!(strip_trip_key("名無し#secret"), "名無し");
+    }
+}
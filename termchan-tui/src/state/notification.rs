@@ -0,0 +1,75 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use entity::{my_post, thread};
+use eyre::Result;
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+use termchan_core::get::message::Message;
+
+use super::post::ThreadPostStateItem;
+
+/// 自分のレスにアンカーを付けたレス
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationStateItem {
+    pub thread_id:   i32,
+    pub thread_name: String,
+    pub thread_url:  String,
+    /// アンカーで参照された自分のレス番号
+    pub my_index:    i32,
+    pub index:       i32,
+    pub name:        String,
+    pub date:        i64,
+    pub message:     Message,
+}
+
+impl NotificationStateItem {
+    /// 取得済みのスレッドから、自分のレスへの返信を新しい順に集める。
+    pub async fn get_all(db: &DbConn) -> Result<Vec<NotificationStateItem>> {
+        let my_posts = my_post::Entity::find()
+            .filter(my_post::Column::ThreadId.is_not_null())
+            .filter(my_post::Column::PostIndex.is_not_null())
+            .all(db)
+            .await?;
+
+        let mut my_indexes: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+        for post in my_posts {
+            if let (Some(thread_id), Some(index)) = (post.thread_id, post.post_index) {
+                my_indexes.entry(thread_id).or_default().push(index);
+            }
+        }
+
+        let mut notifications = vec![];
+        for (thread_id, indexes) in my_indexes {
+            let thread = match thread::Entity::find_by_id(thread_id).one(db).await? {
+                Some(thread) => thread,
+                None => continue,
+            };
+            let posts = ThreadPostStateItem::get_by_thread_id(db, thread_id).await?;
+            for post in posts {
+                if indexes.contains(&post.index) {
+                    continue;
+                }
+                let anchor = post
+                    .message
+                    .anchor_indexes()
+                    .into_iter()
+                    .find(|i| indexes.contains(i) && *i < post.index);
+                if let Some(my_index) = anchor {
+                    notifications.push(NotificationStateItem {
+                        thread_id,
+                        thread_name: thread.name.to_string(),
+                        thread_url: thread.url.to_string(),
+                        my_index,
+                        index: post.index,
+                        name: post.name,
+                        date: post.date,
+                        message: post.message,
+                    });
+                }
+            }
+        }
+
+        notifications.sort_by_key(|n| Reverse(n.date));
+        Ok(notifications)
+    }
+}
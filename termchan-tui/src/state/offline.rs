@@ -0,0 +1,76 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+/// 自動でオフラインにしたあと、接続が戻ったか確かめる間隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// オフラインで読んでいるかと、表示しているものが保存済みのものか
+#[derive(Debug, Clone, Default)]
+pub struct OfflineState {
+    /// 取得せず、データベースに保存したものだけを表示する
+    pub enabled:      bool,
+    /// 接続できなかったので自動で切り替えた 接続が戻れば自動で戻す
+    pub auto:         bool,
+    /// 最後に接続を確かめた時刻
+    pub last_check:   Option<Instant>,
+    /// スレ一覧が取得できずに保存済みのものを表示している
+    pub stale_board:  bool,
+    /// スレが取得できずに保存済みのものを表示している
+    pub stale_thread: bool,
+    /// 送信を待っている書き込みの数
+    pub queued:       usize,
+}
+
+impl OfflineState {
+    /// 接続できなかったときに切り替える。
+    pub fn disconnect(&mut self) {
+        self.enabled = true;
+        self.auto = true;
+        self.last_check = Some(Instant::now());
+    }
+
+    /// 接続が戻ったか確かめる頃か 自分で切り替えたときは確かめない
+    pub fn should_check(&mut self) -> bool {
+        if !self.enabled || !self.auto {
+            return false;
+        }
+        if self
+            .last_check
+            .is_some_and(|t| t.elapsed() < CHECK_INTERVAL)
+        {
+            return false;
+        }
+        self.last_check = Some(Instant::now());
+        true
+    }
+}
+
+/// 接続できなかったか、時間切れになったエラーか
+pub fn is_disconnected<'a>(mut chain: impl Iterator<Item = &'a (dyn Error + 'static)>) -> bool {
+    chain.any(|e| {
+        e.downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_check() {
+        let mut offline = OfflineState::default();
+        assert!(!offline.should_check());
+        offline.disconnect();
+        assert!(offline.enabled);
+        assert!(!offline.should_check());
+        offline.last_check = Some(Instant::now() - CHECK_INTERVAL);
+        assert!(offline.should_check());
+        assert!(!offline.should_check());
+        offline.auto = false;
+        offline.last_check = None;
+        assert!(!offline.should_check());
+    }
+}
@@ -1,42 +1,54 @@
 use entity::thread_post;
 use eyre::Result;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter, QueryOrder};
 use serde::{Deserialize, Serialize};
 use termchan_core::get::message::Message;
 
-use crate::database::connect::establish_connection;
+use crate::config::ng::NgConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThreadPostStateItem {
-    pub id:      i32,
-    pub index:   i32,
-    pub post_id: String,
-    pub message: Message,
-    pub date:    i64,
-    pub email:   Option<String>,
-    pub name:    String,
+    pub id:             i32,
+    pub index:          i32,
+    pub post_id:        String,
+    pub message:        Message,
+    pub date:           i64,
+    pub email:          Option<String>,
+    pub name:           String,
+    /// 自分の書き込み
+    #[serde(default)]
+    pub is_mine:        bool,
+    /// 自分の書き込みへの返信
+    #[serde(default)]
+    pub is_reply_to_me: bool,
+    /// あぼーんの条件に一致する
+    #[serde(default)]
+    pub is_ng:          bool,
 }
 
 impl Default for ThreadPostStateItem {
     fn default() -> Self {
         ThreadPostStateItem {
-            id:      0,
-            index:   0,
-            post_id: String::new(),
-            message: Message::default(),
-            date:    0,
-            email:   None,
-            name:    String::new(),
+            id:             0,
+            index:          0,
+            post_id:        String::new(),
+            message:        Message::default(),
+            date:           0,
+            email:          None,
+            name:           String::new(),
+            is_mine:        false,
+            is_reply_to_me: false,
+            is_ng:          false,
         }
     }
 }
 
 impl ThreadPostStateItem {
-    pub async fn get_by_thread_id(thread_id: i32) -> Result<Vec<ThreadPostStateItem>> {
-        let db = establish_connection().await?;
+    pub async fn get_by_thread_id(db: &DbConn, thread_id: i32) -> Result<Vec<ThreadPostStateItem>> {
         let posts = thread_post::Entity::find()
             .filter(thread_post::Column::ThreadId.eq(thread_id))
-            .all(&db)
+            .order_by_asc(thread_post::Column::Index)
+            .all(db)
             .await?;
         let mut thread_post_state_item = Vec::new();
         for post in posts {
@@ -54,8 +66,31 @@ impl ThreadPostStateItem {
                 date,
                 email: Some(post.email),
                 name: post.name.to_string(),
+                is_mine: false,
+                is_reply_to_me: false,
+                is_ng: false,
             });
         }
         Ok(thread_post_state_item)
     }
+
+    /// 自分のレスと、自分のレスへの返信に印を付ける。
+    pub fn mark_my_posts(posts: &mut [ThreadPostStateItem], my_indexes: &[i32]) {
+        for post in posts.iter_mut() {
+            post.is_mine = my_indexes.contains(&post.index);
+            post.is_reply_to_me = !post.is_mine
+                && post
+                    .message
+                    .anchor_indexes()
+                    .iter()
+                    .any(|i| my_indexes.contains(i));
+        }
+    }
+
+    /// あぼーんの条件に一致するレスに印を付ける。
+    pub fn mark_ng(posts: &mut [ThreadPostStateItem], ng: &NgConfig) {
+        for post in posts.iter_mut() {
+            post.is_ng = ng.is_ng(&post.post_id, &post.name, &post.message.to_string());
+        }
+    }
 }
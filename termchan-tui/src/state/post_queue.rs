@@ -0,0 +1,65 @@
+use entity::{post_queue, prelude::*};
+use eyre::Result;
+use sea_orm::{DbConn, EntityTrait, PaginatorTrait, QueryOrder, Set};
+
+use super::history::PostHistoryStateItem;
+
+/// オフラインのあいだに書いた、送信を待つ書き込み
+#[derive(Debug, Clone, Default)]
+pub struct QueuedPostStateItem {
+    pub id:   i32,
+    pub post: PostHistoryStateItem,
+}
+
+impl From<post_queue::Model> for QueuedPostStateItem {
+    fn from(queued: post_queue::Model) -> Self {
+        QueuedPostStateItem {
+            id:   queued.id,
+            post: PostHistoryStateItem {
+                url: queued.url,
+                is_thread: queued.is_thread,
+                name: queued.name,
+                email: queued.email,
+                subject: queued.subject,
+                message: queued.message,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl QueuedPostStateItem {
+    pub async fn add(db: &DbConn, post: &PostHistoryStateItem) -> Result<()> {
+        let queued = post_queue::ActiveModel {
+            url: Set(post.url.to_string()),
+            is_thread: Set(post.is_thread),
+            name: Set(post.name.to_string()),
+            email: Set(post.email.to_string()),
+            subject: Set(post.subject.clone()),
+            message: Set(post.message.to_string()),
+            queued_at: Set(chrono::Utc::now().timestamp()),
+            ..Default::default()
+        };
+        PostQueue::insert(queued).exec(db).await?;
+        Ok(())
+    }
+
+    /// 書いた順に全て取得する。
+    pub async fn get_all(db: &DbConn) -> Result<Vec<QueuedPostStateItem>> {
+        let queued = post_queue::Entity::find()
+            .order_by_asc(post_queue::Column::QueuedAt)
+            .order_by_asc(post_queue::Column::Id)
+            .all(db)
+            .await?;
+        Ok(queued.into_iter().map(QueuedPostStateItem::from).collect())
+    }
+
+    pub async fn count(db: &DbConn) -> Result<usize> {
+        Ok(post_queue::Entity::find().count(db).await?)
+    }
+
+    pub async fn delete(db: &DbConn, id: i32) -> Result<()> {
+        PostQueue::delete_by_id(id).exec(db).await?;
+        Ok(())
+    }
+}
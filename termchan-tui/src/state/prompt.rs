@@ -0,0 +1,32 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+/// ステータスラインで入力するものの種類 表示は先頭に付ける記号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum PromptKind {
+    /// コマンド 数字だけならレス番号へ移動する
+    #[display(fmt = ":")]
+    Command,
+    /// 一覧から探す
+    #[display(fmt = "/")]
+    Search,
+}
+
+/// ステータスラインの一行入力
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prompt {
+    pub kind:    PromptKind,
+    pub input:   String,
+    /// 履歴から呼び出している位置 新しいものが0
+    pub history: Option<usize>,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Self {
+        Prompt {
+            kind,
+            input: String::new(),
+            history: None,
+        }
+    }
+}
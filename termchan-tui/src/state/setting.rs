@@ -0,0 +1,247 @@
+use eyre::{bail, Result};
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Url,
+};
+use serde::{Deserialize, Serialize};
+
+use super::account::AccountStateItem;
+use crate::{
+    config::{
+        config::Config,
+        theme::{format_color, parse_color, Theme},
+    },
+    database::maintenance::{format_size, BoardUsage},
+};
+
+/// 設定タブの一行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SettingStateItem {
+    /// bbsmenuのURL 番号は`bbsmenu_url_list`の中の位置
+    BbsmenuUrl(usize),
+    AddBbsmenuUrl,
+    ShowIndex,
+    Login,
+    ThumbnailSize,
+    ThumbnailCacheSize,
+    BorderType,
+    /// テーマの色 名前は`Theme::COLOR_NAMES`のどれか
+    Color(String),
+    RequestHeader,
+    Account(AccountStateItem),
+    AddAccount,
+    /// データベースの大きさ 選ぶと整理する
+    Database(i64),
+    /// 板ごとの容量 表示するだけ
+    BoardUsage(BoardUsage),
+}
+
+impl SettingStateItem {
+    /// 設定タブに並べる項目
+    pub fn all(
+        config: &Config,
+        accounts: Vec<AccountStateItem>,
+        database_size: i64,
+        usage: Vec<BoardUsage>,
+    ) -> Vec<SettingStateItem> {
+        let mut items = (0..config.bbsmenu_url_list.len())
+            .map(SettingStateItem::BbsmenuUrl)
+            .collect::<Vec<_>>();
+        items.extend([
+            SettingStateItem::AddBbsmenuUrl,
+            SettingStateItem::ShowIndex,
+            SettingStateItem::Login,
+            SettingStateItem::ThumbnailSize,
+            SettingStateItem::ThumbnailCacheSize,
+            SettingStateItem::BorderType,
+        ]);
+        items.extend(
+            Theme::COLOR_NAMES
+                .iter()
+                .map(|name| SettingStateItem::Color(name.to_string())),
+        );
+        items.push(SettingStateItem::RequestHeader);
+        items.extend(accounts.into_iter().map(SettingStateItem::Account));
+        items.push(SettingStateItem::AddAccount);
+        items.push(SettingStateItem::Database(database_size));
+        items.extend(usage.into_iter().map(SettingStateItem::BoardUsage));
+        items
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            SettingStateItem::BbsmenuUrl(i) => format!("bbsmenu {}", i + 1),
+            SettingStateItem::AddBbsmenuUrl => "bbsmenuを追加".to_string(),
+            SettingStateItem::ShowIndex => "スレの番号を表示".to_string(),
+            SettingStateItem::Login => "ログインして書き込む".to_string(),
+            SettingStateItem::ThumbnailSize => "サムネイルのサイズ".to_string(),
+            SettingStateItem::ThumbnailCacheSize => "サムネイルのキャッシュ".to_string(),
+            SettingStateItem::BorderType => "枠線".to_string(),
+            SettingStateItem::Color(name) => format!("色: {}", name),
+            SettingStateItem::RequestHeader => "リクエストヘッダー".to_string(),
+            SettingStateItem::Account(account) => account.label.clone(),
+            SettingStateItem::AddAccount => "アカウントを追加".to_string(),
+            SettingStateItem::Database(..) => "データベースを整理".to_string(),
+            SettingStateItem::BoardUsage(usage) => format!("容量: {}", usage.name),
+        }
+    }
+
+    /// 今の値 編集フォームにもこの値を入れる。
+    pub fn value(&self, config: &Config) -> String {
+        let on_off = |b: bool| if b { "オン" } else { "オフ" }.to_string();
+        match self {
+            SettingStateItem::BbsmenuUrl(i) => {
+                config.bbsmenu_url_list.get(*i).cloned().unwrap_or_default()
+            }
+            SettingStateItem::ShowIndex => on_off(config.show_index),
+            SettingStateItem::Login => on_off(config.login),
+            SettingStateItem::ThumbnailSize => format!("{:?}", config.thumbnail_size),
+            SettingStateItem::ThumbnailCacheSize => config.thumbnail_cache_size.clone(),
+            SettingStateItem::BorderType => config.theme.border_type_name(),
+            SettingStateItem::Color(name) => {
+                config
+                    .theme
+                    .color(name)
+                    .map(format_color)
+                    .unwrap_or_default()
+            }
+            SettingStateItem::RequestHeader => config.request_header.clone(),
+            SettingStateItem::Account(account) => account.email.clone().unwrap_or_default(),
+            SettingStateItem::Database(size) => format_size(*size),
+            SettingStateItem::BoardUsage(usage) => {
+                format!(
+                    "{}スレ {}レス {}",
+                    usage.threads,
+                    usage.posts,
+                    format_size(usage.bytes)
+                )
+            }
+            SettingStateItem::AddBbsmenuUrl | SettingStateItem::AddAccount => String::new(),
+        }
+    }
+
+    /// 入力せずに切り替える項目なら、切り替えてtrueを返す。
+    pub fn toggle(&self, config: &mut Config) -> bool {
+        match self {
+            SettingStateItem::ShowIndex => config.show_index = !config.show_index,
+            SettingStateItem::Login => config.login = !config.login,
+            SettingStateItem::ThumbnailSize => config.thumbnail_size = config.thumbnail_size.next(),
+            SettingStateItem::BorderType => config.theme.next_border_type(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// 入力した値を検査して設定に反映する。
+    pub fn apply(&self, config: &mut Config, value: &str) -> Result<()> {
+        match self {
+            SettingStateItem::BbsmenuUrl(..) | SettingStateItem::AddBbsmenuUrl => {
+                let url = value.trim();
+                match Url::parse(url) {
+                    Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {}
+                    _ => bail!("http(s)から始まるURLを入力してください。"),
+                }
+                let exists = config.bbsmenu_url_list.iter().position(|u| u == url);
+                match self {
+                    SettingStateItem::BbsmenuUrl(i) if exists.is_none() || exists == Some(*i) => {
+                        config.bbsmenu_url_list[*i] = url.to_string()
+                    }
+                    SettingStateItem::AddBbsmenuUrl if exists.is_none() => {
+                        config.bbsmenu_url_list.push(url.to_string())
+                    }
+                    _ => bail!("既に追加されています。: {}", url),
+                }
+            }
+            SettingStateItem::ThumbnailCacheSize => {
+                let size = value.trim();
+                let digits = size.trim_end_matches(['K', 'M', 'G']);
+                if digits.is_empty()
+                    || size.len() - digits.len() > 1
+                    || !digits.chars().all(|c| c.is_ascii_digit())
+                {
+                    bail!("100Mのように、数字と単位(K, M, G)で入力してください。");
+                }
+                config.thumbnail_cache_size = size.to_string();
+            }
+            SettingStateItem::Color(name) => {
+                let color = match parse_color(value) {
+                    Some(color) => color,
+                    None => bail!("色の名前、#rrggbb、0から255の番号のどれかを入力してください。"),
+                };
+                config.theme.set_color(name, color);
+            }
+            SettingStateItem::RequestHeader => {
+                config.request_header = parse_request_header(value)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 設定から削除できる項目なら、削除してtrueを返す。
+    pub fn remove(&self, config: &mut Config) -> bool {
+        match self {
+            SettingStateItem::BbsmenuUrl(i) if *i < config.bbsmenu_url_list.len() => {
+                config.bbsmenu_url_list.remove(*i);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 一行に一つの`名前: 値`を検査し、空行を除いて並べ直す。
+fn parse_request_header(value: &str) -> Result<String> {
+    let mut header = String::new();
+    for (i, line) in value.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => bail!("{}行目: `名前: 値`の形で入力してください。", i + 1),
+        };
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            bail!(
+                "{}行目: ヘッダーの名前が正しくありません。: {}",
+                i + 1,
+                name
+            );
+        }
+        if HeaderValue::from_str(value).is_err() {
+            bail!("{}行目: ヘッダーの値が正しくありません。", i + 1);
+        }
+        header.push_str(&format!("{}: {}\n", name, value));
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut config = Config::default();
+        let add = SettingStateItem::AddBbsmenuUrl;
+        add.apply(&mut config, "https://menu.5ch.net/bbsmenu.json")
+            .unwrap();
+        assert!(add
+            .apply(&mut config, "https://menu.5ch.net/bbsmenu.json")
+            .is_err());
+        assert!(add.apply(&mut config, "menu.5ch.net").is_err());
+        assert_eq!(config.bbsmenu_url_list.len(), 1);
+
+        let size = SettingStateItem::ThumbnailCacheSize;
+        assert!(size.apply(&mut config, "200M").is_ok());
+        assert!(size.apply(&mut config, "200MB").is_err());
+        assert!(size.apply(&mut config, "M").is_err());
+
+        let header = SettingStateItem::RequestHeader;
+        header
+            .apply(&mut config, "accept:  */*\n\nuser-agent: termchan")
+            .unwrap();
+        assert_eq!(config.request_header, "accept: */*\nuser-agent: termchan\n");
+        assert!(header.apply(&mut config, "user agent: termchan").is_err());
+    }
+}
@@ -11,6 +11,10 @@ pub enum LeftTabItem {
     Categories,
     Category(Title),
     Board(Title),
+    Notifications,
+    Archive,
+    History,
+    Identities,
     Settings,
 }
 type Title = String;
@@ -25,6 +29,10 @@ impl Display for LeftTabItem {
             Self::Categories => write!(f, "カテゴリ"),
             Self::Category(title) => write!(f, "{}", title),
             Self::Board(title) => write!(f, "{}", title),
+            Self::Notifications => write!(f, "通知"),
+            Self::Archive => write!(f, "アーカイブ"),
+            Self::History => write!(f, "書き込み履歴"),
+            Self::Identities => write!(f, "名前"),
             Self::Settings => write!(f, "設定"),
         }
     }
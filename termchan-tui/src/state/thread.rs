@@ -1,49 +1,118 @@
-use entity::{prelude::*, thread, thread_post};
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use entity::{board, prelude::*, thread, thread_post};
 use eyre::Result;
-use migration::{DbErr, Expr, OnConflict};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use migration::{DbErr, Expr, OnConflict, Query};
+use sea_orm::{
+    ColumnTrait,
+    Condition,
+    DbConn,
+    EntityTrait,
+    PaginatorTrait,
+    QueryFilter,
+    QueryOrder,
+    Set,
+    TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
-use termchan_core::get::thread::Thread;
+use termchan_core::get::{
+    board::ThreadSubject,
+    thread::{Thread, ThreadResponse},
+};
 
-use crate::database::connect::establish_connection;
+/// 一度に書き込むスレの数 SQLiteの変数の上限を超えないようにする。
+const UPSERT_CHUNK: usize = 200;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct ThreadStateItem {
-    pub id:           i32,
-    pub index:        i32,
-    pub url:          String,
-    pub name:         String,
-    pub count:        i32,
-    pub ikioi:        f64,
-    pub created_time: i64,
-    pub is_read:      bool,
-    pub stopdone:     bool,
-    pub before_read:  i32,
+    pub id:              i32,
+    pub index:           i32,
+    pub url:             String,
+    pub name:            String,
+    pub count:           i32,
+    pub ikioi:           f64,
+    pub created_time:    i64,
+    pub is_read:         bool,
+    pub stopdone:        bool,
+    pub before_read:     i32,
+    #[serde(default)]
+    pub last_read_index: i32,
+    #[serde(default)]
+    pub scroll_offset:   i32,
+    /// 全てのレスと画像を保存し、整理で消さない
+    #[serde(default)]
+    pub archived:        bool,
+    /// 前回スレ一覧を取得したときに無かった新しいスレ
+    #[serde(default)]
+    pub is_new:          bool,
+}
+
+/// スレ一覧を取得して反映した結果
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BoardSync {
+    /// 新しく立ったスレ
+    pub new:     usize,
+    /// 読んだスレのうち、前回の取得から新しいレスが付いたスレ
+    pub updated: usize,
+    /// 一覧から消えてdat落ちになったスレ
+    pub dropped: usize,
 }
 
 impl Default for ThreadStateItem {
     fn default() -> Self {
         ThreadStateItem {
-            id:           0,
-            index:        0,
-            url:          String::new(),
-            name:         String::new(),
-            count:        0,
-            ikioi:        0.0,
-            created_time: 0,
-            is_read:      false,
-            stopdone:     false,
-            before_read:  0,
+            id:              0,
+            index:           0,
+            url:             String::new(),
+            name:            String::new(),
+            count:           0,
+            ikioi:           0.0,
+            created_time:    0,
+            is_read:         false,
+            stopdone:        false,
+            before_read:     0,
+            last_read_index: 0,
+            scroll_offset:   0,
+            archived:        false,
+            is_new:          false,
+        }
+    }
+}
+
+impl From<thread::Model> for ThreadStateItem {
+    fn from(thread: thread::Model) -> Self {
+        ThreadStateItem {
+            id:              thread.id,
+            index:           thread.index,
+            url:             thread.url.to_string(),
+            name:            thread.name.to_string(),
+            count:           thread.count,
+            ikioi:           thread.ikioi.unwrap_or(0.0),
+            created_time:    thread.created_time.unwrap_or_default(),
+            is_read:         thread.is_read,
+            stopdone:        thread.stopdone,
+            before_read:     thread.before_read,
+            last_read_index: thread.last_read_index,
+            scroll_offset:   thread.scroll_offset,
+            archived:        thread.archived,
+            is_new:          thread.is_new,
         }
     }
 }
 
 impl ThreadStateItem {
-    pub async fn get_by_board_id(board_id: i32) -> Result<Vec<ThreadStateItem>> {
-        let db = establish_connection().await?;
+    /// 板のスレ一覧 dat落ちしたスレは、読んだものかアーカイブしたものだけを並べる。
+    pub async fn get_by_board_id(db: &DbConn, board_id: i32) -> Result<Vec<ThreadStateItem>> {
         let threads: Result<Vec<thread::Model>, DbErr> = thread::Entity::find()
             .filter(thread::Column::BoardId.eq(board_id))
-            .all(&db)
+            .filter(
+                Condition::any()
+                    .add(thread::Column::Stopdone.eq(false))
+                    .add(thread::Column::IsRead.eq(true))
+                    .add(thread::Column::Archived.eq(true)),
+            )
+            .all(db)
             .await;
 
         let threads = match threads {
@@ -58,44 +127,305 @@ impl ThreadStateItem {
 
         let mut thread_state_item = Vec::new();
         for thread in threads {
-            thread_state_item.push(ThreadStateItem {
-                id:           thread.id,
-                index:        thread.index,
-                url:          thread.url.to_string(),
-                name:         thread.name.to_string(),
-                count:        thread.count,
-                ikioi:        thread.ikioi.unwrap_or(0.0),
-                created_time: thread.created_time.unwrap_or_default(),
-                is_read:      thread.is_read,
-                stopdone:     thread.stopdone,
-                before_read:  thread.before_read,
-            });
+            thread_state_item.push(ThreadStateItem::from(thread));
         }
         Ok(thread_state_item)
     }
 
-    pub async fn update_is_read(thread_id: i32) -> Result<()> {
-        let db = establish_connection().await?;
+    pub async fn get_by_id(db: &DbConn, thread_id: i32) -> Result<ThreadStateItem> {
+        let thread = thread::Entity::find_by_id(thread_id).one(db).await?;
+        match thread {
+            Some(thread) => Ok(ThreadStateItem::from(thread)),
+            None => eyre::bail!("thread not found {}", thread_id),
+        }
+    }
+
+    pub async fn get_by_url(db: &DbConn, url: &str) -> Result<Option<ThreadStateItem>> {
+        let thread = thread::Entity::find()
+            .filter(thread::Column::Url.eq(url))
+            .one(db)
+            .await?;
+        Ok(thread.map(ThreadStateItem::from))
+    }
+
+    /// 取得したスレ一覧を板のスレに反映する。
+    /// 一覧にあるスレはレス数、順番、勢いを更新し、無くなったスレはdat落ちにする。
+    /// 前回の一覧に無かったスレには新しい印を付け、アーカイブしている板ならアーカイブする。
+    pub async fn sync_board(
+        db: &DbConn,
+        board_id: i32,
+        subjects: &[ThreadSubject],
+    ) -> Result<BoardSync> {
+        // 取得に失敗して空の一覧になったときに、全てのスレをdat落ちにしない。
+        if subjects.is_empty() {
+            return Ok(BoardSync::default());
+        }
+        let txn = db.begin().await?;
+        let existing = thread::Entity::find()
+            .filter(thread::Column::BoardId.eq(board_id))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|thread| (thread.url.clone(), thread))
+            .collect::<HashMap<_, _>>();
+        let archived = board::Entity::find_by_id(board_id)
+            .one(&txn)
+            .await?
+            .is_some_and(|board| board.archived);
+
+        let mut sync = BoardSync::default();
+        let mut listed = HashSet::new();
+        let mut threads = vec![];
+        for subject in subjects {
+            listed.insert(subject.url.as_str());
+            let is_new = match existing.get(&subject.url) {
+                Some(thread) => {
+                    if thread.is_read && subject.count > thread.count.max(thread.before_read) {
+                        sync.updated += 1;
+                    }
+                    false
+                }
+                None => {
+                    sync.new += 1;
+                    true
+                }
+            };
+            threads.push(thread::ActiveModel {
+                index: Set(subject.index),
+                name: Set(subject.name.to_string()),
+                url: Set(subject.url.to_string()),
+                count: Set(subject.count),
+                ikioi: Set(Some(subject.ikioi)),
+                created_time: Set(Some(subject.created_time.timestamp())),
+                board_id: Set(board_id),
+                stopdone: Set(false),
+                is_read: Set(false),
+                archived: Set(archived),
+                is_new: Set(is_new),
+                ..Default::default()
+            });
+        }
+        sync.dropped = existing
+            .values()
+            .filter(|thread| !thread.stopdone && !listed.contains(thread.url.as_str()))
+            .count();
+
+        // 一覧に無いスレはdat落ちのまま残り、あるスレは下の更新で戻る。
+        thread::Entity::update_many()
+            .col_expr(thread::Column::Stopdone, Expr::value(true))
+            .col_expr(thread::Column::IsNew, Expr::value(false))
+            .filter(thread::Column::BoardId.eq(board_id))
+            .exec(&txn)
+            .await?;
+        for chunk in threads.chunks(UPSERT_CHUNK) {
+            thread::Entity::insert_many(chunk.to_vec())
+                .on_conflict(
+                    OnConflict::column(thread::Column::Url)
+                        .update_columns(vec![
+                            thread::Column::Index,
+                            thread::Column::Count,
+                            thread::Column::Ikioi,
+                            thread::Column::Stopdone,
+                            thread::Column::IsNew,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(sync)
+    }
+
+    /// アーカイブしたスレ 新しいものから並べる。
+    pub async fn get_archived(db: &DbConn) -> Result<Vec<thread::Model>> {
+        Ok(thread::Entity::find()
+            .filter(thread::Column::Archived.eq(true))
+            .order_by_desc(thread::Column::CreatedTime)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn set_archived(db: &DbConn, thread_id: i32, archived: bool) -> Result<()> {
+        thread::Entity::update_many()
+            .col_expr(thread::Column::Archived, Expr::value(archived))
+            .filter(thread::Column::Id.eq(thread_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// レスと画像を全て保存した時刻を記録する。
+    /// `saved_all`でなければ記録を消し、保存待ちに戻す。
+    pub async fn update_archived_at(db: &DbConn, thread_id: i32, saved_all: bool) -> Result<()> {
+        let archived_at = if saved_all { Utc::now().timestamp() } else { 0 };
+        thread::Entity::update_many()
+            .col_expr(thread::Column::ArchivedAt, Expr::value(archived_at))
+            .filter(thread::Column::Id.eq(thread_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// `before`より前に取得したきりで、アーカイブしていないスレのレスを消す。
+    /// スレは一覧や既読の記録に使うので残し、開けばまた取得する。
+    /// 取得した時刻が無いスレは、立った時刻で判断する。
+    /// スレの記録ごと消すのは`maintenance::remove_stale_threads`で、設定の`retention_days`に従う。
+    pub async fn prune(db: &DbConn, before: i64) -> Result<(usize, u64)> {
+        let ids = thread::Entity::find()
+            .filter(thread::Column::Archived.eq(false))
+            .filter(thread::Column::FetchedAt.lt(before))
+            .filter(
+                Condition::any()
+                    .add(thread::Column::FetchedAt.gt(0))
+                    .add(thread::Column::CreatedTime.lt(before)),
+            )
+            // 既にレスを消したスレは数えない。
+            .filter(
+                thread::Column::Id.in_subquery(
+                    Query::select()
+                        .column(thread_post::Column::ThreadId)
+                        .from(thread_post::Entity)
+                        .to_owned(),
+                ),
+            )
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect::<Vec<_>>();
+        if ids.is_empty() {
+            return Ok((0, 0));
+        }
+        let res = ThreadPost::delete_many()
+            .filter(thread_post::Column::ThreadId.is_in(ids.clone()))
+            .exec(db)
+            .await?;
+        Ok((ids.len(), res.rows_affected))
+    }
+
+    /// スレ一覧に無いスレを登録する。タイトルは取得したときに更新する。
+    pub async fn register(db: &DbConn, board_id: i32, url: &str) -> Result<ThreadStateItem> {
+        if let Some(thread) = Self::get_by_url(db, url).await? {
+            return Ok(thread);
+        }
+        let key = url.rsplit('/').next().unwrap_or_default();
+        let thread = thread::ActiveModel {
+            index: Set(0),
+            name: Set(key.to_string()),
+            url: Set(url.to_string()),
+            count: Set(0),
+            ikioi: Set(Some(0.0)),
+            created_time: Set(key.parse::<i64>().ok()),
+            board_id: Set(board_id),
+            stopdone: Set(true),
+            is_read: Set(false),
+            ..Default::default()
+        };
+        let res = thread::Entity::insert(thread).exec(db).await?;
+        Self::get_by_id(db, res.last_insert_id).await
+    }
+
+    /// 未読レスの数
+    pub fn new_count(&self) -> i32 {
+        if self.is_read {
+            (self.count - self.before_read).max(0)
+        } else {
+            0
+        }
+    }
+
+    /// 既読にして、読み込んだレス数を記録する。
+    pub async fn update_is_read(db: &DbConn, thread_id: i32, before_read: i32) -> Result<()> {
         let _ = thread::Entity::update_many()
             .col_expr(thread::Column::IsRead, Expr::value(true))
+            .col_expr(thread::Column::BeforeRead, Expr::value(before_read))
             .filter(thread::Column::Id.eq(thread_id))
-            .exec(&db)
+            .exec(db)
             .await?;
 
         let updated = thread::Entity::find()
             .filter(thread::Column::Id.eq(thread_id))
-            .one(&db)
+            .one(db)
             .await?;
         info!("{:?}", updated);
         Ok(())
     }
 
-    pub async fn fetch(&self) -> Result<()> {
-        let db = establish_connection().await?;
+    /// スレッドを閉じたときの選択位置とスクロール位置を記録する。
+    pub async fn update_read_position(
+        db: &DbConn,
+        thread_id: i32,
+        index: i32,
+        offset: i32,
+    ) -> Result<()> {
+        thread::Entity::update_many()
+            .col_expr(thread::Column::LastReadIndex, Expr::value(index))
+            .col_expr(thread::Column::ScrollOffset, Expr::value(offset))
+            .filter(thread::Column::Id.eq(thread_id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// 取り込んだスレのレス数と既読の位置を記録する。
+    /// 何レス目まで読んだか分からなければ、既読の状態はそのままにする。
+    pub async fn update_imported(
+        db: &DbConn,
+        thread_id: i32,
+        count: i32,
+        read: Option<i32>,
+    ) -> Result<()> {
+        let mut update = thread::Entity::update_many()
+            .col_expr(thread::Column::Count, Expr::value(count))
+            .filter(thread::Column::Id.eq(thread_id));
+        if let Some(read) = read {
+            let read = read.clamp(0, count);
+            let index = (read - 1).max(0);
+            update = update
+                .col_expr(thread::Column::IsRead, Expr::value(true))
+                .col_expr(thread::Column::BeforeRead, Expr::value(read))
+                .col_expr(thread::Column::LastReadIndex, Expr::value(index))
+                .col_expr(thread::Column::ScrollOffset, Expr::value(index));
+        }
+        update.exec(db).await?;
+        Ok(())
+    }
+
+    pub async fn fetch(&self, db: &DbConn) -> Result<()> {
         warn!("update kidoku");
         let res = Thread::new(self.url.to_string())?.get().await?;
 
         warn!("fetched thread");
+        self.save_response(db, res).await
+    }
+
+    /// 保存しているレスの数
+    pub async fn post_count(db: &DbConn, thread_id: i32) -> Result<usize> {
+        Ok(ThreadPost::find()
+            .filter(thread_post::Column::ThreadId.eq(thread_id))
+            .count(db)
+            .await?)
+    }
+
+    /// 取得したレスを保存する。取り込んだDATもここで保存する。
+    pub async fn save_response(&self, db: &DbConn, res: ThreadResponse) -> Result<()> {
+        thread::Entity::update_many()
+            .filter(thread::Column::Id.eq(self.id))
+            .col_expr(
+                thread::Column::FetchedAt,
+                Expr::value(Utc::now().timestamp()),
+            )
+            .exec(db)
+            .await?;
+        // URLから登録したスレは、取得するまでタイトルが分からない。
+        if !res.detail.title.is_empty() && res.detail.title != self.name {
+            thread::Entity::update_many()
+                .filter(thread::Column::Id.eq(self.id))
+                .col_expr(thread::Column::Name, Expr::value(res.detail.title.clone()))
+                .exec(db)
+                .await?;
+        }
         let mut new_posts = vec![];
         for item in res.posts {
             new_posts.push(thread_post::ActiveModel {
@@ -118,8 +448,102 @@ impl ThreadStateItem {
                     .update_columns(vec![thread_post::Column::Message])
                     .to_owned(),
             )
-            .exec(&db)
+            .exec(db)
             .await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use termchan_core::url::thread::BoardParams;
+
+    use super::*;
+    use crate::{database::connect::temp_db, state::board::BoardStateItem};
+
+    fn subject(key: i64, index: i32, count: i32, ikioi: f64) -> ThreadSubject {
+        ThreadSubject {
+            index,
+            name: key.to_string(),
+            url: format!("https://egg.5ch.net/test/read.cgi/news/{}", key),
+            count,
+            ikioi,
+            ..Default::default()
+        }
+    }
+
+    async fn thread_of(db: &DbConn, key: i64) -> ThreadStateItem {
+        let url = format!("https://egg.5ch.net/test/read.cgi/news/{}", key);
+        ThreadStateItem::get_by_url(db, &url)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sync_board() {
+        let (_dir, db) = temp_db().await;
+        let board =
+            BoardStateItem::find_or_register(&db, &BoardParams::from("https://egg.5ch.net/news/"))
+                .await
+                .unwrap();
+
+        let subjects = [
+            subject(1, 1, 10, 1.0),
+            subject(2, 2, 5, 1.0),
+            subject(3, 3, 3, 1.0),
+        ];
+        let sync = ThreadStateItem::sync_board(&db, board.id, &subjects)
+            .await
+            .unwrap();
+        assert_eq!(
+            sync,
+            BoardSync {
+                new:     3,
+                updated: 0,
+                dropped: 0,
+            }
+        );
+        assert!(thread_of(&db, 1).await.is_new);
+
+        // 2は一覧より先にスレを取得して、8レスまで読んでいる。
+        ThreadStateItem::update_is_read(&db, thread_of(&db, 1).await.id, 10)
+            .await
+            .unwrap();
+        ThreadStateItem::update_is_read(&db, thread_of(&db, 2).await.id, 8)
+            .await
+            .unwrap();
+
+        let subjects = [
+            subject(2, 1, 7, 2.0),
+            subject(1, 2, 12, 3.5),
+            subject(4, 3, 1, 0.5),
+        ];
+        let sync = ThreadStateItem::sync_board(&db, board.id, &subjects)
+            .await
+            .unwrap();
+        assert_eq!(
+            sync,
+            BoardSync {
+                new:     1,
+                updated: 1,
+                dropped: 1,
+            }
+        );
+
+        let thread = thread_of(&db, 1).await;
+        assert_eq!((thread.index, thread.count, thread.ikioi), (2, 12, 3.5));
+        assert!(thread.is_read && !thread.is_new && !thread.stopdone);
+        assert!(thread_of(&db, 4).await.is_new);
+        assert!(thread_of(&db, 3).await.stopdone);
+
+        // 取得に失敗した空の一覧では、何も変えない。
+        let sync = ThreadStateItem::sync_board(&db, board.id, &[])
+            .await
+            .unwrap();
+        assert_eq!(sync, BoardSync::default());
+        assert!(thread_of(&db, 4).await.is_new);
+        assert!(!thread_of(&db, 1).await.stopdone);
+        assert!(thread_of(&db, 3).await.stopdone);
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use eyre::{bail, eyre, Result};
+use reqwest::cookie::Jar;
+use serde_json::{json, Map, Value};
+use termchan_core::{
+    export::{plain_message, ExportFormat},
+    get::{bbsmenu::Bbsmenu, board::Board, thread::Thread},
+    post::reply::post_reply,
+    url::resolve::resolve,
+};
+
+use crate::{
+    cli::{Args, Format, Subcommand},
+    config::{config::Config, credentials, secret_store},
+    database::{
+        connect::{establish_connection, resolve_db_path},
+        maintenance::{self, format_size},
+    },
+    export,
+    import,
+    state::{account::AccountStateItem, thread::ThreadStateItem},
+};
+
+/// 出力の一行分 項目の名前と値
+type Record = Map<String, Value>;
+
+/// 画面を開かずにコマンドを実行し、結果を標準出力に書く。
+pub async fn run(args: &Args, command: Subcommand) -> Result<(), Box<dyn Error>> {
+    let output = match command {
+        Subcommand::Menu { url } => menu(&url, args.format).await?,
+        Subcommand::Board { url } => board(&url, args.format).await?,
+        Subcommand::Thread { url, since } => thread(&url, since, args.format).await?,
+        Subcommand::Post {
+            url,
+            message_file,
+            name,
+            mail,
+        } => post(args, &url, &message_file, name, mail).await?,
+        Subcommand::Export {
+            url,
+            format,
+            output,
+        } => {
+            let bytes = export(args, &url, format, output.as_deref()).await?;
+            io::stdout().write_all(&bytes)?;
+            return Ok(());
+        }
+        Subcommand::Import { dir, host } => import(args, &dir, host.as_deref()).await?,
+        Subcommand::Maintenance {
+            days,
+            vacuum,
+            report,
+        } => maintenance(args, days, vacuum, report).await?,
+    };
+    print!("{}", output);
+    Ok(())
+}
+
+async fn menu(url: &str, format: Format) -> Result<String> {
+    let menu = Bbsmenu::new(url.to_string())?.get().await?;
+    let records = menu
+        .menu_list
+        .iter()
+        .flat_map(|category| {
+            category.category_content.iter().map(|board| {
+                record(json!({
+                    "category": category.category_name,
+                    "name": board.board_name,
+                    "url": board.url,
+                }))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut category = String::new();
+    Ok(render(format, &records, |r| {
+        let mut text = String::new();
+        if r["category"] != category {
+            category = str_of(&r["category"]);
+            text.push_str(&format!("[{}]\n", category));
+        }
+        text.push_str(&format!("  {}\t{}", str_of(&r["name"]), str_of(&r["url"])));
+        text
+    }))
+}
+
+async fn board(url: &str, format: Format) -> Result<String> {
+    let (board, _) = resolve(url).ok_or_else(|| eyre!("板のURLではありません。: {}", url))?;
+    let subjects = Board::new(board.build_board_url())?.get().await?;
+    let records = subjects
+        .iter()
+        .map(|subject| {
+            record(json!({
+                "index": subject.index,
+                "title": subject.name,
+                "count": subject.count,
+                "ikioi": (subject.ikioi * 100.0).round() / 100.0,
+                "created": subject.created_time.format("%Y/%m/%d %H:%M:%S").to_string(),
+                "url": subject.url,
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(render(format, &records, |r| {
+        format!(
+            "{:>4} {} ({}) {}",
+            r["index"],
+            str_of(&r["title"]),
+            r["count"],
+            r["ikioi"]
+        )
+    }))
+}
+
+async fn thread(url: &str, since: usize, format: Format) -> Result<String> {
+    let thread_url = match resolve(url) {
+        Some((_, Some(thread))) => thread.build_get(),
+        _ => bail!("スレのURLではありません。: {}", url),
+    };
+    let res = Thread::new(thread_url)?.get().await?;
+    // datとjsonで番号の数え方が違うので、並び順から振り直す。
+    let records = res
+        .posts
+        .iter()
+        .enumerate()
+        .map(|(i, post)| (i + 1, post))
+        .filter(|(number, _)| *number > since)
+        .map(|(number, post)| {
+            let date = DateTime::<Utc>::from_timestamp(post.date, 0)
+                .map(|d| d.format("%Y/%m/%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            record(json!({
+                "number": number,
+                "name": post.name,
+                "mail": post.email,
+                "date": date,
+                "id": post.post_id,
+                "message": plain_message(&post.message),
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(render(format, &records, |r| {
+        format!(
+            "{} {} [{}] {} {}\n{}\n",
+            r["number"],
+            str_of(&r["name"]),
+            str_of(&r["mail"]),
+            str_of(&r["date"]),
+            str_of(&r["id"]),
+            str_of(&r["message"]).trim_end()
+        )
+    }))
+}
+
+async fn post(
+    args: &Args,
+    url: &str,
+    message_file: &Path,
+    name: Option<String>,
+    mail: Option<String>,
+) -> Result<String> {
+    let thread = match resolve(url) {
+        Some((_, Some(thread))) => thread,
+        _ => bail!("スレのURLではありません。: {}", url),
+    };
+    let message = if message_file == Path::new("-") {
+        let mut message = String::new();
+        io::stdin().read_to_string(&mut message)?;
+        message
+    } else {
+        fs::read_to_string(message_file)?
+    };
+    if message.trim().is_empty() {
+        bail!("本文が空です。");
+    }
+
+    let config = Config::load_config()?;
+    let jar = account_jar(args, &config, &thread.build_board()).await?;
+    let result = post_reply(
+        &thread.build_get(),
+        &message,
+        name.filter(|n| !n.is_empty()),
+        mail.filter(|m| !m.is_empty()),
+        config.request_header.clone(),
+        jar,
+    )
+    .await
+    .map_err(|e| eyre!("投稿に失敗しました。: {}", e))?;
+
+    let records = vec![record(json!({
+        "url": thread.build_thread_url(),
+        "result": result,
+    }))];
+    Ok(render(args.format, &records, |r| str_of(&r["result"])))
+}
+
+/// 保存したスレを書き出した内容を返す。ファイルに書いたときは、その場所を返す。
+async fn export(
+    args: &Args,
+    url: &str,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<Vec<u8>> {
+    let thread_url = match resolve(url) {
+        Some((_, Some(thread))) => thread.build_thread_url(),
+        _ => bail!("スレのURLではありません。: {}", url),
+    };
+    let config = Config::load_config()?;
+    let db = establish_connection(&resolve_db_path(args.db_path.clone(), &config)?).await?;
+    let thread = match ThreadStateItem::get_by_url(&db, &thread_url).await? {
+        Some(thread) => thread,
+        None => bail!("保存されていないスレです。: {}", thread_url),
+    };
+    match output {
+        Some(path) => {
+            export::write_thread(&db, &thread, format, path).await?;
+            Ok(format!("{}\n", path.display()).into_bytes())
+        }
+        None => export::export_thread(&db, &thread, format, None).await,
+    }
+}
+
+/// ログのフォルダを取り込み、件数を返す。取り込めなかったファイルは標準エラーに書く。
+async fn import(args: &Args, dir: &Path, host: Option<&str>) -> Result<String> {
+    let config = Config::load_config()?;
+    let db = establish_connection(&resolve_db_path(args.db_path.clone(), &config)?).await?;
+    let summary = import::import_dir(&db, dir, host).await?;
+    for (path, reason) in &summary.skipped {
+        eprintln!("{}: {}", path.display(), reason);
+    }
+    Ok(format!(
+        "{}スレ {}レスを取り込みました。{}件は取り込めませんでした。\n",
+        summary.threads,
+        summary.posts,
+        summary.skipped.len()
+    ))
+}
+
+/// データベースを整理し、板ごとの容量を返す。整理の結果は標準エラーに書く。
+async fn maintenance(args: &Args, days: Option<u32>, vacuum: bool, report: bool) -> Result<String> {
+    let config = Config::load_config()?;
+    let db = establish_connection(&resolve_db_path(args.db_path.clone(), &config)?).await?;
+    if !report {
+        let days = days.unwrap_or(config.retention_days);
+        let cleanup = maintenance::clean(&db, days, vacuum).await?;
+        eprintln!(
+            "スレ{}件 {}レスを消し、{}小さくなりました。",
+            cleanup.threads,
+            cleanup.posts,
+            format_size(cleanup.freed.max(0))
+        );
+    }
+    eprintln!(
+        "データベースの大きさ: {}",
+        format_size(maintenance::database_size(&db).await?)
+    );
+
+    let records = maintenance::board_usage(&db)
+        .await?
+        .into_iter()
+        .map(|usage| {
+            record(json!({
+                "board": usage.name,
+                "threads": usage.threads,
+                "posts": usage.posts,
+                "bytes": usage.bytes,
+            }))
+        })
+        .collect::<Vec<_>>();
+    Ok(render(args.format, &records, |r| {
+        format!(
+            "{:>10} {:>6}スレ {:>8}レス {}",
+            format_size(r["bytes"].as_i64().unwrap_or_default()),
+            str_of(&r["threads"]),
+            str_of(&r["posts"]),
+            str_of(&r["board"])
+        )
+    }))
+}
+
+/// 画面から書き込むときと同じく、ログインする設定なら板で使うアカウントのCookieを返す。
+async fn account_jar(args: &Args, config: &Config, board_url: &str) -> Result<Option<Arc<Jar>>> {
+    if !config.login {
+        return Ok(None);
+    }
+    let db = establish_connection(&resolve_db_path(args.db_path.clone(), config)?).await?;
+    let account = match AccountStateItem::get_for_board(&db, board_url).await? {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+    secret_store::init(config.credential_backend)?;
+    Ok(Some(
+        credentials::Account::load(account.id).get_jar().await?,
+    ))
+}
+
+fn record(value: Value) -> Record {
+    match value {
+        Value::Object(map) => map,
+        _ => Map::new(),
+    }
+}
+
+fn str_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// 形式に合わせて出力を組み立てる。`text`は人が読む形で、一行ずつ`to_text`で作る。
+fn render(
+    format: Format,
+    records: &[Record],
+    mut to_text: impl FnMut(&Record) -> String,
+) -> String {
+    let mut output = String::new();
+    match format {
+        Format::Text => {
+            for r in records {
+                output.push_str(&to_text(r));
+                output.push('\n');
+            }
+        }
+        Format::Json => {
+            output.push_str(&serde_json::to_string_pretty(records).unwrap_or_default());
+            output.push('\n');
+        }
+        Format::Tsv => {
+            if let Some(first) = records.first() {
+                output.push_str(&first.keys().cloned().collect::<Vec<_>>().join("\t"));
+                output.push('\n');
+            }
+            for r in records {
+                let cells = r
+                    .values()
+                    .map(|v| {
+                        str_of(v)
+                            .replace('\\', "\\\\")
+                            .replace('\t', "\\t")
+                            .replace('\n', "\\n")
+                            .replace('\r', "")
+                    })
+                    .collect::<Vec<_>>();
+                output.push_str(&cells.join("\t"));
+                output.push('\n');
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let records = vec![
+            record(json!({"number": 1, "message": "a\tb\nc"})),
+            record(json!({"number": 2, "message": "d"})),
+        ];
+        assert_eq!(
+            render(Format::Tsv, &records, |_| String::new()),
+            "message\tnumber\na\\tb\\nc\t1\nd\t2\n"
+        );
+        assert_eq!(
+            render(Format::Text, &records, |r| format!("{}", r["number"])),
+            "1\n2\n"
+        );
+        let json: Value =
+            serde_json::from_str(&render(Format::Json, &records, |_| String::new())).unwrap();
+        assert_eq!(json[1]["message"], "d");
+    }
+}
@@ -6,7 +6,7 @@ use std::{fmt::Display, vec};
 
 use chrono::{DateTime, NaiveDateTime, Utc};
 use rayon::prelude::*;
-use termchan_core::get::message::Text;
+use termchan_core::{get::message::Text, post::validate::PostField};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
@@ -16,18 +16,31 @@ use tui::{
     widgets::{Block, BorderType, Borders, Clear, Paragraph, Tabs},
     Frame,
 };
+use tui_textarea::TextArea;
+use unicode_width::UnicodeWidthStr;
 
 use self::{
-    layout::{single_area, split_area, thread_form_area},
-    mylist::{List, ListItem},
+    layout::{board_form_area, setting_form_area, single_area, split_area, thread_form_area},
+    mylist::{List, ListItem, ListState},
     popup::draw_popup,
 };
 use crate::{
     application::App,
-    config::theme::Theme,
+    command,
+    config::{
+        keymap::{Action, Mode},
+        secret_store,
+        theme::Theme,
+    },
     state::{
+        account::AccountStateItem,
+        archive::{ArchiveStateItem, ArchiveStatus},
+        history::PostHistoryStateItem,
+        identity::IdentityStateItem,
         layout::Pane,
+        notification::NotificationStateItem,
         post::ThreadPostStateItem,
+        setting::SettingStateItem,
         tab::{LeftTabItem, TabsState},
         thread::ThreadStateItem,
     },
@@ -66,14 +79,76 @@ pub fn draw<'a, B: Backend>(f: &mut Frame<'_, B>, app: &mut App) {
                 LeftTabItem::Board(..) => {
                     draw_board_form(f, &mut app.clone(), popup_block);
                 }
+                LeftTabItem::Identities => {
+                    draw_identity_form(f, &mut app.clone(), popup_block);
+                }
+                LeftTabItem::Settings if app.editing_setting.is_some() => {
+                    draw_setting_form(f, &mut app.clone(), popup_block);
+                }
+                LeftTabItem::Settings => {
+                    draw_account_form(f, &mut app.clone(), popup_block);
+                }
                 _ => {}
             }
         }
     }
 
+    if app.show_help {
+        draw_help(f, app);
+    }
+
     draw_status_line(f, app, chunks[1]);
 }
 
+/// キーの一覧 モードごとに、キーと操作を並べる
+/// 描画で位置が変わらないよう複製した状態で描き、表示できた項目の数だけを戻す。
+fn render_list<B: Backend>(f: &mut Frame<'_, B>, list: List, area: Rect, state: &mut ListState) {
+    let mut drawn = state.clone();
+    f.render_stateful_widget(list, area, &mut drawn);
+    state.height = drawn.height;
+}
+
+fn draw_help<B: Backend>(f: &mut Frame<'_, B>, app: &App) {
+    let area = draw_popup(f);
+    let mut lines = vec![];
+    let mut current = None;
+    for (mode, action, keys) in app.keymap.help() {
+        if current != Some(mode) {
+            current = Some(mode);
+            lines.push(Spans::from(Span::styled(
+                format!("[{}]", mode),
+                Style::default()
+                    .fg(app.theme.selected)
+                    .add_modifier(Modifier::BOLD),
+            )));
+        }
+        lines.push(Spans::from(vec![
+            Span::styled(
+                format!(" {:<16}", keys.join(", ")),
+                Style::default().fg(app.theme.text),
+            ),
+            Span::raw(action.description()),
+        ]));
+    }
+    lines.push(Spans::from(Span::styled(
+        "[コマンド]",
+        Style::default()
+            .fg(app.theme.selected)
+            .add_modifier(Modifier::BOLD),
+    )));
+    for (_, description) in command::COMMANDS {
+        lines.push(Spans::from(Span::raw(format!(" :{}", description))));
+    }
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .border_type(app.theme.border_type())
+            .borders(Borders::ALL)
+            .title(" キーの一覧 (何かキーを押すと閉じる) "),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_right_panel<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
     let is_focused = app.layout.focus_pane == Pane::Main;
 
@@ -108,7 +183,7 @@ fn draw_right_panel<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
 
     f.render_widget(block, content_chunk);
 
-    draw_thread(f, &mut app.clone(), content_chunk);
+    draw_thread(f, app, content_chunk);
 }
 
 fn draw_left_panel<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -137,7 +212,15 @@ fn draw_left_panel<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
     let content_chunk = layout[1];
 
     {
-        draw_tabs(f, &mut app.theme, &mut app.left_tabs, is_focused, tab_chunk);
+        let stale_board = app.offline.stale_board;
+        draw_tabs(
+            f,
+            &mut app.theme,
+            &mut app.left_tabs,
+            is_focused,
+            tab_chunk,
+            |item| stale_board && matches!(item, LeftTabItem::Board(_)),
+        );
     }
 
     let block = Block::default()
@@ -153,6 +236,10 @@ fn draw_left_panel<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
             LeftTabItem::Categories => draw_categories(f, app, content_chunk),
             LeftTabItem::Category(_) => draw_category(f, app, content_chunk),
             LeftTabItem::Board(_) => draw_board(f, app, content_chunk),
+            LeftTabItem::Notifications => draw_notifications(f, app, content_chunk),
+            LeftTabItem::Archive => draw_archives(f, app, content_chunk),
+            LeftTabItem::History => draw_histories(f, app, content_chunk),
+            LeftTabItem::Identities => draw_identities(f, app, content_chunk),
             LeftTabItem::Settings => draw_settings(f, app, content_chunk),
         }
     }
@@ -164,6 +251,7 @@ fn draw_tabs<T: Display + Clone + Default, B: Backend>(
     tab_state: &mut TabsState<T>,
     is_active: bool,
     area: Rect,
+    is_stale: impl Fn(&T) -> bool,
 ) {
     let style = if is_active {
         Style::default().fg(theme.text).bg(theme.reset)
@@ -189,7 +277,14 @@ fn draw_tabs<T: Display + Clone + Default, B: Backend>(
         .titles
         .iter()
         .cloned()
-        .map(|t| Spans::from(format!("{}", t)))
+        .map(|t| {
+            // 取得できずに保存済みのものを表示しているタブ
+            if is_stale(&t) {
+                Spans::from(format!("{} (保存済み)", t))
+            } else {
+                Spans::from(format!("{}", t))
+            }
+        })
         .collect();
 
     let tabs = Tabs::new(titles)
@@ -228,7 +323,7 @@ fn draw_home<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
         )
         .highlight_symbol(&app.theme.active_item_symbol);
 
-    f.render_stateful_widget(list, area, &mut app.home.state.clone());
+    render_list(f, list, area, &mut app.home.state);
 }
 
 fn draw_bookmarks<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -258,7 +353,7 @@ fn draw_bookmarks<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
         )
         .highlight_symbol(&app.theme.active_item_symbol);
 
-    f.render_stateful_widget(list, area, &mut app.bookmark.state.clone());
+    render_list(f, list, area, &mut app.bookmark.state);
 }
 
 fn draw_bbsmenu<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -288,7 +383,7 @@ fn draw_bbsmenu<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
         )
         .highlight_symbol(&app.theme.active_item_symbol);
 
-    f.render_stateful_widget(list, area, &mut app.bbsmenu.state.clone());
+    render_list(f, list, area, &mut app.bbsmenu.state);
 }
 
 fn draw_categories<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -314,7 +409,7 @@ fn draw_categories<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
                 .bg(app.theme.reset),
         )
         .highlight_symbol(&app.theme.active_item_symbol);
-    f.render_stateful_widget(list, area, &mut app.categories.state.clone());
+    render_list(f, list, area, &mut app.categories.state);
 }
 
 fn draw_category<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -352,7 +447,7 @@ fn draw_category<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
                 .bg(app.theme.reset),
         )
         .highlight_symbol(&app.theme.active_item_symbol);
-    f.render_stateful_widget(list, area, &mut app.category.state.clone());
+    render_list(f, list, area, &mut app.category.state);
 }
 
 fn draw_board<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
@@ -367,7 +462,9 @@ fn draw_board<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
     let items = items
         .clone()
         .iter()
-        .map(|thread| list_item_from_board(thread.clone(), area.width as usize, false))
+        .map(|thread| {
+            list_item_from_board(thread.clone(), area.width as usize, app.config.show_index)
+        })
         .collect::<Vec<_>>();
 
     let list = List::new(items)
@@ -379,23 +476,43 @@ fn draw_board<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
         )
         .highlight_symbol(&app.theme.active_item_symbol);
 
-    f.render_stateful_widget(list, area, &mut app.board.state.clone());
+    render_list(f, list, area, &mut app.board.state);
 }
 
 fn draw_thread<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
     let block = Block::default()
         .border_type(app.theme.border_type())
         .borders(Borders::ALL)
-        .title(" Thread ")
+        .title(if app.offline.stale_thread {
+            " Thread (保存済み) "
+        } else {
+            " Thread "
+        })
         .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
 
     let posts = app.thread.items.clone();
+    let first_unread = app.first_unread;
+    let selected = (0..posts.len())
+        .map(|i| app.is_post_selected(i))
+        .collect::<Vec<_>>();
 
     let items = posts
         .par_iter()
-        .map(|post| {
-            let item = list_item_from_message(post.clone(), area.width as usize).clone();
-            item
+        .enumerate()
+        .map(|(i, post)| {
+            let is_first_unread = first_unread == Some(i) && i > 0;
+            let item = list_item_from_message(
+                post.clone(),
+                area.width as usize,
+                is_first_unread,
+                &app.theme,
+            );
+            // アンカーを付けるために選んだレス
+            if selected[i] {
+                item.style(Style::default().fg(Color::White).bg(Color::DarkGray))
+            } else {
+                item
+            }
         })
         .collect::<Vec<_>>();
 
@@ -407,26 +524,373 @@ fn draw_thread<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
                 .bg(app.theme.reset),
         )
         .highlight_symbol(&app.theme.active_item_symbol);
-    f.render_stateful_widget(list, area, &mut app.thread.state.clone());
+    render_list(f, list, area, &mut app.thread.state);
+}
+
+fn draw_notifications<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .border_type(app.theme.border_type())
+        .borders(Borders::ALL)
+        .title(" 通知 ")
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+
+    let items = app
+        .notifications
+        .items
+        .iter()
+        .map(|item| list_item_from_notification(item.clone(), &app.theme))
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.active_selected_text)
+                .bg(app.theme.reset),
+        )
+        .highlight_symbol(&app.theme.active_item_symbol);
+
+    render_list(f, list, area, &mut app.notifications.state);
+}
+
+/// 返信先のスレッド名と、返信の一行目を表示する
+fn list_item_from_notification<'a>(item: NotificationStateItem, theme: &Theme) -> ListItem<'a> {
+    let header = Spans::from(vec![
+        Span::styled(
+            format!("{} >>{} ", theme.posted_symbol, item.my_index),
+            Style::default().fg(Color::LightRed),
+        ),
+        Span::styled(item.thread_name, Style::default().fg(theme.text)),
+    ]);
+    let message = item.message.to_string();
+    let first_line = message.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    let body = Spans::from(vec![
+        Span::styled(
+            format!("  {} ", item.index),
+            Style::default().fg(Color::Blue),
+        ),
+        Span::styled(item.name, Style::default().fg(Color::Gray)),
+        Span::styled(
+            format!(" {}", first_line.trim()),
+            Style::default().fg(theme.text),
+        ),
+    ]);
+    ListItem::new(vec![header, body])
+}
+
+fn draw_archives<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .border_type(app.theme.border_type())
+        .borders(Borders::ALL)
+        .title(" アーカイブ ")
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+
+    let items = app
+        .archives
+        .items
+        .iter()
+        .map(|item| list_item_from_archive(item.clone(), &app.theme))
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.active_selected_text)
+                .bg(app.theme.reset),
+        )
+        .highlight_symbol(&app.theme.active_item_symbol);
+
+    render_list(f, list, area, &mut app.archives.state);
+}
+
+/// スレッド名と、板の名前、保存の状況を表示する
+fn list_item_from_archive<'a>(item: ArchiveStateItem, theme: &Theme) -> ListItem<'a> {
+    let status = item.status();
+    let status_color = match status {
+        ArchiveStatus::Waiting | ArchiveStatus::Updated => Color::Yellow,
+        ArchiveStatus::Saved | ArchiveStatus::Finished => Color::LightGreen,
+    };
+    let archived_at = match DateTime::<Utc>::from_timestamp(item.archived_at, 0) {
+        Some(d) if item.archived_at != 0 => d.format(" %m/%d %H:%M").to_string(),
+        _ => String::new(),
+    };
+    let header = Spans::from(Span::styled(
+        item.thread.name,
+        Style::default().fg(theme.text),
+    ));
+    let body = Spans::from(vec![
+        Span::styled(format!("  {} ", status), Style::default().fg(status_color)),
+        Span::styled(
+            format!("{}/{}", item.saved, item.thread.count),
+            Style::default().fg(Color::LightBlue),
+        ),
+        Span::styled(
+            format!(" {}{}", item.board_name, archived_at),
+            Style::default().fg(Color::Gray),
+        ),
+    ]);
+    ListItem::new(vec![header, body])
+}
+
+fn draw_histories<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .border_type(app.theme.border_type())
+        .borders(Borders::ALL)
+        .title(format!(
+            " 書き込み履歴 ({}: 下書きに戻す {}: 再送) ",
+            app.keymap.hint(Mode::Normal, Action::Enter),
+            app.keymap.hint(Mode::Normal, Action::RetryPost)
+        ))
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+
+    let items = app
+        .histories
+        .items
+        .iter()
+        .map(|item| list_item_from_history(item.clone(), &app.theme))
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.active_selected_text)
+                .bg(app.theme.reset),
+        )
+        .highlight_symbol(&app.theme.active_item_symbol);
+
+    render_list(f, list, area, &mut app.histories.state);
+}
+
+/// 成否と書き込み先、本文の一行目を表示する
+/// 失敗したものは理由も表示する
+fn list_item_from_history<'a>(item: PostHistoryStateItem, theme: &Theme) -> ListItem<'a> {
+    let date = DateTime::<Utc>::from_timestamp(item.posted_at, 0)
+        .map(|d| d.format("%Y/%m/%d %H:%M").to_string())
+        .unwrap_or_default();
+
+    let (mark, color) = if item.success {
+        ("✓", Color::LightGreen)
+    } else {
+        ("✗", Color::LightRed)
+    };
+    let target = match item.subject {
+        Some(subject) if item.is_thread => format!("[スレ立て] {}", subject),
+        _ => item.url,
+    };
+    let mut lines = vec![Spans::from(vec![
+        Span::styled(format!("{} ", mark), Style::default().fg(color)),
+        Span::styled(format!("{} ", date), Style::default().fg(Color::Gray)),
+        Span::styled(target, Style::default().fg(theme.text)),
+    ])];
+
+    let first_line = item
+        .message
+        .lines()
+        .find(|l| !l.trim().is_empty())
+        .unwrap_or("")
+        .to_string();
+    lines.push(Spans::from(Span::styled(
+        format!("  {}", first_line.trim()),
+        Style::default().fg(theme.text),
+    )));
+    if !item.success {
+        lines.push(Spans::from(Span::styled(
+            format!("  {}", item.reason),
+            Style::default().fg(Color::LightRed),
+        )));
+    }
+    ListItem::new(lines)
+}
+
+fn draw_identities<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let block = Block::default()
+        .border_type(app.theme.border_type())
+        .borders(Borders::ALL)
+        .title(" 名前 (w: 追加 Enter: 編集 d: 削除) ")
+        .title_alignment(Alignment::Center)
+        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+
+    let items = app
+        .identities
+        .items
+        .iter()
+        .map(|item| list_item_from_identity(item.clone(), &app.theme))
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.active_selected_text)
+                .bg(app.theme.reset),
+        )
+        .highlight_symbol(&app.theme.active_item_symbol);
+
+    render_list(f, list, area, &mut app.identities.state);
+}
+
+/// ラベルと、書き込んだときの名前とトリップ、メール欄を表示する
+fn list_item_from_identity<'a>(item: IdentityStateItem, theme: &Theme) -> ListItem<'a> {
+    let trip = item.trip().unwrap_or_default();
+    ListItem::new(vec![
+        Spans::from(Span::styled(item.label, Style::default().fg(theme.text))),
+        Spans::from(vec![
+            Span::styled(
+                format!("  {} ", item.name),
+                Style::default().fg(Color::White),
+            ),
+            Span::styled(trip, Style::default().fg(Color::LightGreen)),
+            Span::styled(format!(" {}", item.email), Style::default().fg(Color::Gray)),
+        ]),
+    ])
 }
 
 fn draw_settings<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
     let block = Block::default()
         .border_type(app.theme.border_type())
         .borders(Borders::ALL)
-        .title(" Settings ")
+        .title(" 設定 (Enter: 変更 Space: 切り替え w: アカウント追加 d: 削除) ")
         .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+
+    // どこに認証情報を保存しているかを一行目に出す。
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(block.inner(area));
     f.render_widget(block, area);
+    f.render_widget(
+        Paragraph::new(Span::styled(
+            format!(" {}", secret_store::describe()),
+            Style::default().fg(Color::Gray),
+        )),
+        chunks[0],
+    );
+    let area = chunks[1];
+
+    let items = app
+        .settings
+        .items
+        .iter()
+        .map(|item| list_item_from_setting(item.clone(), app))
+        .collect::<Vec<_>>();
+
+    let list = List::new(items)
+        .highlight_style(
+            Style::default()
+                .fg(app.theme.active_selected_text)
+                .bg(app.theme.reset),
+        )
+        .highlight_symbol(&app.theme.active_item_symbol);
+
+    render_list(f, list, area, &mut app.settings.state);
+}
+
+/// 項目の名前と今の値を並べる 色は見本も表示する
+fn list_item_from_setting<'a>(item: SettingStateItem, app: &App) -> ListItem<'a> {
+    let label_style = Style::default().fg(app.theme.text);
+    let value_style = Style::default().fg(Color::Gray);
+    match item {
+        SettingStateItem::Account(account) => list_item_from_account(account, &app.theme),
+        SettingStateItem::AddBbsmenuUrl | SettingStateItem::AddAccount => {
+            ListItem::new(Span::styled(
+                format!("+ {}", item.label()),
+                Style::default().fg(app.theme.hint),
+            ))
+        }
+        SettingStateItem::Color(ref name) => {
+            let color = app.config.theme.color(name).unwrap_or(Color::Reset);
+            ListItem::new(Spans::from(vec![
+                Span::styled(format!("{:<24}", item.label()), label_style),
+                Span::styled("■ ", Style::default().fg(color)),
+                Span::styled(item.value(&app.config), value_style),
+            ]))
+        }
+        SettingStateItem::RequestHeader => {
+            let lines = app.config.request_header.lines().count();
+            ListItem::new(Spans::from(vec![
+                Span::styled(format!("{:<24}", item.label()), label_style),
+                Span::styled(format!("{}行", lines), value_style),
+            ]))
+        }
+        item => {
+            ListItem::new(Spans::from(vec![
+                Span::styled(format!("{:<24}", item.label()), label_style),
+                Span::styled(item.value(&app.config), value_style),
+            ]))
+        }
+    }
+}
+
+/// ラベルとメールアドレスを表示し、既定のアカウントには印を付ける
+fn list_item_from_account<'a>(item: AccountStateItem, theme: &Theme) -> ListItem<'a> {
+    let mark = if item.is_default { "★ " } else { "  " };
+    ListItem::new(Spans::from(vec![
+        Span::styled(mark, Style::default().fg(Color::Yellow)),
+        Span::styled(format!("{} ", item.label), Style::default().fg(theme.text)),
+        Span::styled(
+            item.email.unwrap_or_default(),
+            Style::default().fg(Color::Gray),
+        ),
+    ]))
+}
+
+fn draw_account_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    draw_post_form(
+        f,
+        app,
+        area,
+        &save_form_title(" アカウント", app),
+        &app.board_textareas,
+        app.board_textareas_which,
+        vec!["ラベル", "メールアドレス", "パスワード (空なら変更しない)"],
+        vec![],
+        thread_form_area,
+    );
+}
+
+fn draw_setting_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let label = app
+        .editing_setting
+        .as_ref()
+        .map(|setting| setting.label())
+        .unwrap_or_default();
+    draw_post_form(
+        f,
+        app,
+        area,
+        &save_form_title(" 設定", app),
+        &app.board_textareas,
+        app.board_textareas_which,
+        vec![&label],
+        vec![],
+        setting_form_area,
+    );
 }
 
 fn draw_status_line<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    // `:`や`/`の入力中は入力だけを表示する。
+    if let Some(prompt) = &app.prompt {
+        let text = format!("{}{}", prompt.kind, prompt.input);
+        f.set_cursor(area.x + text.width() as u16, area.y);
+        f.render_widget(Paragraph::new(text), area);
+        return;
+    }
+
     let mut lines = vec![];
 
     // スレ一覧のときのソートメニュー
     if matches!(app.left_tabs.get_current(), LeftTabItem::Board(_)) {
         //　ソートのオーダーをここに
         lines.push(Span::styled(
-            " 並び替え: Ctrl+f ",
+            format!(
+                " 並び替え: {} ",
+                app.keymap.hint(Mode::Normal, Action::ToggleFilter)
+            ),
             Style::default().bg(Color::White).fg(Color::Black),
         ));
         lines.push(Span::styled(
@@ -435,6 +899,13 @@ fn draw_status_line<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
         ));
     }
 
+    if app.offline.enabled {
+        lines.push(Span::styled(
+            format!(" オフライン 送信待ち{}件 ", app.offline.queued),
+            Style::default().bg(Color::Yellow).fg(Color::Black),
+        ));
+    }
+
     lines.push(Span::styled(
         app.message.clone(),
         Style::default()
@@ -465,18 +936,25 @@ fn list_item_from_board<'a>(
         row_size += index.len();
     }
 
+    // dat落ちしたスレは暗くする。
+    let title_style = if thread.stopdone {
+        Style::default().fg(Color::DarkGray)
+    } else {
+        Style::default()
+    };
+
     // タイトルがスレッド一覧の横幅を超えたら折り返す
     for (_, c) in thread.name.chars().enumerate() {
         row.push(c);
         row_size += c.len_utf8();
         if row_size > width {
-            texts.push(Spans::from(format!("{}", row)));
+            texts.push(Spans::from(Span::styled(row, title_style)));
             row = String::new();
             row_size = 0;
         }
     }
 
-    texts.push(Spans::from(format!("{}", row)));
+    texts.push(Spans::from(Span::styled(row, title_style)));
 
     let naive = NaiveDateTime::from_timestamp(thread.created_time, 0);
     let date: DateTime<Utc> = DateTime::from_utc(naive, Utc);
@@ -494,6 +972,27 @@ fn list_item_from_board<'a>(
     date.push_str(format!("{:.2} {:>4}", thread.ikioi, &thread.count.to_string()).as_str());
     let is_read_label = if thread.is_read { "o " } else { "  " };
     let is_read_label = Span::styled(is_read_label, Style::default().fg(Color::LightGreen));
+    let archived_label = if thread.archived { "A " } else { "" };
+    let archived_label = Span::styled(archived_label, Style::default().fg(Color::Yellow));
+    // 前回の取得から新しく立ったスレと、一覧から消えたスレ
+    let state_label = if thread.stopdone {
+        Span::styled("落 ", Style::default().fg(Color::DarkGray))
+    } else if thread.is_new {
+        Span::styled(
+            "新 ",
+            Style::default()
+                .fg(Color::LightMagenta)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        Span::raw("")
+    };
+
+    // 前回読んだところから増えたレス数
+    let new_count = match thread.new_count() {
+        0 => Span::raw(""),
+        n => Span::styled(format!("+{} ", n), Style::default().fg(Color::LightRed)),
+    };
 
     let ikioi = Span::styled(
         format!("{:.2}", thread.ikioi),
@@ -506,6 +1005,9 @@ fn list_item_from_board<'a>(
 
     texts.push(Spans::from(vec![
         is_read_label,
+        archived_label,
+        state_label,
+        new_count,
         Span::styled(date, Style::default().fg(Color::Gray)),
         ikioi,
         count,
@@ -526,27 +1028,60 @@ fn list_item_from_board<'a>(
 
 /// draw_threadで使う
 /// スレッドアイテムをパースし、ListItemに変換する
-fn list_item_from_message<'a>(thread: ThreadPostStateItem, width: usize) -> ListItem<'a> {
+/// is_first_unreadがtrueなら区切りの代わりに新着の目印を表示する
+/// 自分のレスには印を付け、自分への返信はレス番号を強調する
+fn list_item_from_message<'a>(
+    thread: ThreadPostStateItem,
+    width: usize,
+    is_first_unread: bool,
+    theme: &Theme,
+) -> ListItem<'a> {
     let thread = thread.clone();
 
     // Spans Vector
     let mut texts = vec![];
 
     //区切りを追加
-    let mut hr = String::new();
-    for _ in 0..width {
-        hr.push('─');
+    if is_first_unread {
+        let label = " ここから新着 ";
+        let mut hr = String::new();
+        for _ in 0..width.saturating_sub(label.width()) / 2 {
+            hr.push('─');
+        }
+        texts.push(Spans::from(vec![
+            Span::styled(hr.clone(), Style::default().fg(Color::LightRed)),
+            Span::styled(label, Style::default().fg(Color::LightRed)),
+            Span::styled(hr, Style::default().fg(Color::LightRed)),
+        ]));
+    } else {
+        let mut hr = String::new();
+        for _ in 0..width {
+            hr.push('─');
+        }
+        texts.push(Spans::from(Span::styled(
+            hr,
+            Style::default().fg(Color::Gray),
+        )));
     }
-    texts.push(Spans::from(Span::styled(
-        hr,
-        Style::default().fg(Color::Gray),
-    )));
 
     //タイトルを追加
-    let mut header_spans = vec![Span::styled(
+    let mut header_spans = vec![];
+    if thread.is_mine {
+        header_spans.push(Span::styled(
+            format!("{} ", theme.posted_symbol),
+            Style::default().fg(Color::LightGreen),
+        ));
+    }
+    header_spans.push(Span::styled(
         format!("{} ", thread.index),
-        Style::default().fg(Color::Blue),
-    )];
+        if thread.is_reply_to_me {
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Blue)
+        },
+    ));
 
     header_spans.push(Span::styled(thread.name, Style::default().fg(Color::White)));
 
@@ -565,6 +1100,14 @@ fn list_item_from_message<'a>(thread: ThreadPostStateItem, width: usize) -> List
         Span::styled(thread.post_id.clone(), Style::default().fg(Color::Gray)),
     ]));
 
+    if thread.is_ng {
+        texts.push(Spans::from(Span::styled(
+            "あぼーん",
+            Style::default().fg(Color::Gray),
+        )));
+        return ListItem::new(texts).style(Style::default().fg(Color::White));
+    }
+
     let mut spans = vec![];
     for text in thread.message.text.iter() {
         use Text::*;
@@ -610,26 +1153,173 @@ fn list_item_from_message<'a>(thread: ThreadPostStateItem, width: usize) -> List
 }
 
 fn draw_thread_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+        .split(area);
+    draw_post_preview(f, app, chunks[1]);
+    draw_post_form(
+        f,
+        app,
+        chunks[0],
+        &post_form_title(" 書き込み", app),
+        &app.thread_textareas,
+        app.thread_textareas_which,
+        vec!["名前", "メール", "本文"],
+        vec![PostField::Name, PostField::Mail, PostField::Message],
+        thread_form_area,
+    );
+}
+
+/// 保存するだけのフォームのタイトル
+fn save_form_title(title: &str, app: &App) -> String {
+    format!(
+        "{} ({}: 保存) ",
+        title,
+        app.keymap.hint(Mode::Popup, Action::Post)
+    )
+}
+
+/// 書き込みに使うアカウントと、フォームの操作を並べる
+fn post_form_title(title: &str, app: &App) -> String {
+    let account = match &app.post_account {
+        Some(account) => format!(" [{}]", account.label),
+        None => String::new(),
+    };
+    let hint = |action| app.keymap.hint(Mode::Popup, action);
+    format!(
+        "{}{} ({}: 送信 {}: エディタ {}: 名前 {}: アカウント) ",
+        title,
+        account,
+        hint(Action::Post),
+        hint(Action::OpenEditor),
+        hint(Action::NextIdentity),
+        hint(Action::NextAccount)
+    )
+}
+
+fn draw_identity_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    draw_post_form(
+        f,
+        app,
+        area,
+        &save_form_title(" 名前", app),
+        &app.board_textareas,
+        app.board_textareas_which,
+        vec!["ラベル", "名前", "トリップキー", "メール"],
+        vec![],
+        board_form_area,
+    );
+}
+
+/// 書き込む前のレスをスレと同じ見た目で表示する。
+/// アンカー先のレスは読み込んでいるスレから探して下に並べる。
+fn draw_post_preview<B: Backend>(f: &mut Frame<'_, B>, app: &App, area: Rect) {
     f.render_widget(Clear, area);
     let block = Block::default()
         .border_type(BorderType::Rounded)
         .borders(Borders::ALL)
-        .title(" 書き込み (Ctrl+s) ")
+        .title(" プレビュー ")
+        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
+    let width = block.inner(area).width as usize;
+
+    let mut items = vec![];
+    if let Some(post) = app.preview_post() {
+        let anchors = post.message.anchor_indexes();
+        items.push(list_item_from_message(post, width, false, &app.theme));
+
+        let quoted = app
+            .thread
+            .items
+            .iter()
+            .filter(|p| anchors.contains(&p.index))
+            .collect::<Vec<_>>();
+        if !quoted.is_empty() {
+            items.push(ListItem::new(vec![
+                Spans::from(""),
+                Spans::from(Span::styled(" 引用先", Style::default().fg(Color::Gray))),
+            ]));
+        }
+        for post in quoted {
+            items.push(list_item_from_message(
+                post.clone(),
+                width,
+                false,
+                &app.theme,
+            ));
+        }
+    }
+
+    f.render_widget(List::new(items).block(block), area);
+}
+
+fn draw_board_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
+    draw_post_form(
+        f,
+        app,
+        area,
+        &post_form_title(" スレ立て", app),
+        &app.board_textareas,
+        app.board_textareas_which,
+        vec!["名前", "メール", "タイトル", "本文"],
+        vec![
+            PostField::Name,
+            PostField::Mail,
+            PostField::Subject,
+            PostField::Message,
+        ],
+        board_form_area,
+    );
+}
+
+/// 書き込みとスレ立てで共通のフォーム
+/// 送信前の検査で問題のある項目は枠を赤くし、問題を下に並べる。
+#[allow(clippy::too_many_arguments)]
+fn draw_post_form<B: Backend>(
+    f: &mut Frame<'_, B>,
+    app: &App,
+    area: Rect,
+    title: &str,
+    textareas: &[TextArea],
+    which: usize,
+    form_titles: Vec<&str>,
+    fields: Vec<PostField>,
+    split: fn(Rect) -> Vec<Rect>,
+) {
+    f.render_widget(Clear, area);
+    let block = Block::default()
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL)
+        .title(title.to_string())
         .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
     f.render_widget(block.clone(), area);
-    let area = thread_form_area(block.inner(area));
-    let form_titles = vec!["名前", "メール", "本文"];
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(app.post_problems.len() as u16),
+            ]
+            .as_ref(),
+        )
+        .split(block.inner(area));
+    let area = split(chunks[0]);
     let input_mode_style = Style::default().fg(Color::LightBlue).bg(Color::Black);
+    let problem_style = Style::default().fg(Color::LightRed);
 
-    for (i, ((textarea, chunk), title)) in app
-        .thread_textareas
+    let problems = app
+        .post_problems
         .iter()
-        .zip(area)
-        .zip(form_titles)
-        .enumerate()
-    {
+        .map(|p| Spans::from(Span::styled(format!(" ! {}", p), problem_style)))
+        .collect::<Vec<_>>();
+    f.render_widget(Paragraph::new(problems), chunks[1]);
+
+    for (i, ((textarea, chunk), title)) in textareas.iter().zip(area).zip(form_titles).enumerate() {
+        let has_problem = fields
+            .get(i)
+            .is_some_and(|field| app.post_problems.iter().any(|p| p.field() == *field));
         let mut textarea = textarea.clone();
-        if app.thread_textareas_which == i {
+        if which == i {
             textarea.set_cursor_line_style(Style::default().add_modifier(Modifier::UNDERLINED));
             textarea.set_cursor_style(Style::default().add_modifier(Modifier::REVERSED));
 
@@ -638,7 +1328,9 @@ fn draw_thread_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
                 .cloned()
                 .unwrap_or_else(|| Block::default().borders(Borders::ALL));
             textarea.set_block(
-                b.style(if app.input_mode {
+                b.style(if has_problem {
+                    problem_style
+                } else if app.input_mode {
                     input_mode_style
                 } else {
                     Style::default()
@@ -659,19 +1351,14 @@ fn draw_thread_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect)
                 .cloned()
                 .unwrap_or_else(|| Block::default().borders(Borders::ALL));
             textarea.set_block(
-                b.style(Style::default().fg(Color::DarkGray))
-                    .title(format!(" {} (Tab: フォーム切り替え) ", title)),
+                b.style(if has_problem {
+                    problem_style
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                })
+                .title(format!(" {} (Tab: フォーム切り替え) ", title)),
             );
             f.render_widget(textarea.widget(), chunk);
         }
     }
 }
-
-fn draw_board_form<B: Backend>(f: &mut Frame<'_, B>, app: &mut App, area: Rect) {
-    let block = Block::default()
-        .border_type(app.theme.border_type())
-        .borders(Borders::ALL)
-        .title(" new thread ")
-        .style(Style::default().fg(app.theme.text).bg(app.theme.reset));
-    f.render_widget(block, area);
-}
@@ -47,3 +47,31 @@ pub fn thread_form_area(area: Rect) -> Vec<Rect> {
 
     chunks
 }
+
+/// 設定の編集フォーム 一つだけの入力欄
+pub fn setting_form_area(area: Rect) -> Vec<Rect> {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3)].as_ref())
+        .split(area);
+
+    chunks
+}
+
+/// スレ立てフォーム 名前、メール、タイトル、本文
+pub fn board_form_area(area: Rect) -> Vec<Rect> {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Min(3),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    chunks
+}
@@ -12,6 +12,9 @@ use unicode_width::UnicodeWidthStr;
 pub struct ListState {
     pub offset: usize,
     selected:   Option<usize>,
+    /// 前回の描画で表示できた項目の数 ページ単位の移動に使う
+    #[serde(skip)]
+    pub height: usize,
 }
 
 impl ListState {
@@ -207,6 +210,7 @@ impl<'a> StatefulWidget for List<'a> {
 
         let (start, end) = self.get_items_bounds(state.selected, state.offset, list_height);
         state.offset = start;
+        state.height = end - start;
 
         let highlight_symbol = self.highlight_symbol.unwrap_or("");
         let blank_symbol = " ".repeat(highlight_symbol.width());
@@ -2,6 +2,21 @@ use serde::{Deserialize, Serialize};
 
 use super::mylist::ListState;
 
+/// 選択の移動 数は繰り返す回数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Down(usize),
+    Up(usize),
+    PageDown(usize),
+    PageUp(usize),
+    HalfPageDown(usize),
+    HalfPageUp(usize),
+    Top,
+    Bottom,
+    /// 0から数えた位置
+    Index(usize),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatefulList<T> {
     pub state:      ListState,
@@ -9,6 +24,12 @@ pub struct StatefulList<T> {
     pub loop_items: bool,
 }
 
+impl<T> Default for StatefulList<T> {
+    fn default() -> Self {
+        Self::with_items(vec![])
+    }
+}
+
 #[allow(dead_code)]
 impl<T> StatefulList<T> {
     pub fn with_items(items: Vec<T>) -> Self {
@@ -24,6 +45,9 @@ impl<T> StatefulList<T> {
         // aitem no length - 4 ni nattara select wo ugokasu
 
         let selected = self.selected();
+        if self.items.is_empty() {
+            return;
+        }
         if selected >= self.items.len() - 1 {
             if self.loop_items {
                 self.state.offset = 0;
@@ -49,7 +73,10 @@ impl<T> StatefulList<T> {
     pub fn prev(&mut self) {
         let selected = self.selected();
 
-        if self.selected() <= 0 {
+        if self.items.is_empty() {
+            return;
+        }
+        if selected == 0 {
             if self.loop_items {
                 self.state.offset = 8;
                 self.state.select(Some(self.items.len() - 1));
@@ -74,4 +101,126 @@ impl<T> StatefulList<T> {
         self.items = items;
         self.state.offset = 0;
     }
+
+    /// 位置を選択する。範囲外なら末尾を選択する。
+    /// 表示する範囲は描画のときに選択に合わせて動く。
+    pub fn select_index(&mut self, index: usize) {
+        if self.items.is_empty() {
+            return;
+        }
+        self.state.select(Some(index.min(self.items.len() - 1)));
+    }
+
+    /// 一画面に表示できる項目の数 描画前なら1
+    fn page(&self) -> usize {
+        self.state.height.max(1)
+    }
+
+    /// 一つずつ動かす回数 項目の数より多く動かしても同じなので、大きな回数でも止まらない。
+    fn steps(&self, n: usize) -> usize {
+        let len = self.items.len();
+        if self.loop_items && len > 0 {
+            n % len
+        } else {
+            n.min(len)
+        }
+    }
+
+    pub fn motion(&mut self, motion: Motion) {
+        let selected = self.selected();
+        let page = self.page();
+        let half = (page / 2).max(1);
+        match motion {
+            Motion::Down(n) => (0..self.steps(n)).for_each(|_| self.next()),
+            Motion::Up(n) => (0..self.steps(n)).for_each(|_| self.prev()),
+            Motion::PageDown(n) => {
+                self.select_index(selected.saturating_add(page.saturating_mul(n)))
+            }
+            Motion::PageUp(n) => self.select_index(selected.saturating_sub(page.saturating_mul(n))),
+            Motion::HalfPageDown(n) => {
+                self.select_index(selected.saturating_add(half.saturating_mul(n)));
+            }
+            Motion::HalfPageUp(n) => {
+                self.select_index(selected.saturating_sub(half.saturating_mul(n)));
+            }
+            Motion::Top => self.select_index(0),
+            Motion::Bottom => self.select_index(self.items.len().saturating_sub(1)),
+            Motion::Index(i) => self.select_index(i),
+        }
+    }
+
+    /// 選択の次(`forward`がfalseなら前)から探して、最初に一致した項目を選択する。
+    /// 端まで探したら反対の端から続ける。見つかればtrue
+    pub fn find<F: Fn(&T) -> bool>(&mut self, forward: bool, matches: F) -> bool {
+        let len = self.items.len();
+        let selected = self.selected();
+        let found = (1..=len)
+            .map(|i| {
+                if forward {
+                    (selected + i) % len
+                } else {
+                    (selected + len - i % len) % len
+                }
+            })
+            .find(|&i| matches(&self.items[i]));
+        match found {
+            Some(i) => {
+                self.select_index(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_motion() {
+        let mut list = StatefulList::with_items((0..100).collect::<Vec<_>>());
+        list.state.height = 20;
+        list.motion(Motion::Down(10));
+        assert_eq!(list.selected(), 10);
+        list.motion(Motion::PageDown(2));
+        assert_eq!(list.selected(), 50);
+        list.motion(Motion::HalfPageUp(1));
+        assert_eq!(list.selected(), 40);
+        list.motion(Motion::Bottom);
+        assert_eq!(list.selected(), 99);
+        list.motion(Motion::PageDown(1));
+        assert_eq!(list.selected(), 99);
+        list.motion(Motion::Up(200));
+        assert_eq!(list.selected(), 0);
+
+        // 大きな回数でも止まらず、あふれない。
+        list.motion(Motion::Down(usize::MAX));
+        assert_eq!(list.selected(), 99);
+        list.motion(Motion::HalfPageUp(usize::MAX));
+        assert_eq!(list.selected(), 0);
+        list.motion(Motion::PageDown(usize::MAX));
+        assert_eq!(list.selected(), 99);
+        list.loop_items(true).motion(Motion::Down(usize::MAX));
+        assert_eq!(list.selected(), (99 + usize::MAX % 100) % 100);
+
+        let mut empty = StatefulList::<i32>::default();
+        empty.motion(Motion::Bottom);
+        empty.motion(Motion::Up(1));
+        assert_eq!(empty.state.selected(), None);
+    }
+
+    #[test]
+    fn test_find() {
+        let mut list = StatefulList::with_items(vec!["a", "b", "a", "c"]);
+        list.select_index(0);
+        assert!(list.find(true, |s| *s == "a"));
+        assert_eq!(list.selected(), 2);
+        assert!(list.find(true, |s| *s == "a"));
+        assert_eq!(list.selected(), 0);
+        assert!(list.find(false, |s| *s == "a"));
+        assert_eq!(list.selected(), 2);
+        assert!(!list.find(true, |s| *s == "d"));
+        assert_eq!(list.selected(), 2);
+    }
 }